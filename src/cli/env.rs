@@ -64,6 +64,11 @@ define_env! {
     #[env(GEL_SERVER_DEV_DIR, EDGEDB_SERVER_DEV_DIR)]
     server_dev_dir: PathBuf,
 
+    /// Directory of instance credentials, instead of `credentials` in the
+    /// config directory
+    #[env(GEL_CREDENTIALS_DIR, EDGEDB_CREDENTIALS_DIR)]
+    credentials_dir: PathBuf,
+
     /// Whether to run version check
     #[env(GEL_RUN_VERSION_CHECK, EDGEDB_RUN_VERSION_CHECK)]
     run_version_check: VersionCheck,
@@ -72,6 +77,16 @@ define_env! {
     #[env(GEL_PAGER, EDGEDB_PAGER)]
     pager: String,
 
+    /// Error messages up to this many bytes long are printed in bold,
+    /// `0` disables it
+    #[env(GEL_ERROR_EMPHASIS_THRESHOLD, EDGEDB_ERROR_EMPHASIS_THRESHOLD)]
+    error_emphasis_threshold: usize,
+
+    /// Default maximum number of vector elements to print: a number,
+    /// `unlimited` or `auto`
+    #[env(GEL_MAX_VECTOR_LENGTH, EDGEDB_MAX_VECTOR_LENGTH)]
+    max_vector_length: crate::repl::VectorLimit,
+
     /// Debug flag for analyze JSON output
     #[env(_GEL_ANALYZE_DEBUG_JSON, _EDGEDB_ANALYZE_DEBUG_JSON)]
     _analyze_debug_json: bool,
@@ -108,6 +123,10 @@ define_env! {
     #[env(_GEL_WSL_LINUX_BINARY, _EDGEDB_WSL_LINUX_BINARY)]
     _wsl_linux_binary: PathBuf,
 
+    /// User inside the WSL distro (default `edgedb`)
+    #[env(_GEL_WSL_USER, _EDGEDB_WSL_USER)]
+    _wsl_user: String,
+
     /// Flag indicating Windows wrapper
     #[env(_GEL_FROM_WINDOWS, _EDGEDB_FROM_WINDOWS)]
     _from_windows: bool,
@@ -6,6 +6,8 @@ pub mod logo;
 pub mod migrate;
 pub mod upgrade;
 
+use crate::print;
+
 #[macro_use]
 mod markdown;
 
@@ -27,6 +29,11 @@ pub enum Subcommand {
     /// Migrate files from `~/.edgedb` to the new directory layout
     #[command(hide = true)]
     Migrate(migrate::Command),
+    /// Show whether colors are enabled and why, with a sample of every
+    /// output style
+    ColorTest,
+    /// Print the JSON schema of the output settings (`print` options)
+    PrintConfigSchema,
 }
 
 pub fn run(cmd: &Command) -> anyhow::Result<()> {
@@ -36,5 +43,14 @@ pub fn run(cmd: &Command) -> anyhow::Result<()> {
         Upgrade(s) => upgrade::run(s),
         Install(s) => install::run(s),
         Migrate(s) => migrate::run(s),
+        ColorTest => {
+            print::print_color_test();
+            Ok(())
+        }
+        PrintConfigSchema => {
+            let schema = print::settings::schema();
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            Ok(())
+        }
     }
 }
@@ -6,6 +6,7 @@ use anyhow::Context;
 use fn_error_context::context;
 use fs_err as fs;
 use indicatif::{ProgressBar, ProgressStyle};
+use once_cell::sync::OnceCell;
 
 use crate::platform::{binary_path, current_exe, home_dir, tmp_file_path};
 use crate::portable::platform;
@@ -201,10 +202,17 @@ pub fn channel() -> repository::Channel {
     channel_of(env!("CARGO_PKG_VERSION"))
 }
 
+/// Version of the running binary. Parsed once per process; concurrent
+/// first calls block on a single initialization.
 pub fn self_version() -> anyhow::Result<ver::Semver> {
-    env!("CARGO_PKG_VERSION")
-        .parse()
-        .context("cannot parse cli version")
+    static SELF_VERSION: OnceCell<ver::Semver> = OnceCell::new();
+    SELF_VERSION
+        .get_or_try_init(|| {
+            env!("CARGO_PKG_VERSION")
+                .parse()
+                .context("cannot parse cli version")
+        })
+        .cloned()
 }
 
 pub fn upgrade_to_arm64() -> anyhow::Result<()> {
@@ -1,13 +1,15 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::io;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
 use anyhow::Context;
+use const_format::concatcp;
 use fs_err as fs;
 use tokio::time::sleep;
 
-use crate::branding::BRANDING_CLOUD;
+use crate::branding::{BRANDING_CLI_CMD, BRANDING_CLOUD};
 use crate::browser::open_link;
 use crate::cloud::client::{
     CloudClient, CloudConfig, ErrorResponse, cloud_config_dir, cloud_config_file,
@@ -15,15 +17,20 @@ use crate::cloud::client::{
 use crate::cloud::options;
 use crate::cloud::secret_keys::{CreateSecretKeyInput, SecretKey};
 use crate::commands::ExitCode;
+use crate::hint::HintExt;
 use crate::options::CloudOptions;
 use crate::portable::exit_codes;
 use crate::portable::local::write_json;
 use crate::portable::project::{find_project_stash_dirs, read_project_path};
 use crate::print;
-use crate::question;
+use crate::retry::Backoff;
 
 const AUTHENTICATION_WAIT_TIME: Duration = Duration::from_secs(10 * 60);
 const AUTHENTICATION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const AUTHENTICATION_REMINDER_INTERVAL: Duration = Duration::from_secs(30);
+const SESSION_CREATE_ATTEMPTS: u32 = 4;
+const SESSION_CREATE_BACKOFF: Duration = Duration::from_secs(2);
+const SESSION_CREATE_MAX_DELAY: Duration = Duration::from_secs(60);
 
 #[derive(Debug, serde::Deserialize)]
 struct UserSession {
@@ -37,17 +44,42 @@ struct User {
     name: String,
 }
 
-pub fn login(_c: &options::Login, options: &CloudOptions) -> anyhow::Result<()> {
-    let mut client = CloudClient::new(options)?;
-    do_login(&mut client)
+/// How the login waits for the authentication in the browser
+#[derive(Debug, Clone)]
+pub struct Polling {
+    pub interval: Duration,
+    /// Number of failed checks in a row after which the login fails
+    pub max_errors: Option<u32>,
+}
+
+impl Default for Polling {
+    fn default() -> Polling {
+        Polling {
+            interval: AUTHENTICATION_POLL_INTERVAL,
+            max_errors: None,
+        }
+    }
+}
+
+pub fn login(
+    c: &options::Login,
+    options: &CloudOptions,
+    connect_timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    let mut client = CloudClient::new(options, connect_timeout)?;
+    let polling = Polling {
+        interval: c.retry_delay,
+        max_errors: c.max_poll_errors,
+    };
+    do_login(&mut client, &polling)
 }
 
 #[tokio::main(flavor = "current_thread")]
-pub async fn do_login(client: &mut CloudClient) -> anyhow::Result<()> {
-    _do_login(client).await
+pub async fn do_login(client: &mut CloudClient, polling: &Polling) -> anyhow::Result<()> {
+    _do_login(client, polling).await
 }
 
-pub async fn _do_login(client: &mut CloudClient) -> anyhow::Result<()> {
+pub async fn _do_login(client: &mut CloudClient, polling: &Polling) -> anyhow::Result<()> {
     // See if we're already logged in.
     let user_resp: anyhow::Result<User> = client.get("user").await;
 
@@ -72,68 +104,185 @@ pub async fn _do_login(client: &mut CloudClient) -> anyhow::Result<()> {
         }
     }
 
+    let session = HashMap::from([("type", "CLI")]);
+    let api: &CloudClient = client;
     let UserSession {
         id,
         auth_url,
         token: _,
-    } = client
-        .post("auth/sessions/", &HashMap::from([("type", "CLI")]))
-        .await?;
-    {
-        let link = client.api_endpoint.join(&auth_url)?.to_string();
-        let success_prompt = "Complete the authentication process now open in your browser";
-        let error_prompt = "Please paste this link into your browser to complete authentication:";
-        open_link(&link, Some(success_prompt), Some(error_prompt));
+    } = retry_throttled(SESSION_CREATE_BACKOFF, || {
+        api.post("auth/sessions/", &session)
+    })
+    .await?;
+    let link = client.api_endpoint.join(&auth_url)?.to_string();
+    let success_prompt = "Complete the authentication process now open in your browser";
+    let error_prompt = "Please paste this link into your browser to complete authentication:";
+    if open_link(&link, Some(success_prompt), Some(error_prompt)) {
+        // browsers can fail to open without an error, so show the link anyway
+        print::msg!("If your browser didn't open, paste this link into it:");
+        println!("{link}");
     }
+    let mut next_reminder = Instant::now() + AUTHENTICATION_REMINDER_INTERVAL;
+    let api: &CloudClient = client;
+    let id = &id;
+    let poll = move || async move {
+        let session: UserSession = api.get(format!("auth/sessions/{id}")).await?;
+        Ok(session.token)
+    };
+    let remind = || {
+        if Instant::now() >= next_reminder {
+            print::msg!(
+                "Still waiting for authentication... \
+                 paste this link if your browser didn't open: {link}"
+            );
+            next_reminder += AUTHENTICATION_REMINDER_INTERVAL;
+        }
+    };
+    let secret_key = wait_for_token(polling, poll, remind).await?;
+
+    // `token` is a short-lived secret key, obtain a
+    // non-expiring secret key from the secretkeys/ API now.
+    client.set_secret_key(Some(&secret_key))?;
+    let hostname = gethostname::gethostname();
+    let key: SecretKey = client
+        .post(
+            "secretkeys/",
+            &CreateSecretKeyInput {
+                name: Some(format!("CLI @ {hostname:#?}")),
+                description: None,
+                scopes: None,
+                ttl: None,
+            },
+        )
+        .await?;
+
+    write_json(
+        &cloud_config_file(&client.profile)?,
+        "cloud config",
+        &CloudConfig {
+            secret_key: key.secret_key,
+        },
+    )?;
+    client.set_secret_key(None)?;
+
+    let user: User = client.get("user").await?;
+    print::success!(
+        "Successfully logged in to {BRANDING_CLOUD} as {}.",
+        user.name
+    );
+    Ok(())
+}
+
+/// Calls `poll` every `polling.interval` until it returns the token of the
+/// authenticated session. Fails when the authentication times out or
+/// `polling.max_errors` polls in a row fail.
+async fn wait_for_token<F, Fut>(
+    polling: &Polling,
+    mut poll: F,
+    mut remind: impl FnMut(),
+) -> anyhow::Result<String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<Option<String>>>,
+{
     let deadline = Instant::now() + AUTHENTICATION_WAIT_TIME;
+    // tells "the user never finished" apart from "we couldn't ask"
+    let mut any_poll_succeeded = false;
+    let mut last_error = None;
+    let mut errors_in_row = 0;
     while Instant::now() < deadline {
-        match client.get(format!("auth/sessions/{id}")).await {
-            Ok(UserSession {
-                id: _,
-                auth_url: _,
-                token: Some(secret_key),
-            }) => {
-                // `token` is a short-lived secret key, obtain a
-                // non-expiring secret key from the secretkeys/ API now.
-                client.set_secret_key(Some(&secret_key))?;
-                let hostname = gethostname::gethostname();
-                let key: SecretKey = client
-                    .post(
-                        "secretkeys/",
-                        &CreateSecretKeyInput {
-                            name: Some(format!("CLI @ {hostname:#?}")),
-                            description: None,
-                            scopes: None,
-                            ttl: None,
-                        },
-                    )
-                    .await?;
-
-                write_json(
-                    &cloud_config_file(&client.profile)?,
-                    "cloud config",
-                    &CloudConfig {
-                        secret_key: key.secret_key,
-                    },
-                )?;
-                client.set_secret_key(None)?;
-
-                let user: User = client.get("user").await?;
-                print::success!(
-                    "Successfully logged in to {BRANDING_CLOUD} as {}.",
-                    user.name
-                );
-                return Ok(());
+        match poll().await {
+            Ok(Some(token)) => return Ok(token),
+            Ok(None) => {
+                any_poll_succeeded = true;
+                errors_in_row = 0;
+            }
+            Err(e) => {
+                errors_in_row += 1;
+                if polling.max_errors.is_some_and(|max| errors_in_row >= max) {
+                    return Err(e
+                        .context(format!(
+                            "Checking the authentication with {BRANDING_CLOUD} \
+                             failed {errors_in_row} times in a row"
+                        ))
+                        .hint(concatcp!(
+                            "Check your network connection and run `",
+                            BRANDING_CLI_CMD,
+                            " cloud login` again."
+                        )))?;
+                }
+                print::warn!("Request failed: {e:?}\nRetrying...");
+                last_error = Some(e);
             }
-            Err(e) => print::warn!("Request failed: {e:?}\nRetrying..."),
-            _ => {}
         }
-        sleep(AUTHENTICATION_POLL_INTERVAL).await;
+        remind();
+        sleep(polling.interval).await;
+    }
+    match last_error {
+        Some(e) if !any_poll_succeeded => Err(e
+            .context(format!(
+                "Could not reach {BRANDING_CLOUD} while waiting for authentication"
+            ))
+            .hint(concatcp!(
+                "Check your network connection and run `",
+                BRANDING_CLI_CMD,
+                " cloud login` again."
+            )))?,
+        _ => Err(anyhow::anyhow!(
+            "Authentication was not completed in the browser within {}.",
+            humantime::format_duration(AUTHENTICATION_WAIT_TIME)
+        )
+        .hint(concatcp!(
+            "Run `",
+            BRANDING_CLI_CMD,
+            " cloud login` again and complete the authentication in the browser."
+        )))?,
+    }
+}
+
+/// Delay before retrying a rate-limited or temporarily unavailable request,
+/// `None` if the error is not one of those.
+fn throttle_delay(err: &anyhow::Error, backoff: Duration) -> Option<Duration> {
+    match err.downcast_ref::<ErrorResponse>() {
+        Some(ErrorResponse {
+            code: reqwest::StatusCode::TOO_MANY_REQUESTS | reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            retry_after,
+            ..
+        }) => Some(retry_after.unwrap_or(backoff).min(SESSION_CREATE_MAX_DELAY)),
+        _ => None,
+    }
+}
+
+async fn retry_throttled<T, F, Fut>(backoff: Duration, f: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let backoff =
+        Backoff::new(SESSION_CREATE_ATTEMPTS, backoff).max_delay(SESSION_CREATE_MAX_DELAY);
+    let next_delay = |err: &anyhow::Error, _: u32, delay: Duration| {
+        let delay = throttle_delay(err, delay)?;
+        print::warn!(
+            "{BRANDING_CLOUD} is busy ({err}), retrying in {}...",
+            humantime::format_duration(delay)
+        );
+        Some(delay)
+    };
+    match backoff.retry(next_delay, f).await {
+        Err(err) if throttle_delay(&err, Duration::ZERO).is_some() => Err(err
+            .context(format!(
+                "{BRANDING_CLOUD} is busy, could not start authentication \
+                 after {} attempts",
+                backoff.attempts()
+            ))
+            .hint(concatcp!(
+                "Wait a few minutes and run `",
+                BRANDING_CLI_CMD,
+                " cloud login` again."
+            ))
+            .into()),
+        result => result,
     }
-    anyhow::bail!(
-        "Authentication expected to complete in {:?}.",
-        AUTHENTICATION_WAIT_TIME
-    )
 }
 
 fn find_project_dirs(f: impl Fn(&str) -> bool) -> anyhow::Result<HashMap<String, Vec<PathBuf>>> {
@@ -160,7 +309,11 @@ fn find_project_dirs(f: impl Fn(&str) -> bool) -> anyhow::Result<HashMap<String,
         .collect())
 }
 
-pub fn logout(c: &options::Logout, options: &CloudOptions) -> anyhow::Result<()> {
+pub fn logout(
+    c: &options::Logout,
+    options: &CloudOptions,
+    connect_timeout: Option<Duration>,
+) -> anyhow::Result<()> {
     let mut warnings = Vec::new();
     let mut skipped = false;
     let mut removed = false;
@@ -171,8 +324,12 @@ pub fn logout(c: &options::Logout, options: &CloudOptions) -> anyhow::Result<()>
             Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
             Err(e) => anyhow::bail!(e),
         };
-        let mut projects = find_project_dirs(|_| true)
-            .or_else(|e| if c.force { Ok(HashMap::new()) } else { Err(e) })?;
+        let mut projects = if c.ignore_project_refs {
+            HashMap::new()
+        } else {
+            find_project_dirs(|_| true)
+                .or_else(|e| if c.force { Ok(HashMap::new()) } else { Err(e) })?
+        };
         for item in dir_entries {
             let item = item?;
             let sub_dir = item.path();
@@ -184,23 +341,9 @@ pub fn logout(c: &options::Logout, options: &CloudOptions) -> anyhow::Result<()>
             let profile = stem.unwrap();
             log::debug!("Logging out from profile {:?}", profile);
             if let Some(projects) = projects.remove(profile) {
-                if !projects.is_empty() {
-                    if c.non_interactive {
-                        warnings.push((profile.to_string(), projects));
-                        if !c.force {
-                            skipped = true;
-                            continue;
-                        }
-                    } else {
-                        let q = question::Confirm::new_dangerous(format!(
-                            "{}\nStill log out?",
-                            make_project_warning(profile, projects),
-                        ));
-                        if !q.ask()? {
-                            skipped = true;
-                            continue;
-                        }
-                    }
+                if !projects.is_empty() && !confirm_logout(profile, projects, c, &mut warnings)? {
+                    skipped = true;
+                    continue;
                 }
             }
             removed = true;
@@ -208,26 +351,21 @@ pub fn logout(c: &options::Logout, options: &CloudOptions) -> anyhow::Result<()>
             print::success!("You are now logged out from {BRANDING_CLOUD} profile {profile:?}.");
         }
     } else {
-        let client = CloudClient::new(options)?;
+        let client = CloudClient::new(options, connect_timeout)?;
         let path = cloud_config_file(&client.profile)?;
         if path.exists() {
             let profile = client.profile.as_deref().unwrap_or("default");
             log::debug!("Logging out from profile {:?}", profile);
-            let projects = find_project_dirs(|p| profile == p)
-                .map(|projects| projects.into_values().flatten().collect())
-                .or_else(|e| if c.force { Ok(Vec::new()) } else { Err(e) })?;
+            let projects = if c.ignore_project_refs {
+                Vec::new()
+            } else {
+                find_project_dirs(|p| profile == p)
+                    .map(|projects| projects.into_values().flatten().collect())
+                    .or_else(|e| if c.force { Ok(Vec::new()) } else { Err(e) })?
+            };
             removed = true;
             if !projects.is_empty() {
-                if c.non_interactive {
-                    warnings.push((profile.to_string(), projects));
-                    removed = c.force;
-                } else {
-                    let q = question::Confirm::new_dangerous(format!(
-                        "{}\nStill log out?",
-                        make_project_warning(profile, projects),
-                    ));
-                    removed = q.ask()?;
-                }
+                removed = confirm_logout(profile, projects, c, &mut warnings)?;
             }
             if removed {
                 fs::remove_file(path).with_context(|| "failed to log out")?;
@@ -266,6 +404,28 @@ pub fn logout(c: &options::Logout, options: &CloudOptions) -> anyhow::Result<()>
     }
 }
 
+/// Asks whether to log out from `profile` still used by `projects`. In
+/// non-interactive mode the projects are added to `warnings` instead.
+fn confirm_logout(
+    profile: &str,
+    projects: Vec<PathBuf>,
+    c: &options::Logout,
+    warnings: &mut Vec<(String, Vec<PathBuf>)>,
+) -> anyhow::Result<bool> {
+    let details = if c.non_interactive {
+        warnings.push((profile.to_string(), projects));
+        Vec::new()
+    } else {
+        vec![make_project_warning(profile, projects)]
+    };
+    print::confirm_overwrite(
+        &format!("log out from {BRANDING_CLOUD} profile {profile:?}"),
+        &details,
+        c.force,
+        c.non_interactive,
+    )
+}
+
 fn make_project_warning(profile: &str, projects: Vec<PathBuf>) -> String {
     format!(
         "{BRANDING_CLOUD} profile {:?} is still used by the following projects:\n    {}",
@@ -277,3 +437,102 @@ fn make_project_warning(profile: &str, projects: Vec<PathBuf>) -> String {
             .join("\n    "),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use reqwest::StatusCode;
+
+    use super::{Polling, SESSION_CREATE_ATTEMPTS, retry_throttled, wait_for_token};
+    use crate::cloud::client::ErrorResponse;
+
+    fn error(code: StatusCode, retry_after: Option<Duration>) -> anyhow::Error {
+        let mut err: ErrorResponse = serde_json::from_str(r#"{"status": "error"}"#).unwrap();
+        err.code = code;
+        err.retry_after = retry_after;
+        anyhow::anyhow!(err)
+    }
+
+    #[tokio::test]
+    async fn retry_session_create() {
+        let calls = Cell::new(0);
+        let res = retry_throttled(Duration::ZERO, || {
+            calls.set(calls.get() + 1);
+            let call = calls.get();
+            async move {
+                match call {
+                    1 => Err(error(StatusCode::TOO_MANY_REQUESTS, Some(Duration::ZERO))),
+                    2 => Err(error(StatusCode::SERVICE_UNAVAILABLE, None)),
+                    _ => Ok("session"),
+                }
+            }
+        })
+        .await;
+        assert_eq!(res.unwrap(), "session");
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let res: anyhow::Result<()> = retry_throttled(Duration::ZERO, || {
+            calls.set(calls.get() + 1);
+            async { Err(error(StatusCode::TOO_MANY_REQUESTS, None)) }
+        })
+        .await;
+        assert!(res.unwrap_err().to_string().contains("attempts"));
+        assert_eq!(calls.get(), SESSION_CREATE_ATTEMPTS);
+
+        calls.set(0);
+        let res: anyhow::Result<()> = retry_throttled(Duration::ZERO, || {
+            calls.set(calls.get() + 1);
+            async { Err(error(StatusCode::UNAUTHORIZED, None)) }
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn poll_errors_threshold() {
+        let polling = Polling {
+            interval: Duration::ZERO,
+            max_errors: Some(3),
+        };
+        let calls = Cell::new(0);
+        let poll = |errors: &'static [bool]| {
+            let calls = &calls;
+            move || {
+                calls.set(calls.get() + 1);
+                let failed = errors.get(calls.get() - 1).copied();
+                async move {
+                    match failed {
+                        Some(true) => Err(error(StatusCode::BAD_GATEWAY, None)),
+                        Some(false) => Ok(None),
+                        None => Ok(Some("token".to_string())),
+                    }
+                }
+            }
+        };
+
+        // errors interrupted by a successful poll don't count together
+        let res = wait_for_token(&polling, poll(&[true, true, false, true, true]), || {}).await;
+        assert_eq!(res.unwrap(), "token");
+        assert_eq!(calls.get(), 6);
+
+        calls.set(0);
+        let res = wait_for_token(&polling, poll(&[false, true, true, true, true]), || {}).await;
+        let err = res.unwrap_err();
+        assert!(err.to_string().contains("3 times in a row"), "{err}");
+        assert_eq!(calls.get(), 4);
+
+        // by default errors are retried until the deadline
+        calls.set(0);
+        let polling = Polling {
+            interval: Duration::ZERO,
+            ..Polling::default()
+        };
+        let res = wait_for_token(&polling, poll(&[true; 10]), || {}).await;
+        assert_eq!(res.unwrap(), "token");
+        assert_eq!(calls.get(), 11);
+    }
+}
@@ -29,6 +29,9 @@ const REQUEST_RETRIES_MAX_INTERVAL: Duration = Duration::from_secs(30);
 pub struct ErrorResponse {
     #[serde(skip, default)]
     pub code: StatusCode,
+    /// Delay requested by the `Retry-After` header, if sent in seconds
+    #[serde(skip, default)]
+    pub retry_after: Option<Duration>,
     status: String,
     error: Option<String>,
 }
@@ -63,17 +66,21 @@ pub struct CloudClient {
     options_secret_key: Option<String>,
     options_profile: Option<String>,
     options_api_endpoint: Option<String>,
+    options_connect_timeout: Option<Duration>,
     pub secret_key: Option<String>,
     pub profile: Option<String>,
     pub is_default_partition: bool,
 }
 
 impl CloudClient {
-    pub fn new(options: &CloudOptions) -> anyhow::Result<Self> {
+    /// `connect_timeout` is the `--connect-timeout` given on the command
+    /// line; it limits connecting to the API, not the whole request
+    pub fn new(options: &CloudOptions, connect_timeout: Option<Duration>) -> anyhow::Result<Self> {
         Self::new_inner(
             &options.cloud_secret_key,
             &options.cloud_profile,
             &options.cloud_api_endpoint,
+            connect_timeout,
         )
     }
 
@@ -81,12 +88,9 @@ impl CloudClient {
         options_secret_key: &Option<String>,
         options_profile: &Option<String>,
         options_api_endpoint: &Option<String>,
+        options_connect_timeout: Option<Duration>,
     ) -> anyhow::Result<Self> {
-        let profile = if let Some(p) = options_profile.clone() {
-            Some(p)
-        } else {
-            Env::cloud_profile()?
-        };
+        let profile = resolve_profile(options_profile.clone(), Env::cloud_profile()?);
         let secret_key = if let Some(secret_key) = options_secret_key {
             Some(secret_key.into())
         } else if let Some(secret_key) = Env::cloud_secret_key()? {
@@ -111,6 +115,9 @@ impl CloudClient {
         };
         let mut builder =
             reqwest::Client::builder().timeout(Duration::from_secs(EDGEDB_CLOUD_API_TIMEOUT));
+        if let Some(timeout) = options_connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
         let is_logged_in;
         let dns_zone;
         if let Some(secret_key) = secret_key.clone() {
@@ -196,6 +203,7 @@ impl CloudClient {
             options_secret_key: options_secret_key.clone(),
             options_profile: options_profile.clone(),
             options_api_endpoint: options_api_endpoint.clone(),
+            options_connect_timeout,
             secret_key,
             profile,
             is_default_partition: (api_endpoint
@@ -208,6 +216,7 @@ impl CloudClient {
             &self.options_secret_key,
             &self.options_profile,
             &self.options_api_endpoint,
+            self.options_connect_timeout,
         )?;
         Ok(())
     }
@@ -238,17 +247,25 @@ impl CloudClient {
             })
         } else {
             let code = resp.status();
+            let retry_after = resp
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse().ok())
+                .map(Duration::from_secs);
             let full = resp.text().await?;
             Err(anyhow::anyhow!(
                 serde_json::from_str(&full)
                     .map(|mut e: ErrorResponse| {
                         e.code = code;
+                        e.retry_after = retry_after;
                         e
                     })
                     .unwrap_or_else(|e| {
                         log::debug!("Response body: {}", full);
                         ErrorResponse {
                             code,
+                            retry_after,
                             status: format!("error decoding response body: {e:#}"),
                             error: Some(full),
                         }
@@ -333,6 +350,12 @@ impl fmt::Display for ErrorResponse {
     }
 }
 
+/// Profile to use: `--cloud-profile`, then `GEL_CLOUD_PROFILE` (or
+/// `EDGEDB_CLOUD_PROFILE`). `None` stands for the "default" profile.
+fn resolve_profile(option: Option<String>, env: Option<String>) -> Option<String> {
+    option.or(env.filter(|profile| !profile.is_empty()))
+}
+
 pub fn cloud_config_file(profile: &Option<String>) -> anyhow::Result<PathBuf> {
     Ok(cloud_config_dir()?.join(format!("{}.json", profile.as_deref().unwrap_or("default"))))
 }
@@ -340,3 +363,22 @@ pub fn cloud_config_file(profile: &Option<String>) -> anyhow::Result<PathBuf> {
 pub fn cloud_config_dir() -> anyhow::Result<PathBuf> {
     Ok(config_dir()?.join("cloud-credentials"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{cloud_config_file, resolve_profile};
+
+    #[test]
+    fn profile_precedence() {
+        let some = |s: &str| Some(String::from(s));
+        assert_eq!(resolve_profile(some("flag"), some("env")), some("flag"));
+        assert_eq!(resolve_profile(None, some("env")), some("env"));
+        assert_eq!(resolve_profile(None, some("")), None);
+        assert_eq!(resolve_profile(None, None), None);
+
+        let default = cloud_config_file(&None).unwrap();
+        assert_eq!(default.file_name().unwrap(), "default.json");
+        let env = cloud_config_file(&resolve_profile(None, some("staging"))).unwrap();
+        assert_eq!(env.file_name().unwrap(), "staging.json");
+    }
+}
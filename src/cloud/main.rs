@@ -1,14 +1,20 @@
+use std::time::Duration;
+
 use crate::cloud::auth;
 use crate::cloud::options::CloudCommand;
 use crate::cloud::secret_keys;
 use crate::options::CloudOptions;
 
-pub fn cloud_main(cmd: &CloudCommand, options: &CloudOptions) -> anyhow::Result<()> {
+pub fn cloud_main(
+    cmd: &CloudCommand,
+    options: &CloudOptions,
+    connect_timeout: Option<Duration>,
+) -> anyhow::Result<()> {
     use crate::cloud::options::Command::*;
 
     match &cmd.subcommand {
-        Login(c) => auth::login(c, options),
-        Logout(c) => auth::logout(c, options),
-        SecretKey(c) => secret_keys::main(c, options),
+        Login(c) => auth::login(c, options, connect_timeout),
+        Logout(c) => auth::logout(c, options, connect_timeout),
+        SecretKey(c) => secret_keys::main(c, options, connect_timeout),
     }
 }
@@ -361,7 +361,7 @@ pub fn prompt_cloud_login(client: &mut CloudClient) -> anyhow::Result<()> {
         " yet, log in now?"
     ));
     if q.default(true).ask()? {
-        crate::cloud::auth::do_login(client)?;
+        crate::cloud::auth::do_login(client, &Default::default())?;
         client.reinit()?;
         client.ensure_authenticated()?;
         Ok(())
@@ -375,8 +375,9 @@ pub async fn restart_cloud_instance(
     name: &str,
     org: &str,
     options: &CloudOptions,
+    connect_timeout: Option<Duration>,
 ) -> anyhow::Result<()> {
-    let client = CloudClient::new(options)?;
+    let client = CloudClient::new(options, connect_timeout)?;
     client.ensure_authenticated()?;
     let operation: CloudOperation = client
         .post(
@@ -393,8 +394,9 @@ pub async fn destroy_cloud_instance(
     name: &str,
     org: &str,
     options: &CloudOptions,
+    connect_timeout: Option<Duration>,
 ) -> anyhow::Result<()> {
-    let client = CloudClient::new(options)?;
+    let client = CloudClient::new(options, connect_timeout)?;
     client.ensure_authenticated()?;
     let operation: CloudOperation = client
         .delete(format!("orgs/{org}/instances/{name}"))
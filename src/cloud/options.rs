@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crate::options::CloudOptions;
 
 #[derive(clap::Args, Debug, Clone)]
@@ -21,7 +23,19 @@ pub enum Command {
 }
 
 #[derive(clap::Args, Debug, Clone)]
-pub struct Login {}
+pub struct Login {
+    /// Time to wait between checks whether the authentication in the
+    /// browser is complete (e.g. '5s')
+    #[arg(long, value_name = "DELAY", default_value = "1s")]
+    #[arg(value_parser = crate::options::parse_duration)]
+    pub retry_delay: Duration,
+
+    /// Give up after this many checks in a row fail, e.g. because the
+    /// Cloud API is down. By default failed checks are retried until
+    /// the authentication times out
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_poll_errors: Option<u32>,
+}
 
 #[derive(clap::Args, Debug, Clone)]
 pub struct Logout {
@@ -34,6 +48,11 @@ pub struct Logout {
     #[arg(long)]
     pub force: bool,
 
+    /// Log out even from profiles still used by projects, without
+    /// bypassing any other checks the way `--force` does.
+    #[arg(long)]
+    pub ignore_project_refs: bool,
+
     /// Do not ask questions, assume user wants to log out of all profiles not
     /// linked to a project
     #[arg(long)]
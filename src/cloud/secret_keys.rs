@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use anyhow::Context;
 
 use crate::branding::BRANDING_CLOUD;
@@ -9,8 +11,6 @@ use crate::options::CloudOptions;
 
 use crate::portable::exit_codes;
 
-use crate::table::{self, Cell, Row, Table};
-
 use crate::print::{self, Highlight, msg};
 use crate::question;
 
@@ -38,17 +38,25 @@ pub struct CreateSecretKeyInput {
     pub ttl: Option<String>,
 }
 
-pub fn main(cmd: &SecretKeyCommand, options: &CloudOptions) -> anyhow::Result<()> {
+pub fn main(
+    cmd: &SecretKeyCommand,
+    options: &CloudOptions,
+    connect_timeout: Option<Duration>,
+) -> anyhow::Result<()> {
     use crate::cloud::options::SecretKeySubCommand::*;
     match &cmd.subcommand {
-        List(c) => list(c, options),
-        Create(c) => create(c, options),
-        Revoke(c) => revoke(c, options),
+        List(c) => list(c, options, connect_timeout),
+        Create(c) => create(c, options, connect_timeout),
+        Revoke(c) => revoke(c, options, connect_timeout),
     }
 }
 
-pub fn list(c: &options::ListSecretKeys, options: &CloudOptions) -> anyhow::Result<()> {
-    do_list(c, &CloudClient::new(options)?)
+pub fn list(
+    c: &options::ListSecretKeys,
+    options: &CloudOptions,
+    connect_timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    do_list(c, &CloudClient::new(options, connect_timeout)?)
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -69,36 +77,32 @@ pub async fn _do_list(c: &options::ListSecretKeys, client: &CloudClient) -> anyh
 }
 
 fn print_table(items: impl Iterator<Item = SecretKey>) {
-    let mut table = Table::new();
-    table.set_format(*table::FORMAT);
-    table.set_titles(Row::new(
-        ["ID", "Name", "Created", "Expires", "Scopes"]
-            .iter()
-            .map(|x| table::header_cell(x))
-            .collect(),
-    ));
-    for key in items {
-        table.add_row(Row::new(vec![
-            Cell::new(&key.id),
-            Cell::new(&key.name.unwrap_or_default()),
-            Cell::new(&humantime::format_rfc3339_seconds(key.created_on).to_string()),
-            Cell::new(
-                &key.expires_on.map_or(String::from("does not expire"), |t| {
+    let rows = items
+        .map(|key| {
+            vec![
+                key.id,
+                key.name.unwrap_or_default(),
+                humantime::format_rfc3339_seconds(key.created_on).to_string(),
+                key.expires_on.map_or(String::from("does not expire"), |t| {
                     humantime::format_rfc3339_seconds(t).to_string()
                 }),
-            ),
-            Cell::new(&key.scopes.join(", ")),
-        ]));
-    }
-    if !table.is_empty() {
-        table.printstd();
+                key.scopes.join(", "),
+            ]
+        })
+        .collect::<Vec<_>>();
+    if !rows.is_empty() {
+        print::table(&["ID", "Name", "Created", "Expires", "Scopes"], &rows).print();
     } else {
         println!("No secret keys present.")
     }
 }
 
-pub fn create(c: &options::CreateSecretKey, options: &CloudOptions) -> anyhow::Result<()> {
-    do_create(c, &CloudClient::new(options)?)
+pub fn create(
+    c: &options::CreateSecretKey,
+    options: &CloudOptions,
+    connect_timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    do_create(c, &CloudClient::new(options, connect_timeout)?)
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -162,8 +166,12 @@ pub async fn create_secret_key(
     client.post("secretkeys/", params).await
 }
 
-pub fn revoke(c: &options::RevokeSecretKey, options: &CloudOptions) -> anyhow::Result<()> {
-    do_revoke(c, &CloudClient::new(options)?)
+pub fn revoke(
+    c: &options::RevokeSecretKey,
+    options: &CloudOptions,
+    connect_timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    do_revoke(c, &CloudClient::new(options, connect_timeout)?)
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -74,6 +74,10 @@ Connection
 Settings
   \set [OPTION [VALUE]]     Show/change settings. Type \set to list
                             all available options
+  \output [FORMAT]          Show/change output format of the current
+                            language: default (native), json,
+                            json-pretty, json-lines, tab-separated,
+                            tabular (table), auto, kv or edgeql
 
 Help
   \?, \h, \help             Show help on backslash commands
@@ -548,6 +552,7 @@ pub fn get_setting(s: &Setting, prompt: &repl::State) -> Cow<'static, str> {
         DisplayTypenames(_) => bool_str(prompt.display_typenames).into(),
         ExpandStrings(_) => bool_str(prompt.print.expand_strings).into(),
         PrintStats(_) => prompt.print_stats.as_str().into(),
+        Echo(_) => bool_str(prompt.print.echo_query).into(),
     }
 }
 
@@ -649,10 +654,11 @@ pub async fn execute(
                     prompt.input_language = l.value.expect("only writes here");
                 }
                 OutputFormat(c) => {
-                    prompt.output_format = c.value.expect("only writes here");
+                    prompt.output_format = repl_output_format(c.value.expect("only writes here"))?;
                 }
                 SqlOutputFormat(c) => {
-                    prompt.sql_output_format = c.value.expect("only writes here");
+                    prompt.sql_output_format =
+                        repl_output_format(c.value.expect("only writes here"))?;
                 }
                 DisplayTypenames(b) => {
                     prompt.display_typenames = b.unwrap_value();
@@ -663,6 +669,9 @@ pub async fn execute(
                 PrintStats(v) => {
                     prompt.print_stats = v.value.expect("only writes here");
                 }
+                Echo(b) => {
+                    prompt.print.echo_query = b.unwrap_value();
+                }
             }
             Ok(Skip)
         }
@@ -738,10 +747,36 @@ pub async fn execute(
             prompt::Input::Text(text) => Ok(Input(text)),
             prompt::Input::Interrupt | prompt::Input::Eof => Ok(Skip),
         },
+        Output(c) => {
+            let format = match prompt.input_language {
+                repl::InputLanguage::EdgeQl => &mut prompt.output_format,
+                repl::InputLanguage::Sql => &mut prompt.sql_output_format,
+            };
+            if let Some(value) = c.value {
+                *format = repl_output_format(value)?;
+                eprintln!("Output format set to {}", format.as_str());
+            } else {
+                println!("{}", format.as_str());
+            }
+            Ok(Skip)
+        }
         Exit => Ok(Quit),
     }
 }
 
+fn repl_output_format(value: repl::OutputFormat) -> anyhow::Result<repl::OutputFormat> {
+    if matches!(
+        value,
+        repl::OutputFormat::Parquet | repl::OutputFormat::Msgpack
+    ) {
+        anyhow::bail!(
+            "`{}` output can only be written to a file, use `query --output-file`",
+            value.as_str()
+        );
+    }
+    Ok(value)
+}
+
 #[cfg(test)]
 mod test {
     use super::Item::{self, *};
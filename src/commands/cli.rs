@@ -1,16 +1,14 @@
-use is_terminal::IsTerminal;
-
 use crate::cli;
 use crate::cli::directory_check;
 use crate::cloud::main::cloud_main;
 use crate::commands;
-use crate::commands::parser::Common;
+use crate::commands::parser::{Common, Restore};
 use crate::migrations;
 use crate::migrations::options::{Migration, MigrationCmd as M};
 use crate::non_interactive;
 use crate::options::{Command, Options};
 use crate::portable;
-use crate::print::style::Styler;
+use crate::print::{self, style::Styler};
 use crate::watch;
 
 #[tokio::main(flavor = "current_thread")]
@@ -26,8 +24,19 @@ async fn common_cmd(
 pub fn main(options: &Options) -> Result<(), anyhow::Error> {
     match options.subcommand.as_ref().expect("subcommand is present") {
         Command::Common(cmd) => {
-            let cmdopt = init_command_opts(options)?;
             directory_check::check_and_warn();
+            if let Common::Restore(
+                params @ Restore {
+                    create_instance: Some(name),
+                    ..
+                },
+            ) = cmd
+            {
+                // the instance doesn't exist yet, so there is nothing to
+                // connect to before it is created
+                return commands::restore_into_new_instance(options, params, name);
+            }
+            let cmdopt = init_command_opts(options)?;
             match cmd.as_migration() {
                 // Process commands that don't need connection first
                 Some(Migration {
@@ -71,7 +80,11 @@ pub fn main(options: &Options) -> Result<(), anyhow::Error> {
         Command::Cli(c) => cli::run(c),
         Command::Info(info) => commands::info(options, info),
         Command::UI(c) => commands::show_ui(c, options),
-        Command::Cloud(c) => cloud_main(c, &options.cloud_options),
+        Command::Cloud(c) => cloud_main(
+            c,
+            &options.cloud_options,
+            options.conn_options.connect_timeout,
+        ),
         Command::Watch(c) => watch::run(options, c),
         Command::HashPassword(cmd) => {
             println!("{}", portable::password_hash(&cmd.password_to_hash));
@@ -83,7 +96,7 @@ pub fn main(options: &Options) -> Result<(), anyhow::Error> {
 fn init_command_opts(options: &Options) -> Result<commands::Options, anyhow::Error> {
     Ok(commands::Options {
         command_line: true,
-        styler: if std::io::stdout().is_terminal() {
+        styler: if print::use_color() {
             Some(Styler::new())
         } else {
             None
@@ -43,5 +43,5 @@ pub use self::list_roles::list_roles;
 pub use self::list_scalar_types::list_scalar_types;
 pub use self::options::Options;
 pub use self::psql::psql;
-pub use self::restore::{restore, restore_all};
+pub use self::restore::{restore, restore_all, restore_into_new_instance};
 pub use self::ui::show_ui;
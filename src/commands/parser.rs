@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::branding::BRANDING_CLI_CMD;
 use crate::migrations::options::Migration;
@@ -167,6 +168,7 @@ pub enum BackslashCmd {
     Connect(Connect),
     Edit(Edit),
     Set(SetCommand),
+    Output(OutputFormat),
     Exit,
 }
 
@@ -201,7 +203,8 @@ pub enum Setting {
     /// Set maximum number of elements to display for ext::pgvector::vector type.
     ///
     /// Defaults to `auto` which displays whatever fits a single line, but no less
-    /// than 3. Can be set to `unlimited` or a fixed number.
+    /// than 3. Can be set to `unlimited`, a fixed number, or `HEAD..TAIL` to
+    /// display the first HEAD and last TAIL elements and the dimension count.
     VectorDisplayLength(VectorLimitValue),
     /// Set output format
     OutputFormat(OutputFormat),
@@ -215,6 +218,8 @@ pub enum Setting {
     HistorySize(SettingUsize),
     /// Print statistics on each query
     PrintStats(PrintStats),
+    /// Print each query, syntax highlighted, above its result
+    Echo(SettingBool),
     /// Set idle transaction timeout in Duration format.
     /// Default is 5 minutes; specify 0 to disable.
     IdleTransactionTimeout(IdleTransactionTimeout),
@@ -403,6 +408,9 @@ pub struct Dump {
     pub overwrite_existing: bool,
 }
 
+/// `restore --read-ahead` unless given: blocks are read on demand
+pub const DEFAULT_READ_AHEAD: u32 = 0;
+
 #[derive(clap::Args, Clone, Debug)]
 #[command(override_usage(concatcp!(
     BRANDING_CLI_CMD, " restore [OPTIONS] <path>\n    \
@@ -414,18 +422,172 @@ pub struct Restore {
     pub conn: Option<ConnectionOptions>,
 
     /// Path to file (or directory in case of `--all`) to read dump from.
-    /// Use dash `-` to read from stdin
+    /// Use dash `-` to read from stdin, or an
+    /// `sftp://[user@]host[:port]/path` URL to read from a remote host by
+    /// running `ls` and `cat` there through the `ssh` command,
+    /// authenticating with an SSH agent or keys
     #[arg(value_hint=clap::ValueHint::AnyPath)]
     pub path: PathBuf,
 
     /// Restore all databases and server configuration. `path` is a
-    /// directory in this case
+    /// directory in this case. A database dumped in several parts named
+    /// `<name>.000.dump`, `<name>.001.dump`, ... is restored from all
+    /// parts in order. Databases are restored in name order, or in the
+    /// order given by a `manifest.txt` in the directory, one database
+    /// name per line (unlisted ones follow by name). The server
+    /// configuration is restored from `init.edgeql`, or from the `*.edgeql`
+    /// files of an `init/` subdirectory in name order if there is one.
+    /// `path` may also be a `.tar`, `.tar.gz` or `.tgz` archive of such a
+    /// directory, which is read without extracting it: `init.edgeql` must
+    /// come first and the `*.dump` files are restored one at a time in
+    /// archive order
     #[arg(long)]
     pub all: bool,
 
+    /// Restore the dump into each of these databases (or branches),
+    /// separated by commas, instead of the one connected to. Missing ones
+    /// are created. The dump file is read again for every target; a dump
+    /// read from stdin is held in memory instead. A failed target doesn't
+    /// stop the others, the exit code is 7 if any of them failed
+    #[arg(long, value_name = "name,...", value_delimiter = ',')]
+    #[arg(conflicts_with_all = ["all", "dry_run", "create_instance"])]
+    pub into: Vec<String>,
+
     /// Verbose output
     #[arg(long, short = 'v')]
     pub verbose: bool,
+
+    /// Also append a timestamped log of the restore to this file: the
+    /// databases started and finished with their sizes, retries, errors,
+    /// and the blocks restored as `--verbose` shows them
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Do not show progress while restoring
+    #[arg(long)]
+    pub no_progress: bool,
+
+    /// Progress message to show in place of "Restoring database: ...".
+    /// `{read}` is replaced with the size of the dump read so far and
+    /// `{total}` with the size of the dump, or `unknown` if it isn't known
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "no_progress")]
+    pub progress_template: Option<String>,
+
+    /// With `--all`, retry restoring a database up to this many times if
+    /// the connection is lost midway. Databases created by this restore
+    /// are dropped and re-created before retrying.
+    #[arg(long, default_value = "0")]
+    pub retries: u32,
+
+    /// With `--all`, restore up to this many databases concurrently. Each
+    /// job holds its own connection, so the number of jobs is capped below
+    /// the server's connection limit with a warning, and lowered further
+    /// if the limit is reached anyway, e.g. by other clients. Progress
+    /// bars are disabled when more than one job is used
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    pub jobs: u32,
+
+    /// Read up to this many blocks of the dump ahead of the server, so
+    /// that reading and decompressing the dump overlaps with the server
+    /// applying the previous blocks. Each block read ahead is held in
+    /// memory; by default a block is read only once the server asks for it
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_READ_AHEAD)]
+    pub read_ahead: u32,
+
+    /// With `--all`, remove this prefix from the database names taken
+    /// from the dump file names. Names without the prefix are kept
+    #[arg(long, value_name = "prefix")]
+    pub strip_prefix: Option<String>,
+
+    /// With `--all`, prepend this prefix to the database names taken from
+    /// the dump file names, after `--strip-prefix` is applied
+    #[arg(long, value_name = "prefix")]
+    pub add_prefix: Option<String>,
+
+    /// With `--all`, only warn instead of failing when the directory
+    /// contains no `.dump` files
+    #[arg(long)]
+    pub allow_empty: bool,
+
+    /// With `--all`, stop at the first init script, connection or database
+    /// error. This is the default
+    #[arg(long, requires = "all", overrides_with = "no_fail_fast")]
+    pub fail_fast: bool,
+
+    /// With `--all`, keep restoring the other databases after an error and
+    /// list the errors at the end. The exit code is 7 if anything failed
+    #[arg(long, requires = "all", overrides_with = "fail_fast")]
+    pub no_fail_fast: bool,
+
+    /// Skip `CONFIGURE INSTANCE` (and `CONFIGURE SYSTEM`) statements of the
+    /// init script, e.g. when the instance configuration is managed
+    /// elsewhere. `CONFIGURE CURRENT DATABASE` statements are still applied
+    #[arg(long, requires = "all")]
+    pub skip_config: bool,
+
+    /// Rewrite the statements of the init script before applying them,
+    /// e.g. `--init-sed 'prod\.example\.com=staging.example.com'`. The
+    /// part before the first `=` is a regular expression, the rest is the
+    /// replacement, which can refer to groups as `$1`. Can be repeated,
+    /// substitutions are applied in order. A statement broken by a
+    /// substitution, e.g. by an unbalanced quote, fails the restore
+    #[arg(long, value_name = "PATTERN=REPLACEMENT", requires = "all")]
+    #[arg(value_parser = crate::commands::restore::parse_init_sed)]
+    pub init_sed: Vec<crate::commands::restore::InitSubstitution>,
+
+    /// Create a new local instance with this name and restore into it.
+    /// The instance is destroyed again if the restore fails
+    #[arg(long, value_name = "name")]
+    pub create_instance: Option<String>,
+
+    /// Check the target and read the whole dump, then print what would be
+    /// restored (and which databases created with `--all`) without
+    /// changing anything
+    #[arg(long, conflicts_with = "create_instance")]
+    pub dry_run: bool,
+
+    /// List the databases of the dump directory, marking the ones that
+    /// already exist on the target, and exit without restoring
+    #[arg(long, requires = "all")]
+    pub list_databases: bool,
+
+    /// Print `--list-databases` as JSON
+    #[arg(long, requires = "list_databases")]
+    pub json: bool,
+
+    /// After restoring, count the objects of every non-abstract object
+    /// type outside the standard library (`SELECT count(Type)`, which
+    /// includes objects of subtypes) and print the counts. Dumps do not
+    /// record object counts, so compare them with the source yourself
+    #[arg(long)]
+    pub verify_after: bool,
+
+    /// Run this query instead of counting objects with `--verify-after`,
+    /// and print its results
+    #[arg(long, value_name = "query", requires = "verify_after")]
+    pub verify_query: Option<String>,
+
+    /// Fail instead of warning when the `--verify-after` query fails
+    #[arg(long, requires = "verify_after")]
+    pub strict_verify: bool,
+
+    /// After restoring, refresh the query planner statistics of the
+    /// database with `administer statistics_update()`. A server that
+    /// doesn't support the statement prints a warning and is skipped
+    #[arg(long)]
+    pub analyze_after: bool,
+
+    /// Give up if checking that the target database is empty takes longer
+    /// than TIMEOUT (e.g. '1m'). Defaults to the connect timeout if one is
+    /// set, 30 seconds otherwise
+    #[arg(long, value_name = "TIMEOUT", value_parser = crate::options::parse_duration)]
+    pub empty_check_timeout: Option<Duration>,
+
+    /// Give up if restoring a database takes longer than TIMEOUT (e.g.
+    /// '2h'), leaving it partially restored. With `--all` the limit applies
+    /// to each database separately
+    #[arg(long, value_name = "TIMEOUT", value_parser = crate::options::parse_duration)]
+    pub timeout: Option<Duration>,
 }
 
 #[derive(clap::Args, Clone, Debug)]
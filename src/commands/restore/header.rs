@@ -0,0 +1,287 @@
+//! Reading the dump format: the magic, the version and the packets.
+
+use std::fmt;
+use std::pin::Pin;
+use std::str;
+use std::task::{Context, Poll};
+
+use anyhow::Context as _;
+use async_fn_stream::TryStreamEmitter;
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::StreamExt;
+use sha1::Digest;
+use tokio::io::{self, AsyncRead, AsyncReadExt};
+use tokio_stream::Stream;
+
+use gel_errors::{ClientError, Error, ErrorKind};
+
+use crate::commands::restore::RestoreError;
+use crate::print;
+
+const MAX_SUPPORTED_DUMP_VER: i64 = 1;
+pub(super) const DUMP_MAGIC: &[u8; 17] = b"\xFF\xD8\x00\x00\xD8EDGEDB\x00DUMP\x00";
+
+/// Kind of a packet of a dump: a single header followed by data blocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PacketType {
+    Header,
+    Block,
+}
+
+/// Splits a dump into packets without reading the whole input in advance.
+///
+/// Only the block being assembled (plus whatever the last read returned
+/// past its end) is buffered, and memory is reused once the previous block
+/// is dropped by the consumer. Blocks are sent to the server as single
+/// messages, so a pathologically large block still has to be held in
+/// memory in full: peak usage is about twice the largest block.
+pub struct Packets {
+    input: Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>>,
+}
+
+async fn packet_generator(
+    emitter: TryStreamEmitter<Bytes, Error>,
+    mut input: impl AsyncRead + Unpin + Send + 'static,
+) -> Result<(), Error> {
+    const HEADER_LEN: usize = 1 + 20 + 4;
+    let mut buf = BytesMut::with_capacity(65536);
+    let mut packet_index = 0;
+
+    'outer: loop {
+        while buf.len() < HEADER_LEN {
+            buf.reserve(HEADER_LEN);
+            let n = input
+                .read_buf(&mut buf)
+                .await
+                .context("Cannot read packet header")?;
+            if n == 0 {
+                // EOF
+                if buf.is_empty() {
+                    break 'outer;
+                } else {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+                        .context("Cannot read packet header")?;
+                }
+            }
+        }
+
+        let expected = if packet_index == 0 {
+            PacketType::Header
+        } else {
+            PacketType::Block
+        };
+
+        let packet_type = match buf[0] {
+            b'H' => PacketType::Header,
+            b'D' => PacketType::Block,
+            _ => {
+                return Err(io::Error::from(io::ErrorKind::InvalidData))
+                    .context(format!("Invalid block type {:x}", buf[0]))?;
+            }
+        };
+
+        if packet_type != expected {
+            return Err(io::Error::from(io::ErrorKind::InvalidData)).context(format!(
+                "Expected type {expected:?}, got {packet_type:?} at packet {packet_index}"
+            ))?;
+        }
+
+        let len = u32::from_be_bytes(buf[1 + 20..][..4].try_into().unwrap()) as usize;
+
+        // reserve exactly one block rather than rounding up, so reads can't
+        // run far ahead of the block being assembled
+        if buf.len() < HEADER_LEN + len {
+            buf.reserve(HEADER_LEN + len - buf.len());
+        }
+
+        while buf.len() < HEADER_LEN + len {
+            let read = input
+                .read_buf(&mut buf)
+                .await
+                .with_context(|| format!("Error reading block of {len} bytes"))?;
+            if read == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+                    .with_context(|| format!("Error reading block of {len} bytes"))?;
+            }
+        }
+
+        let hash: [u8; 20] = buf[1..][..20].try_into().unwrap();
+        let block = buf.split_to(HEADER_LEN + len).split_off(HEADER_LEN);
+        if sha1::Sha1::digest(&block)[..] != hash {
+            return Err(ClientError::with_source(RestoreError::BlockHashMismatch {
+                packet: packet_index,
+            }));
+        }
+        emitter.emit(block.freeze()).await;
+
+        _ = buf.try_reclaim(len);
+        packet_index += 1;
+    }
+
+    Ok(())
+}
+
+impl Packets {
+    /// Reads packets from `input`, positioned after the dump magic and
+    /// version, see [`DumpHeader::read`] to read a whole dump. Yields the
+    /// payload of each packet; the header packet comes first.
+    pub(super) fn new(input: impl AsyncRead + Unpin + Send + 'static) -> Self {
+        Packets {
+            input: Box::pin(async_fn_stream::try_fn_stream(move |emitter| {
+                packet_generator(emitter, input)
+            })),
+        }
+    }
+}
+
+impl Stream for Packets {
+    type Item = Result<Bytes, Error>;
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Error>>> {
+        self.input.poll_next_unpin(cx)
+    }
+}
+
+/// The start of a dump: the format version and the header packet, which
+/// holds the dump attributes and the schema.
+pub struct DumpHeader {
+    pub version: i64,
+    pub header: Bytes,
+}
+
+impl DumpHeader {
+    /// Reads and checks the start of a dump. Returns the header and the
+    /// data blocks that follow it, so a dump can be inspected and then
+    /// restored from the same input.
+    pub async fn read(
+        mut input: impl AsyncRead + Unpin + Send + 'static,
+    ) -> anyhow::Result<(DumpHeader, Packets)> {
+        let mut buf = [0u8; 17 + 8];
+        buf[0] = skip_magic_prefix(&mut input).await?;
+        input
+            .read_exact(&mut buf[1..])
+            .await
+            .context("Cannot read header")?;
+        if &buf[..17] != DUMP_MAGIC {
+            Err(RestoreError::BadMagic)?
+        }
+        let version = i64::from_be_bytes(buf[17..].try_into().unwrap());
+        if version == 0 || version > MAX_SUPPORTED_DUMP_VER {
+            Err(RestoreError::UnsupportedVersion {
+                found: version,
+                max: MAX_SUPPORTED_DUMP_VER,
+            })?
+        }
+        let mut packets = Packets::new(input);
+        let header = packets.next().await.ok_or(RestoreError::EmptyDump)??;
+        Ok((DumpHeader { version, header }, packets))
+    }
+
+    /// Attributes of the dump, see [`decode_header`]
+    pub fn fields(&self) -> Option<Vec<(String, String)>> {
+        decode_header(&self.header)
+    }
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+/// Most whitespace bytes skipped before the dump magic
+const MAX_WHITESPACE_PREFIX: usize = 16;
+
+/// Skips a UTF-8 BOM and whitespace that some storage gateways add in
+/// front of files, and returns the first byte after them. Anything else is
+/// left for the magic check to reject.
+async fn skip_magic_prefix(input: &mut (impl AsyncRead + Unpin)) -> anyhow::Result<u8> {
+    async fn read_byte(input: &mut (impl AsyncRead + Unpin)) -> anyhow::Result<u8> {
+        input.read_u8().await.context("Cannot read header")
+    }
+    let mut byte = read_byte(input).await?;
+    let mut bom = false;
+    if byte == UTF8_BOM[0] {
+        for &expected in &UTF8_BOM[1..] {
+            if read_byte(input).await? != expected {
+                return Err(RestoreError::BadMagic.into());
+            }
+        }
+        bom = true;
+        byte = read_byte(input).await?;
+    }
+    let mut whitespace = 0;
+    while byte.is_ascii_whitespace() && whitespace < MAX_WHITESPACE_PREFIX {
+        whitespace += 1;
+        byte = read_byte(input).await?;
+    }
+    match (bom, whitespace) {
+        (false, 0) => {}
+        (true, 0) => print::warn!("Skipped a UTF-8 BOM before the dump header"),
+        (false, n) => print::warn!("Skipped {n} whitespace bytes before the dump header"),
+        (true, n) => {
+            print::warn!("Skipped a UTF-8 BOM and {n} whitespace bytes before the dump header")
+        }
+    }
+    Ok(byte)
+}
+
+/// Logs the attributes and protocol version of the dump header to `log`.
+/// The rest of the header (schema and type descriptors) is only
+/// summarized.
+pub(super) fn log_header(header: &DumpHeader, mut log: impl FnMut(fmt::Arguments)) {
+    match header.fields() {
+        Some(fields) => {
+            for (name, value) in fields {
+                log(format_args!("Dump header {name}: {value}"));
+            }
+        }
+        None => log(format_args!(
+            "Dump header: {} bytes, cannot decode",
+            header.header.len()
+        )),
+    }
+}
+
+fn decode_header(mut data: &[u8]) -> Option<Vec<(String, String)>> {
+    fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        let (head, tail) = data.split_at_checked(len)?;
+        *data = tail;
+        Some(head)
+    }
+    fn take_u16(data: &mut &[u8]) -> Option<u16> {
+        Some(u16::from_be_bytes(take(data, 2)?.try_into().ok()?))
+    }
+    fn take_bytes<'a>(data: &mut &'a [u8]) -> Option<&'a [u8]> {
+        let len = u32::from_be_bytes(take(data, 4)?.try_into().ok()?);
+        take(data, len as usize)
+    }
+
+    let mut fields = Vec::new();
+    for _ in 0..take_u16(&mut data)? {
+        let code = take_u16(&mut data)?;
+        let value = take_bytes(&mut data)?;
+        let name = match code {
+            101 => "block type",
+            102 => "server time",
+            103 => "server version",
+            104 => "blocks info",
+            105 => "catalog version",
+            _ => {
+                fields.push((
+                    format!("attribute {code}"),
+                    format!("{} bytes", value.len()),
+                ));
+                continue;
+            }
+        };
+        let value = match str::from_utf8(value) {
+            Ok(text) => text.to_string(),
+            Err(_) => format!("{} bytes", value.len()),
+        };
+        fields.push((name.to_string(), value));
+    }
+    let major = take_u16(&mut data)?;
+    let minor = take_u16(&mut data)?;
+    fields.push(("protocol".into(), format!("{major}.{minor}")));
+    let schema = take_bytes(&mut data)?;
+    fields.push(("schema DDL".into(), format!("{} bytes", schema.len())));
+    Some(fields)
+}
@@ -0,0 +1,86 @@
+//! Concurrency of `restore --all --jobs`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::commands::restore::LOG_TARGET;
+use crate::connect::Connection;
+
+/// Postgres `max_connections` of the server, which all connections to the
+/// instance count against
+const CONNECTION_LIMIT_QUERY: &str =
+    "SELECT assert_single(cfg::InstanceConfig.__pg_max_connections)";
+
+/// Bounds the number of databases `restore --all --jobs` restores at once.
+///
+/// Every job holds a server connection. The number of jobs starts within
+/// the server's connection limit, see [`jobs_within_limit`], and is
+/// lowered (down to one) whenever the server refuses a connection because
+/// the limit is reached anyway, e.g. by other clients.
+pub(super) struct JobLimiter {
+    semaphore: Semaphore,
+    jobs: AtomicUsize,
+}
+
+impl JobLimiter {
+    pub(super) fn new(jobs: usize) -> JobLimiter {
+        let jobs = jobs.max(1);
+        JobLimiter {
+            semaphore: Semaphore::new(jobs),
+            jobs: AtomicUsize::new(jobs),
+        }
+    }
+    pub(super) fn jobs(&self) -> usize {
+        self.jobs.load(Ordering::Relaxed)
+    }
+    pub(super) async fn acquire(&self) -> SemaphorePermit<'_> {
+        self.semaphore
+            .acquire()
+            .await
+            .expect("semaphore is never closed")
+    }
+    /// Gives up the job slot held by `permit` for good. The last slot is
+    /// never removed.
+    pub(super) fn shrink(&self, permit: SemaphorePermit<'_>) {
+        let shrunk = self
+            .jobs
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |jobs| {
+                jobs.checked_sub(1).filter(|&jobs| jobs > 0)
+            });
+        if shrunk.is_ok() {
+            permit.forget();
+        }
+    }
+}
+
+/// Connection limit of the server, or `None` if it can't be read, e.g.
+/// without the permission to read the instance config.
+pub(super) async fn connection_limit(cli: &mut Connection) -> Option<usize> {
+    match cli
+        .query_single::<i64, _>(CONNECTION_LIMIT_QUERY, &())
+        .await
+    {
+        Ok((limit, _)) => limit.and_then(|limit| usize::try_from(limit).ok()),
+        Err(e) => {
+            log::info!(target: LOG_TARGET, "Cannot read the server connection limit: {e:#}");
+            None
+        }
+    }
+}
+
+/// Number of jobs to start `restore --all --jobs` with: at most one less
+/// than the connection `limit`, as `restore` keeps a connection of its own
+/// open, but at least one.
+pub(super) fn jobs_within_limit(jobs: usize, limit: usize) -> usize {
+    jobs.min(limit.saturating_sub(1)).max(1)
+}
+
+pub(super) fn is_connection_limit(err: &anyhow::Error) -> bool {
+    // there is no dedicated error code, so match both the server's and
+    // postgres' wording of the error
+    err.chain().any(|e| {
+        let msg = e.to_string().to_lowercase();
+        msg.contains("too many connections") || msg.contains("too many clients")
+    })
+}
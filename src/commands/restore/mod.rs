@@ -0,0 +1,1974 @@
+mod header;
+mod limiter;
+mod progress;
+mod sftp;
+#[cfg(test)]
+mod tests;
+mod transport;
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use bytes::{Bytes, BytesMut};
+use fn_error_context::context;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use indicatif::HumanBytes;
+use tokio::fs;
+use tokio::io::{self, AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc;
+
+use edgeql_parser::helpers::quote_name;
+use edgeql_parser::preparser::is_empty;
+use edgeql_parser::tokenizer::Tokenizer;
+use gel_dsn::gel::DatabaseBranch;
+use gel_errors::{ClientConnectionError, Error, QueryError};
+use gel_protocol::value::Value;
+use gel_tokio::Builder;
+
+use crate::branding::{BRANDING, BRANDING_CLI_CMD, QUERY_TAG};
+use crate::commands::list_databases;
+use crate::commands::parser::Restore as RestoreCmd;
+use crate::commands::{ExitCode, Options};
+use crate::connect::{Connection, Connector};
+use crate::credentials;
+use crate::hint::HintExt;
+use crate::portable::exit_codes;
+use crate::portable::instance::{create, destroy};
+use crate::portable::options::{CloudInstanceBillables, CloudInstanceParams, InstanceName};
+use crate::portable::ver;
+use crate::print::{self, Highlight, msg};
+use crate::retry::Backoff;
+use crate::statement::{EndOfFile, read_statement};
+use crate::table::{self, Row, Table};
+
+use self::header::{DumpHeader, log_header};
+use self::limiter::{JobLimiter, connection_limit, is_connection_limit, jobs_within_limit};
+use self::progress::{
+    CountingReader, StreamWithProgress, count_blocks, log_blocks, progress_bar, render_template,
+};
+use self::transport::{
+    Blocks, dir_file, open_dump_bytes, open_dump_parts, open_file, read_ahead_blocks,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RestoreError {
+    #[error("cannot restore: the database is not empty")]
+    NonEmptyDatabase,
+    #[error("Incorrect header; file is not a dump from {}", BRANDING)]
+    BadMagic,
+    #[error("Unsupported dump version {found}")]
+    UnsupportedVersion { found: i64, max: i64 },
+    #[error("Dump is empty")]
+    EmptyDump,
+    #[error("Checksum mismatch in packet {packet}; the dump file is corrupted")]
+    BlockHashMismatch { packet: usize },
+    #[error("{} is not a dump directory: `init.edgeql` is missing", dir.display())]
+    MissingInit { dir: PathBuf },
+    #[error("No `.dump` files found in {}", dir.display())]
+    NoDumps { dir: PathBuf },
+    #[error("could not verify target is empty (timed out after {})",
+            humantime::format_duration(*timeout))]
+    EmptyCheckTimeout { timeout: Duration },
+    #[error("Dump part {} has a different header than the first part", part.display())]
+    PartHeaderMismatch { part: PathBuf },
+    #[error("Part {part} of the dump of database {database:?} is missing")]
+    MissingPart { database: String, part: u32 },
+    #[error("{} is a directory; use `--all` to restore a dump set", path.display())]
+    PathIsDirectory { path: PathBuf },
+    #[error("{} is a file, but `--all` restores a dump directory or a `.tar` \
+             archive of one; drop `--all` to restore a single dump", path.display())]
+    PathIsFile { path: PathBuf },
+    #[error("restore timed out after {} with {} of the dump read in {blocks} blocks; \
+             the database is partially restored",
+            humantime::format_duration(*timeout), HumanBytes(*bytes))]
+    Timeout {
+        timeout: Duration,
+        bytes: u64,
+        blocks: u64,
+    },
+    #[error("database {database:?} existed before the restore and is now partially restored")]
+    PartiallyRestored { database: String },
+}
+
+#[context("error checking if DB is empty")]
+async fn is_non_empty_db(cli: &mut Connection) -> Result<bool, anyhow::Error> {
+    let non_empty = cli
+        .query_required_single::<bool, _>(
+            r###"SELECT
+            count(
+                schema::Module
+                FILTER NOT .builtin AND NOT .name = "default"
+            ) + count(
+                schema::Object
+                FILTER .name LIKE "default::%"
+            ) > 0
+        "###,
+            &(),
+        )
+        .await?;
+    return Ok(non_empty);
+}
+
+const DEFAULT_EMPTY_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn empty_check_timeout(params: &RestoreCmd) -> Duration {
+    params
+        .empty_check_timeout
+        .or_else(|| params.conn.as_ref().and_then(|c| c.connect_timeout))
+        .unwrap_or(DEFAULT_EMPTY_CHECK_TIMEOUT)
+}
+
+/// Fails unless `is_non_empty` reports an empty database within `timeout`.
+/// A server that accepted the connection but doesn't respond would
+/// otherwise block the restore forever.
+async fn check_empty(
+    is_non_empty: impl Future<Output = anyhow::Result<bool>>,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let non_empty = tokio::time::timeout(timeout, is_non_empty)
+        .await
+        .map_err(|_| RestoreError::EmptyCheckTimeout { timeout })??;
+    if non_empty {
+        return Err(RestoreError::NonEmptyDatabase.into());
+    }
+    Ok(())
+}
+
+pub async fn restore<'x>(
+    cli: &mut Connection,
+    options: &Options,
+    params: &RestoreCmd,
+) -> Result<(), anyhow::Error> {
+    check_path_kind(&params.path, params.all).await?;
+    if params.all {
+        restore_all(cli, options, params).await
+    } else if !params.into.is_empty() {
+        restore_into(cli, options, params).await
+    } else {
+        let parts = std::slice::from_ref(&params.path);
+        if params.dry_run {
+            return dry_run_db(cli, params, parts).await;
+        }
+        let database = cli.database().to_string();
+        log::info!(target: LOG_TARGET, "Restoring {:?} into {database:?}", params.path);
+        let (stats, verification) = restore_db(cli, params, parts)
+            .await
+            .inspect_err(|e| log::info!(target: LOG_TARGET, "Restore failed: {e:#}"))?;
+        log::info!(target: LOG_TARGET, "Restored database {database:?}: {stats}");
+        if let Some(lines) = verification {
+            print_verification(&lines);
+        }
+        print::success_json(serde_json::json!({
+            "command": "restore",
+            "databases": [database],
+        }));
+        Ok(())
+    }
+}
+
+/// Implements `restore --into`: restores the same dump into each target
+/// database, creating the missing ones, and reports every target's result.
+///
+/// The dump is consumed by a restore, so the file is opened again for each
+/// target. Stdin can't be read twice and is read into memory up front.
+async fn restore_into(
+    cli: &mut Connection,
+    options: &Options,
+    params: &RestoreCmd,
+) -> anyhow::Result<()> {
+    let stdin = if params.path.to_str() == Some("-") {
+        let mut data = Vec::new();
+        io::stdin()
+            .read_to_end(&mut data)
+            .await
+            .context("Failed to read dump from stdin")?;
+        Some(Bytes::from(data))
+    } else {
+        None
+    };
+    let version = cli
+        .get_version()
+        .await
+        .context("cannot fetch the server version to tell databases from branches")?;
+    let kind = DbKind::of_server(&version.specific());
+    let dbs = list_databases::get_databases(cli).await?;
+    let existing: BTreeSet<_> = dbs.into_iter().collect();
+
+    let stdin = &stdin;
+    let mut failures = Failures::new(false);
+    let mut restored = Vec::new();
+    for target in &params.into {
+        log::info!(target: LOG_TARGET, "Restoring {:?} into {target:?}", params.path);
+        eprintln!("\nRestoring into {target:?}");
+        let restore = async {
+            if !existing.contains(target) {
+                create_database(cli, kind, target).await?;
+            }
+            let mut conn_params = options.conn_params.clone();
+            conn_params.db(kind.select(target))?;
+            let mut conn = conn_params
+                .connect()
+                .await
+                .with_context(|| format!("cannot connect to database {target:?}"))?;
+            restore_dump(&mut conn, params, |position| async move {
+                match stdin {
+                    Some(data) => open_dump_bytes(data.clone(), position).await,
+                    None => open_dump_parts(std::slice::from_ref(&params.path), &position).await,
+                }
+            })
+            .await
+        };
+        let result = restore
+            .await
+            .inspect_err(|e| log::info!(target: LOG_TARGET, "Database {target:?} failed: {e:#}"))
+            .with_context(|| format!("restoring into {target:?}"));
+        if let Some((stats, verification)) = failures.check(result)? {
+            log::info!(target: LOG_TARGET, "Restored database {target:?}: {stats}");
+            if let Some(lines) = verification {
+                eprint!("Database {target:?}: ");
+                print_verification(&lines);
+            }
+            restored.push(target.clone());
+        }
+    }
+    eprintln!(
+        "Restored into {} of {} databases",
+        restored.len(),
+        params.into.len()
+    );
+    failures.finish()?;
+    print::success_json(serde_json::json!({
+        "command": "restore",
+        "databases": restored,
+    }));
+    Ok(())
+}
+
+/// Checks that `path` is a directory (or an archive) with `--all` and a
+/// file without it.
+/// Stdin, SFTP URLs and missing paths are left to fail when opened.
+async fn check_path_kind(path: &Path, all: bool) -> anyhow::Result<()> {
+    if path.to_str() == Some("-") || sftp::parse_url(path).is_some() {
+        return Ok(());
+    }
+    let Ok(metadata) = fs::metadata(path).await else {
+        return Ok(());
+    };
+    if metadata.is_dir() && !all {
+        return Err(RestoreError::PathIsDirectory { path: path.into() }.into());
+    }
+    if metadata.is_file() && all && ArchiveFormat::of_path(path).is_none() {
+        return Err(RestoreError::PathIsFile { path: path.into() }.into());
+    }
+    Ok(())
+}
+
+/// Bytes of dump read and time taken by a restore, printed when it's done
+/// whether or not the progress bar is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct RestoreStats {
+    bytes: u64,
+    elapsed: Duration,
+}
+
+impl fmt::Display for RestoreStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        write!(
+            f,
+            "{} in {seconds:.1}s ({}/s)",
+            HumanBytes(self.bytes),
+            HumanBytes((self.bytes as f64 / seconds.max(0.001)) as u64)
+        )
+    }
+}
+
+/// Restores a single dump, which may be split into `parts`. Returns the
+/// restore stats and the `--verify-after` results, if verification was
+/// requested and succeeded.
+async fn restore_db(
+    cli: &mut Connection,
+    params: &RestoreCmd,
+    parts: &[PathBuf],
+) -> Result<(RestoreStats, Option<Vec<String>>), anyhow::Error> {
+    restore_dump(cli, params, |position| async move {
+        open_dump_parts(parts, &position).await
+    })
+    .await
+}
+
+/// Restores the dump opened by `open`, which is given the counter to
+/// report the bytes read to, see [`restore_db`].
+async fn restore_dump<F>(
+    cli: &mut Connection,
+    params: &RestoreCmd,
+    open: impl FnOnce(Arc<AtomicU64>) -> F,
+) -> Result<(RestoreStats, Option<Vec<String>>), anyhow::Error>
+where
+    F: Future<Output = anyhow::Result<(DumpHeader, Blocks, Option<u64>)>>,
+{
+    let RestoreCmd {
+        path: _,
+        all: _,
+        into: _,
+        verbose: _,
+        log_file: _,
+        no_progress: _,
+        progress_template: _,
+        retries: _,
+        jobs: _,
+        read_ahead,
+        strip_prefix: _,
+        add_prefix: _,
+        allow_empty: _,
+        fail_fast: _,
+        no_fail_fast: _,
+        conn: _,
+        create_instance: _,
+        dry_run: _,
+        skip_config: _,
+        init_sed: _,
+        list_databases: _,
+        json: _,
+        verify_after: _,
+        verify_query: _,
+        strict_verify: _,
+        analyze_after,
+        empty_check_timeout: _,
+        timeout,
+    } = *params;
+    check_empty(is_non_empty_db(cli), empty_check_timeout(params)).await?;
+
+    let start = Instant::now();
+    let position = Arc::new(AtomicU64::new(0));
+    let blocks = Arc::new(AtomicU64::new(0));
+    let restore = async {
+        let (header, packets, file_size) = open(position.clone()).await?;
+        if log::log_enabled!(target: LOG_TARGET, log::Level::Info) {
+            log_header(&header, log_info);
+        }
+        let bar = progress_bar(params);
+        bar.set_message("Restoring database");
+        let packets = read_ahead_blocks(packets, read_ahead as usize);
+        let packets = count_blocks(log_blocks(packets, log_info), blocks.clone());
+        let mut input = StreamWithProgress::new(packets, bar, position.clone(), file_size);
+        if let Some(template) = params.progress_template.clone() {
+            input.set_template(move |read, total| render_template(&template, read, total));
+        }
+        cli.restore(header.header, input).await?;
+        anyhow::Ok(())
+    };
+    limit_restore(timeout, &position, &blocks, restore).await?;
+
+    let stats = RestoreStats {
+        bytes: position.load(Ordering::Relaxed),
+        elapsed: start.elapsed(),
+    };
+    eprintln!("Restored {stats}");
+
+    if analyze_after {
+        let conn = &mut *cli;
+        analyze(|statement| async move { conn.execute(statement, &()).await.map(|_| ()) }).await?;
+    }
+
+    if params.verify_after {
+        Ok((stats, verify_after(cli, params).await?))
+    } else {
+        Ok((stats, None))
+    }
+}
+
+const CONNECTION_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for the server before restoring each database, unless
+/// `--connect-timeout` is given
+const RESTORE_WAIT: Duration = Duration::from_secs(300);
+
+/// `--connect-timeout` given after `restore` or before it, `RESTORE_WAIT`
+/// if neither
+fn restore_wait(conn_params: &Connector, params: &RestoreCmd) -> Duration {
+    params
+        .conn
+        .as_ref()
+        .and_then(|c| c.connect_timeout)
+        .unwrap_or_else(|| conn_params.wait_time(RESTORE_WAIT))
+}
+
+/// Runs `connect`, a test connection, before databases are restored with
+/// a long wait for the server to become available each. A server that
+/// can't be reached at all fails here instead of once per database.
+async fn check_connection(
+    connect: impl Future<Output = anyhow::Result<()>>,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let result = match tokio::time::timeout(timeout, connect).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!(
+            "timed out after {}",
+            humantime::format_duration(timeout)
+        )),
+    };
+    result.context("cannot connect to the server to restore databases")
+}
+
+/// A database of a dump directory for `restore --list-databases`
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct PlannedDatabase {
+    name: String,
+    /// exists on the target already, restoring it fails unless it's empty
+    exists: bool,
+}
+
+fn planned_databases(
+    dumps: &[(String, Vec<PathBuf>)],
+    existing: &BTreeSet<String>,
+) -> Vec<PlannedDatabase> {
+    dumps
+        .iter()
+        .map(|(name, _)| PlannedDatabase {
+            name: name.clone(),
+            exists: existing.contains(name),
+        })
+        .collect()
+}
+
+fn format_planned_databases(databases: &[PlannedDatabase], json: bool) -> anyhow::Result<String> {
+    if json {
+        return Ok(serde_json::to_string_pretty(databases)? + "\n");
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(vec![
+        table::header_cell("Database"),
+        table::header_cell("Action"),
+    ]));
+    for db in databases {
+        let action = if db.exists {
+            "exists (conflict)"
+        } else {
+            "will create"
+        };
+        table.add_row(Row::new(vec![
+            table::Cell::new(&db.name),
+            table::Cell::new(action),
+        ]));
+    }
+    Ok(table.to_string())
+}
+
+/// What `restore --dry-run` found reading a dump
+#[derive(Debug, PartialEq)]
+struct DumpPlan {
+    version: i64,
+    bytes: u64,
+    blocks: u64,
+}
+
+impl fmt::Display for DumpPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "dump format {}, {} in {} blocks",
+            self.version,
+            HumanBytes(self.bytes),
+            self.blocks
+        )
+    }
+}
+
+/// State of the target of a dump in a `restore --dry-run` plan
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PlannedTarget {
+    Create,
+    Empty,
+    NotEmpty,
+}
+
+impl fmt::Display for PlannedTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PlannedTarget::Create => "would be created",
+            PlannedTarget::Empty => "exists and is empty",
+            PlannedTarget::NotEmpty => "exists and is NOT empty, restoring would fail",
+        })
+    }
+}
+
+/// Reads a dump to the end without restoring it, which checks the header
+/// and the checksum of every block
+async fn scan_dump(parts: &[PathBuf]) -> anyhow::Result<DumpPlan> {
+    let position = Arc::new(AtomicU64::new(0));
+    let (header, mut packets, _) = open_dump_parts(parts, &position).await?;
+    let mut blocks = 0;
+    while let Some(block) = packets.next().await {
+        block?;
+        blocks += 1;
+    }
+    Ok(DumpPlan {
+        version: header.version,
+        bytes: position.load(Ordering::Relaxed),
+        blocks,
+    })
+}
+
+fn plan_lines(init_files: &[PathBuf], dumps: &[(String, DumpPlan, PlannedTarget)]) -> Vec<String> {
+    let mut lines = vec!["Dry run, nothing was restored. The restore would:".to_string()];
+    for path in init_files {
+        lines.push(format!("  apply init script {}", path.display()));
+    }
+    for (database, plan, target) in dumps {
+        lines.push(format!("  restore {database:?} ({target}): {plan}"));
+    }
+    lines
+}
+
+/// `restore --dry-run` of a single dump
+async fn dry_run_db(
+    cli: &mut Connection,
+    params: &RestoreCmd,
+    parts: &[PathBuf],
+) -> anyhow::Result<()> {
+    check_empty(is_non_empty_db(cli), empty_check_timeout(params)).await?;
+    let plan = scan_dump(parts).await?;
+    let database = cli.database().to_string();
+    for line in plan_lines(&[], &[(database, plan, PlannedTarget::Empty)]) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// `restore --all --dry-run`: reads every dump and checks that existing
+/// databases are empty, without creating any
+async fn dry_run_all(
+    conn_params: &Connector,
+    kind: DbKind,
+    init_files: &[PathBuf],
+    dump_files: Vec<(String, Vec<PathBuf>)>,
+    existing: &BTreeSet<String>,
+) -> anyhow::Result<()> {
+    let mut dumps = Vec::with_capacity(dump_files.len());
+    for (database, parts) in dump_files {
+        let plan = scan_dump(&parts)
+            .await
+            .with_context(|| format!("reading the dump of database {database:?}"))?;
+        let target = if existing.contains(&database) {
+            let mut conn_params = conn_params.clone();
+            conn_params.db(kind.select(&database))?;
+            let mut cli = conn_params.connect().await?;
+            if is_non_empty_db(&mut cli).await? {
+                PlannedTarget::NotEmpty
+            } else {
+                PlannedTarget::Empty
+            }
+        } else {
+            PlannedTarget::Create
+        };
+        dumps.push((database, plan, target));
+    }
+    for line in plan_lines(init_files, &dumps) {
+        println!("{line}");
+    }
+    let conflicts = dumps
+        .iter()
+        .filter(|(_, _, target)| *target == PlannedTarget::NotEmpty)
+        .count();
+    if conflicts > 0 {
+        anyhow::bail!("{conflicts} of the target databases are not empty");
+    }
+    Ok(())
+}
+
+/// Non-abstract object types outside the standard library, counted by
+/// `restore --verify-after` unless `--verify-query` is given.
+const VERIFY_TYPES_QUERY: &str = r###"
+    SELECT (
+        SELECT schema::ObjectType
+        FILTER NOT .builtin AND NOT .abstract
+        ORDER BY .name
+    ).name
+"###;
+
+/// Statement run with `--analyze-after`
+const ANALYZE_STATEMENT: &str = "administer statistics_update()";
+
+/// Runs [`ANALYZE_STATEMENT`] using `execute`. Servers that reject the
+/// statement only get a warning, the restore itself has succeeded.
+async fn analyze<F>(execute: impl FnOnce(&'static str) -> F) -> anyhow::Result<()>
+where
+    F: Future<Output = Result<(), Error>>,
+{
+    match execute(ANALYZE_STATEMENT).await {
+        Ok(()) => {
+            eprintln!("Statistics updated");
+            Ok(())
+        }
+        Err(e) if e.is::<QueryError>() => {
+            print::warn!("Cannot update statistics, skipping: {e:#}");
+            Ok(())
+        }
+        Err(e) => Err(anyhow::Error::from(e).context("error updating statistics")),
+    }
+}
+
+async fn verify_after(
+    cli: &mut Connection,
+    params: &RestoreCmd,
+) -> anyhow::Result<Option<Vec<String>>> {
+    match verify(cli, params).await {
+        Ok(lines) => Ok(Some(lines)),
+        Err(e) if params.strict_verify => Err(e.context("restore verification failed")),
+        Err(e) => {
+            print::warn!("Restore verification failed: {e:#}");
+            Ok(None)
+        }
+    }
+}
+
+async fn verify(cli: &mut Connection, params: &RestoreCmd) -> anyhow::Result<Vec<String>> {
+    if let Some(query) = &params.verify_query {
+        let rows = cli
+            .query::<Value, _>(query, &())
+            .await
+            .context("error running verification query")?;
+        let config = print::Config::new();
+        return rows
+            .iter()
+            .map(|row| Ok(print::json_item_to_string(row, &config)?))
+            .collect();
+    }
+    let types = cli
+        .query::<String, _>(VERIFY_TYPES_QUERY, &())
+        .await
+        .context("error listing object types")?;
+    let mut lines = Vec::with_capacity(types.len());
+    for name in types {
+        let count = cli
+            .query_required_single::<i64, _>(&format!("SELECT count({})", quote_type(&name)), &())
+            .await
+            .with_context(|| format!("error counting objects of type {name}"))?;
+        lines.push(format!("{name}: {count}"));
+    }
+    Ok(lines)
+}
+
+fn quote_type(name: &str) -> String {
+    name.split("::")
+        .map(quote_name)
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+fn print_verification(lines: &[String]) {
+    if lines.is_empty() {
+        eprintln!("Verification: no results");
+    } else {
+        eprintln!("Verification:");
+        for line in lines {
+            eprintln!("  {line}");
+        }
+    }
+}
+
+// `--verbose` enables info level for this target and `--log-file` writes
+// its records to the file, see `log_levels`
+const LOG_TARGET: &str = "edgedb::restore";
+
+fn log_info(args: fmt::Arguments) {
+    log::info!(target: LOG_TARGET, "{args}");
+}
+
+/// Runs `restore` within `--timeout`, reporting the bytes read and blocks
+/// sent so far when it runs out
+async fn limit_restore(
+    timeout: Option<Duration>,
+    position: &AtomicU64,
+    blocks: &AtomicU64,
+    restore: impl Future<Output = anyhow::Result<()>>,
+) -> anyhow::Result<()> {
+    let Some(timeout) = timeout else {
+        return restore.await;
+    };
+    tokio::time::timeout(timeout, restore)
+        .await
+        .map_err(|_| RestoreError::Timeout {
+            timeout,
+            bytes: position.load(Ordering::Relaxed),
+            blocks: blocks.load(Ordering::Relaxed),
+        })?
+}
+
+/// Name of a file in a `dump --all` directory.
+#[derive(Debug, Clone, PartialEq)]
+struct DumpFileName {
+    /// The whole decoded file stem
+    database: String,
+    /// Database name and part number if the name ends with `.<digits>`
+    part: Option<(String, u32)>,
+    /// Whether the name ends with `.000`, the first part of a split dump
+    first_part: bool,
+}
+
+/// Database name of a file in a `dump --all` directory, and the part it
+/// would be of a split dump.
+///
+/// A large database can be split into parts named `<name>.000.dump`,
+/// `<name>.001.dump` and so on, numbered from zero. Each part is a dump
+/// file of its own with the same header, and the data blocks of all parts
+/// are restored in order into a single database. `dump --all` doesn't
+/// encode dots, so a database such as `app.1` is dumped as `app.1.dump`:
+/// files are only taken as parts if `<name>.000.dump` exists too, see
+/// [`group_parts`].
+fn path_to_database_name(path: &Path) -> anyhow::Result<DumpFileName> {
+    let encoded = path
+        .file_stem()
+        .and_then(|x| x.to_str())
+        .ok_or_else(|| anyhow::anyhow!("invalid dump filename {:?}", path))?;
+    let decode = |name| {
+        urlencoding::decode(name)
+            .map(|name| name.into_owned())
+            .with_context(|| format!("failed to decode filename {path:?}"))
+    };
+    let part = match encoded.rsplit_once('.') {
+        Some((name, part)) if !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) => {
+            // a number too large for a part can only be part of a name
+            part.parse().ok().map(|part| (name, part))
+        }
+        _ => None,
+    };
+    Ok(DumpFileName {
+        first_part: encoded.ends_with(".000"),
+        database: decode(encoded)?,
+        part: part
+            .map(|(name, part)| Ok::<_, anyhow::Error>((decode(name)?, part)))
+            .transpose()?,
+    })
+}
+
+/// Groups the files of a `dump --all` directory by database, with the part
+/// numbers of split dumps: a file is a part only if the database has a
+/// part `000`, otherwise its whole name is the database name.
+fn group_parts(
+    files: Vec<(DumpFileName, PathBuf)>,
+) -> BTreeMap<String, Vec<(Option<u32>, PathBuf)>> {
+    let split: BTreeSet<String> = files
+        .iter()
+        .filter(|(name, _)| name.first_part)
+        .filter_map(|(name, _)| name.part.as_ref().map(|(database, _)| database.clone()))
+        .collect();
+    let mut grouped = BTreeMap::<_, Vec<_>>::new();
+    for (name, path) in files {
+        let (database, part) = match name.part {
+            Some((database, part)) if split.contains(&database) => (database, Some(part)),
+            _ => (name.database, None),
+        };
+        grouped.entry(database).or_default().push((part, path));
+    }
+    grouped
+}
+
+async fn apply_init(
+    cli: &mut Connection,
+    mut input: impl AsyncRead + Unpin,
+    params: &RestoreCmd,
+) -> anyhow::Result<()> {
+    let mut inbuf = BytesMut::with_capacity(8192);
+    log::debug!("Restoring init script");
+    let bar = progress_bar(params);
+    bar.set_message("Applying init script");
+    let mut applied = 0;
+    let mut skipped = 0;
+    let mut substitutions = 0;
+    // init scripts are small, read them whole to know all the extensions
+    // they need if one of the statements fails on a missing one
+    let mut statements = Vec::new();
+    loop {
+        let stmt = match read_statement(&mut inbuf, &mut input).await {
+            Ok(chunk) => chunk,
+            Err(e) if e.is::<EndOfFile>() => break,
+            Err(e) => return Err(e),
+        };
+        let stmt = str::from_utf8(&stmt[..]).context("can't decode statement")?;
+        let (stmt, replaced) = substitute(stmt, &params.init_sed)?;
+        substitutions += replaced;
+        statements.push(stmt.into_owned());
+    }
+    let required: BTreeSet<String> = statements
+        .iter()
+        .filter_map(|stmt| created_extension(stmt))
+        .collect();
+    for stmt in &statements {
+        let stmt = &stmt[..];
+        if params.skip_config && is_instance_config(stmt) {
+            skipped += 1;
+            log::info!(
+                target: LOG_TARGET,
+                "Skipping instance config: {}",
+                statement_preview(stmt)
+            );
+        } else if !is_empty(stmt) {
+            applied += 1;
+            bar.set_message(format!("Applying init script: statement {applied}"));
+            if params.verbose {
+                bar.suspend(|| eprintln!("{}", stmt.trim()));
+            } else {
+                log::trace!("Executing {:?}", stmt);
+            }
+            if let Err(e) = cli.execute(stmt, &()).await {
+                let err = anyhow::Error::from(e);
+                let extensions = if is_missing_extension(&err) && !required.is_empty() {
+                    Some(InitExtensions {
+                        required: required.clone(),
+                        installed: installed_extensions(cli).await,
+                    })
+                } else {
+                    None
+                };
+                bar.finish_and_clear();
+                return Err(init_statement_error(
+                    err,
+                    applied,
+                    stmt,
+                    extensions.as_ref(),
+                ));
+            }
+        }
+    }
+    bar.finish_and_clear();
+    if !params.init_sed.is_empty() {
+        print::warn!("Init script rewritten with --init-sed: {substitutions} substitutions made");
+    }
+    if skipped > 0 {
+        eprintln!("Init applied: {applied} statements, {skipped} config statements skipped");
+    } else {
+        eprintln!("Init applied: {applied} statements");
+    }
+    Ok(())
+}
+
+/// Regular expression replacement from `--init-sed`.
+#[derive(Debug, Clone)]
+pub struct InitSubstitution {
+    pattern: regex::Regex,
+    replacement: String,
+}
+
+pub fn parse_init_sed(value: &str) -> anyhow::Result<InitSubstitution> {
+    let (pattern, replacement) = value
+        .split_once('=')
+        .context("expected PATTERN=REPLACEMENT")?;
+    if pattern.is_empty() {
+        anyhow::bail!("pattern is empty");
+    }
+    Ok(InitSubstitution {
+        pattern: regex::Regex::new(pattern)?,
+        replacement: replacement.to_string(),
+    })
+}
+
+/// Applies the `--init-sed` substitutions to a statement of the init
+/// script, and returns it with the number of replacements made.
+///
+/// Fails if the statement could be tokenized before the substitutions but
+/// not after, so that e.g. a replacement with a quote doesn't turn the rest
+/// of the statement into a string.
+fn substitute<'a>(
+    stmt: &'a str,
+    substitutions: &[InitSubstitution],
+) -> anyhow::Result<(Cow<'a, str>, usize)> {
+    let mut result = Cow::Borrowed(stmt);
+    let mut replaced = 0;
+    for sub in substitutions {
+        let matches = sub.pattern.find_iter(&result).count();
+        if matches > 0 {
+            replaced += matches;
+            let text = sub
+                .pattern
+                .replace_all(&result, sub.replacement.as_str())
+                .into_owned();
+            result = Cow::Owned(text);
+        }
+    }
+    let tokenizes = |text: &str| Tokenizer::new(text).all(|token| token.is_ok());
+    if replaced > 0 && tokenizes(stmt) && !tokenizes(&result) {
+        anyhow::bail!(
+            "--init-sed made an invalid statement of: {}",
+            statement_preview(stmt)
+        );
+    }
+    Ok((result, replaced))
+}
+
+/// Whether `stmt` is `CONFIGURE INSTANCE` or the older `CONFIGURE SYSTEM`,
+/// which `--skip-config` leaves out. Database and branch configuration is
+/// restored anyway.
+fn is_instance_config(stmt: &str) -> bool {
+    let mut words = Tokenizer::new(stmt)
+        .map_while(Result::ok)
+        .map(|token| token.text.to_ascii_lowercase());
+    matches!(
+        (words.next().as_deref(), words.next().as_deref()),
+        (Some("configure"), Some("instance" | "system"))
+    )
+}
+
+/// Name of the extension created by a `CREATE EXTENSION` statement
+fn created_extension(stmt: &str) -> Option<String> {
+    let mut words = Tokenizer::new(stmt).map_while(Result::ok).map(|t| t.text);
+    let create = words.next()?;
+    let extension = words.next()?;
+    let name = words.next()?;
+    if !create.eq_ignore_ascii_case("create")
+        || !extension.eq_ignore_ascii_case("extension")
+        || name.eq_ignore_ascii_case("package")
+    {
+        return None;
+    }
+    Some(name.trim_matches('`').to_string())
+}
+
+/// Names of the extension packages installed on the server, `None` if they
+/// can't be listed
+async fn installed_extensions(cli: &mut Connection) -> Option<BTreeSet<String>> {
+    let query = "SELECT DISTINCT sys::ExtensionPackage.name";
+    match cli.query::<String, _>(query, &()).await {
+        Ok(names) => Some(names.into_iter().collect()),
+        Err(e) => {
+            log::warn!(target: LOG_TARGET, "Cannot list installed extensions: {e}");
+            None
+        }
+    }
+}
+
+/// Extensions created by an init script and the ones installed on the
+/// server it is applied to, listed in the hint when the script fails on a
+/// missing extension
+struct InitExtensions {
+    required: BTreeSet<String>,
+    installed: Option<BTreeSet<String>>,
+}
+
+impl InitExtensions {
+    fn hint(&self) -> String {
+        let list = |names: &BTreeSet<String>| {
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        };
+        let mut hint = format!(
+            "The dump requires these extensions: {}.",
+            list(&self.required)
+        );
+        let Some(installed) = &self.installed else {
+            hint.push_str(" Install the ones missing on this server, then restore again.");
+            return hint;
+        };
+        hint += &format!(" Installed on this server: {}.", list(installed));
+        let missing: BTreeSet<String> = self.required.difference(installed).cloned().collect();
+        if missing.is_empty() {
+            hint.push_str(" Create the extensions before they are used, then restore again.");
+        } else {
+            hint += &format!(
+                " Install {} first with `{BRANDING_CLI_CMD} extension install`, \
+                 then restore again.",
+                list(&missing)
+            );
+        }
+        hint
+    }
+}
+
+/// Adds the position and a preview of the failing statement to an error
+/// from the init script, and a hint if it is about a missing role or
+/// extension. The hint lists the `extensions` needed by the script if
+/// given.
+fn init_statement_error(
+    err: anyhow::Error,
+    ordinal: usize,
+    stmt: &str,
+    extensions: Option<&InitExtensions>,
+) -> anyhow::Error {
+    let missing_global = is_missing_role_or_extension(&err);
+    let err = err.context(format!(
+        "statement {ordinal} of the init script failed: {}",
+        statement_preview(stmt)
+    ));
+    if let Some(extensions) = extensions {
+        err.with_hint(|| extensions.hint()).into()
+    } else if missing_global {
+        err.hint(
+            "The dump refers to roles or extensions missing on this server. \
+             Create the roles and install the extensions, then restore again.",
+        )
+        .into()
+    } else {
+        err
+    }
+}
+
+fn is_missing_role_or_extension(err: &anyhow::Error) -> bool {
+    is_missing(err, |msg| msg.contains("role") || mentions_extension(msg))
+}
+
+/// Whether the error is about an unknown extension or a module of one, e.g.
+/// a type from `ext::` used by the init script
+fn is_missing_extension(err: &anyhow::Error) -> bool {
+    is_missing(err, mentions_extension)
+}
+
+fn mentions_extension(msg: &str) -> bool {
+    msg.contains("extension") || msg.contains("module") || msg.contains("ext::")
+}
+
+fn is_missing(err: &anyhow::Error, subject: impl Fn(&str) -> bool) -> bool {
+    err.chain().any(|e| {
+        let msg = e.to_string().to_lowercase();
+        subject(&msg) && (msg.contains("does not exist") || msg.contains("not found"))
+    })
+}
+
+/// First line of a statement, cut to a length that fits an error message
+fn statement_preview(stmt: &str) -> String {
+    const MAX_CHARS: usize = 100;
+    let stmt = stmt.trim();
+    let first_line = stmt.lines().next().unwrap_or("");
+    let mut preview: String = first_line.chars().take(MAX_CHARS).collect();
+    if preview.len() < stmt.len() {
+        preview.push_str("...");
+    }
+    preview
+}
+
+/// Errors of `restore --all`. With `--fail-fast`, the default, the first
+/// one stops the restore; with `--no-fail-fast` they are collected and
+/// listed once everything else is restored.
+struct Failures {
+    fail_fast: bool,
+    errors: Vec<anyhow::Error>,
+}
+
+impl Failures {
+    fn new(fail_fast: bool) -> Failures {
+        Failures {
+            fail_fast,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Passes the error of `result` on with `--fail-fast`, and collects it
+    /// otherwise, returning `None` to skip what failed.
+    fn check<T>(&mut self, result: anyhow::Result<T>) -> anyhow::Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if self.fail_fast => Err(e),
+            Err(e) => {
+                print::error!("{e:#}");
+                log::info!(target: LOG_TARGET, "Failed, continuing: {e:#}");
+                self.errors.push(e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Lists the collected errors, failing with the `PARTIAL_SUCCESS` exit
+    /// code if there are any.
+    fn finish(self) -> anyhow::Result<()> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        eprintln!("Restore finished with {} errors:", self.errors.len());
+        for e in &self.errors {
+            eprintln!("  - {e:#}");
+        }
+        Err(ExitCode::new(exit_codes::PARTIAL_SUCCESS).into())
+    }
+}
+
+pub async fn restore_all<'x>(
+    cli: &mut Connection,
+    options: &Options,
+    params: &RestoreCmd,
+) -> anyhow::Result<()> {
+    if let Some(format) = ArchiveFormat::of_path(&params.path) {
+        return restore_archive(cli, options, params, format).await;
+    }
+    let dir = &params.path;
+    let dump_files = rename_databases(
+        find_dumps(dir, params.allow_empty).await?,
+        params.strip_prefix.as_deref(),
+        params.add_prefix.as_deref(),
+    )?;
+    if params.list_databases {
+        let dbs = list_databases::get_databases(cli).await?;
+        let existing: BTreeSet<_> = dbs.into_iter().collect();
+        let databases = planned_databases(&dump_files, &existing);
+        print!("{}", format_planned_databases(&databases, params.json)?);
+        return Ok(());
+    }
+    let mut check_params = options.conn_params.clone();
+    check_params.wait_until_available(CONNECTION_CHECK_TIMEOUT);
+    let connect = async {
+        let mut conn = check_params.connect().await?;
+        conn.query_required_single::<i64, _>("SELECT 1", &())
+            .await?;
+        anyhow::Ok(())
+    };
+    check_connection(connect, CONNECTION_CHECK_TIMEOUT).await?;
+    let init_files = find_init_files(dir).await?;
+    let mut failures = Failures::new(!params.no_fail_fast);
+    if !params.dry_run {
+        log::info!(target: LOG_TARGET, "Restoring {} databases from {dir:?}", dump_files.len());
+        for filename in &init_files {
+            let apply = async {
+                let (input, _) = open_file(filename).await?;
+                apply_init(cli, input, params).await
+            };
+            let applied = apply
+                .await
+                .with_context(|| format!("error applying init file {filename:?}"));
+            if failures.check(applied)?.is_some() {
+                log::info!(target: LOG_TARGET, "Applied init file {filename:?}");
+            }
+        }
+    }
+
+    let mut conn_params = options.conn_params.clone();
+    conn_params.wait_until_available(restore_wait(&conn_params, params));
+    let mut params = params.clone();
+    if params.jobs > 1 {
+        // concurrent progress bars would overwrite each other
+        params.no_progress = true;
+    }
+    let version = cli
+        .get_version()
+        .await
+        .context("cannot fetch the server version to tell databases from branches")?;
+    let kind = DbKind::of_server(&version.specific());
+    let dbs = list_databases::get_databases(cli).await?;
+    let existing: BTreeSet<_> = dbs.into_iter().collect();
+    if params.dry_run {
+        return dry_run_all(&conn_params, kind, &init_files, dump_files, &existing).await;
+    }
+
+    let mut dumps = Vec::new();
+    for (name, parts) in dump_files {
+        let created = !existing.contains(&name);
+        if created
+            && failures
+                .check(create_database(cli, kind, &name).await)?
+                .is_none()
+        {
+            continue;
+        }
+        dumps.push(DatabaseDump {
+            name,
+            parts,
+            created,
+        });
+    }
+
+    let start = Instant::now();
+    let mut jobs = params.jobs as usize;
+    if jobs > 1 {
+        if let Some(limit) = connection_limit(cli).await {
+            let capped = jobs_within_limit(jobs, limit);
+            if capped < jobs {
+                print::warn!(
+                    "Server allows {limit} connections, \
+                     restoring {capped} databases at once instead of {jobs}"
+                );
+                jobs = capped;
+            }
+        }
+    }
+    let limiter = JobLimiter::new(jobs);
+    let mut tasks = dumps
+        .into_iter()
+        .map(|dump| restore_database(&conn_params, options, &params, kind, dump, &limiter))
+        .collect::<FuturesUnordered<_>>();
+    let mut restored = Vec::new();
+    let mut retried = Vec::new();
+    let mut verified = Vec::new();
+    let mut total = RestoreStats::default();
+    while let Some(result) = tasks.next().await {
+        let Some((database, attempts, stats, verification)) = failures.check(result)? else {
+            continue;
+        };
+        total.bytes += stats.bytes;
+        restored.push(database.clone());
+        if attempts > 0 {
+            retried.push(database.clone());
+        }
+        if let Some(lines) = verification {
+            verified.push((database, lines));
+        }
+    }
+    total.elapsed = start.elapsed();
+    eprintln!("Restored {} databases: {total}", restored.len());
+    log::info!(target: LOG_TARGET, "Restored {} databases: {total}", restored.len());
+    if !retried.is_empty() {
+        retried.sort();
+        eprintln!("Databases restored after retrying: {}", retried.join(", "));
+    }
+    verified.sort();
+    for (database, lines) in verified {
+        eprint!("Database {database:?}: ");
+        print_verification(&lines);
+    }
+    failures.finish()?;
+    restored.sort();
+    print::success_json(serde_json::json!({
+        "command": "restore",
+        "databases": restored,
+    }));
+    Ok(())
+}
+
+/// Lists the per-database dumps of a `dump --all` directory, checking
+/// that it looks like one before anything is restored.
+///
+/// Parts of split dumps are grouped by database and sorted, see
+/// [`path_to_database_name`]. Databases are sorted by name, unless the
+/// directory has a `manifest.txt`, see [`order_by_manifest`]. The
+/// directory may be an SFTP URL, in which case the dumps are URLs too.
+async fn find_dumps(dir: &Path, allow_empty: bool) -> anyhow::Result<Vec<(String, Vec<PathBuf>)>> {
+    let dump_ext = OsString::from("dump");
+    let mut files = Vec::new();
+    let has_manifest;
+    if let Some(url) = sftp::parse_url(dir) {
+        let url = url?;
+        let entries = sftp::read_dir(&url).await?;
+        if !entries
+            .iter()
+            .any(|e| e.name == INIT_FILE || e.name == INIT_DIR)
+        {
+            return Err(RestoreError::MissingInit { dir: dir.into() }.into());
+        }
+        has_manifest = entries
+            .iter()
+            .any(|e| e.name == MANIFEST_FILE && e.is_file());
+        for entry in entries {
+            let name = Path::new(&entry.name);
+            if !entry.is_file() || name.extension() != Some(&dump_ext) {
+                continue;
+            }
+            let name = path_to_database_name(name)?;
+            let path: PathBuf = sftp::join(&url, &entry.name).to_string().into();
+            files.push((name, path));
+        }
+    } else {
+        if fs::metadata(dir.join(INIT_FILE)).await.is_err()
+            && fs::metadata(dir.join(INIT_DIR)).await.is_err()
+        {
+            return Err(RestoreError::MissingInit { dir: dir.into() }.into());
+        }
+        has_manifest = fs::metadata(dir.join(MANIFEST_FILE)).await.is_ok();
+        let mut dir_list = fs::read_dir(&dir).await?;
+        while let Some(entry) = dir_list.next_entry().await? {
+            let path = entry.path();
+            if path.extension() != Some(&dump_ext) {
+                continue;
+            }
+            files.push((path_to_database_name(&path)?, path));
+        }
+    }
+    let files = group_parts(files);
+    let mut dumps = Vec::with_capacity(files.len());
+    for (database, mut parts) in files {
+        parts.sort();
+        if parts.len() > 1 || parts[0].0.is_some_and(|part| part > 0) {
+            for (expected, (part, path)) in (0..).zip(&parts) {
+                if *part != Some(expected) {
+                    if part.is_none() {
+                        anyhow::bail!(
+                            "{} conflicts with the parts of the split dump of database {database:?}",
+                            path.display()
+                        );
+                    }
+                    return Err(RestoreError::MissingPart {
+                        database,
+                        part: expected,
+                    }
+                    .into());
+                }
+            }
+        }
+        dumps.push((database, parts.into_iter().map(|(_, path)| path).collect()));
+    }
+    if has_manifest {
+        let path = dir_file(dir, MANIFEST_FILE)?;
+        let mut manifest = String::new();
+        open_file(&path)
+            .await?
+            .0
+            .read_to_string(&mut manifest)
+            .await
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        dumps = order_by_manifest(dumps, &manifest)
+            .with_context(|| format!("invalid {}", path.display()))?;
+    }
+    if dumps.is_empty() {
+        let err = RestoreError::NoDumps { dir: dir.into() };
+        if !allow_empty {
+            return Err(err.into());
+        }
+        print::warn!("{err}; only the server configuration will be restored.");
+    }
+    Ok(dumps)
+}
+
+const MANIFEST_FILE: &str = "manifest.txt";
+const INIT_FILE: &str = "init.edgeql";
+const INIT_DIR: &str = "init";
+
+/// Init scripts of a `dump --all` directory: the `*.edgeql` files of its
+/// `init/` subdirectory sorted by name, or `init.edgeql` if there is no
+/// such subdirectory.
+async fn find_init_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let edgeql_ext = OsString::from("edgeql");
+    let mut files = Vec::new();
+    if let Some(url) = sftp::parse_url(dir) {
+        let url = url?;
+        let entries = sftp::read_dir(&url).await?;
+        if entries.iter().any(|e| e.name == INIT_DIR && !e.is_file()) {
+            let init_dir = sftp::join(&url, INIT_DIR);
+            for entry in sftp::read_dir(&init_dir).await? {
+                if entry.is_file() && Path::new(&entry.name).extension() == Some(&edgeql_ext) {
+                    files.push(sftp::join(&init_dir, &entry.name).to_string().into());
+                }
+            }
+        } else if entries.iter().any(|e| e.name == INIT_FILE) {
+            files.push(dir_file(dir, INIT_FILE)?);
+        }
+    } else {
+        let init_dir = dir.join(INIT_DIR);
+        if fs::metadata(&init_dir).await.is_ok_and(|m| m.is_dir()) {
+            let mut dir_list = fs::read_dir(&init_dir).await?;
+            while let Some(entry) = dir_list.next_entry().await? {
+                let path = entry.path();
+                if path.extension() == Some(&edgeql_ext) && entry.file_type().await?.is_file() {
+                    files.push(path);
+                }
+            }
+        } else if fs::metadata(dir.join(INIT_FILE)).await.is_ok() {
+            files.push(dir.join(INIT_FILE));
+        }
+    }
+    if files.is_empty() {
+        return Err(RestoreError::MissingInit { dir: dir.into() }.into());
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Applies `--strip-prefix` and `--add-prefix` to the database names of
+/// the dumps, checking that the new names are usable and distinct.
+fn rename_databases(
+    dumps: Vec<(String, Vec<PathBuf>)>,
+    strip_prefix: Option<&str>,
+    add_prefix: Option<&str>,
+) -> anyhow::Result<Vec<(String, Vec<PathBuf>)>> {
+    if strip_prefix.is_none() && add_prefix.is_none() {
+        return Ok(dumps);
+    }
+    let mut sources = BTreeMap::new();
+    dumps
+        .into_iter()
+        .map(|(dump_name, parts)| {
+            let mut name = dump_name.as_str();
+            if let Some(prefix) = strip_prefix {
+                match name.strip_prefix(prefix) {
+                    Some(stripped) => name = stripped,
+                    None => print::warn!(
+                        "Database {dump_name:?} does not start with {prefix:?}, \
+                         keeping its name"
+                    ),
+                }
+            }
+            let name = format!("{}{name}", add_prefix.unwrap_or(""));
+            if name.trim().is_empty() || name.chars().any(char::is_control) {
+                anyhow::bail!(
+                    "the dump of database {dump_name:?} would be restored \
+                     into invalid database name {name:?}"
+                );
+            }
+            if let Some(other) = sources.insert(name.clone(), dump_name.clone()) {
+                anyhow::bail!(
+                    "the dumps of databases {other:?} and {dump_name:?} would both \
+                     be restored into database {name:?}"
+                );
+            }
+            Ok((name, parts))
+        })
+        .collect()
+}
+
+/// Puts the databases listed in `manifest.txt` first, in the listed order,
+/// followed by the unlisted ones by name. The manifest has a database name
+/// per line; empty lines and lines starting with `#` are skipped.
+fn order_by_manifest(
+    mut dumps: Vec<(String, Vec<PathBuf>)>,
+    manifest: &str,
+) -> anyhow::Result<Vec<(String, Vec<PathBuf>)>> {
+    let mut ordered = Vec::with_capacity(dumps.len());
+    for line in manifest.lines() {
+        let name = line.trim();
+        if name.is_empty() || name.starts_with('#') {
+            continue;
+        }
+        match dumps.iter().position(|(database, _)| database == name) {
+            Some(index) => ordered.push(dumps.remove(index)),
+            None if ordered.iter().any(|(database, _)| database == name) => {
+                anyhow::bail!("database {name:?} is listed more than once");
+            }
+            None => anyhow::bail!("database {name:?} is listed, but there is no dump of it"),
+        }
+    }
+    ordered.extend(dumps);
+    Ok(ordered)
+}
+
+/// Format of a `restore --all` archive: a tar file of a dump directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Tells archives from dump directories by the file name: `.tar`, or
+    /// `.tar.gz` and `.tgz` for gzipped ones. Only local files can be
+    /// archives.
+    fn of_path(path: &Path) -> Option<ArchiveFormat> {
+        if path.to_str() == Some("-") || sftp::parse_url(path).is_some() {
+            return None;
+        }
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Size of the buffers between the thread reading an archive and the
+/// restore
+const ARCHIVE_BUFFER: usize = 65536;
+
+/// A file of a `restore --all` archive, streamed to the restore while the
+/// archive is read
+enum ArchiveEntry {
+    Init(io::DuplexStream),
+    Dump {
+        database: String,
+        size: u64,
+        data: io::DuplexStream,
+    },
+}
+
+/// Reads the tar archive `name` of a dump directory and sends its
+/// `init.edgeql` and `*.dump` files to `entries` in archive order, without
+/// extracting them. Other files are skipped. Must run on a blocking thread
+/// of the runtime; stops when `entries` is closed.
+fn read_archive(
+    archive: impl std::io::Read,
+    name: &Path,
+    allow_empty: bool,
+    entries: mpsc::Sender<ArchiveEntry>,
+) -> anyhow::Result<()> {
+    use std::io::Read;
+    use tokio::io::AsyncWriteExt;
+
+    let runtime = tokio::runtime::Handle::current();
+    let mut archive = tar::Archive::new(archive);
+    let mut has_init = false;
+    let mut dumps = 0;
+    let mut buf = vec![0; ARCHIVE_BUFFER];
+    let read_ctx = || format!("cannot read archive {}", name.display());
+    for entry in archive.entries().with_context(read_ctx)? {
+        let mut entry = entry.with_context(read_ctx)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().with_context(read_ctx)?.into_owned();
+        let (reader, mut writer) = io::duplex(ARCHIVE_BUFFER);
+        let item = if path.file_name().is_some_and(|file| file == INIT_FILE) {
+            if dumps > 0 {
+                anyhow::bail!(
+                    "`{INIT_FILE}` must come before the dumps in archive {}",
+                    name.display()
+                );
+            }
+            has_init = true;
+            ArchiveEntry::Init(reader)
+        } else if path.extension().is_some_and(|ext| ext == "dump") {
+            if !has_init {
+                return Err(RestoreError::MissingInit { dir: name.into() }.into());
+            }
+            let DumpFileName {
+                database,
+                part,
+                first_part,
+            } = path_to_database_name(&path)?;
+            // entries are streamed, so later parts can't be looked up: a
+            // first part is rejected, other names are whole database names
+            if let Some((database, _)) = part.filter(|_| first_part) {
+                anyhow::bail!(
+                    "split dumps are not supported in archives, \
+                     extract {} to restore {database:?}",
+                    name.display()
+                );
+            }
+            dumps += 1;
+            ArchiveEntry::Dump {
+                database,
+                size: entry.size(),
+                data: reader,
+            }
+        } else {
+            log::debug!("Skipping {path:?} in archive");
+            continue;
+        };
+        if entries.blocking_send(item).is_err() {
+            // the restore has stopped
+            return Ok(());
+        }
+        loop {
+            let n = entry
+                .read(&mut buf)
+                .with_context(|| format!("cannot read {path:?} from archive"))?;
+            if n == 0 {
+                break;
+            }
+            if runtime.block_on(writer.write_all(&buf[..n])).is_err() {
+                // the restore stopped reading this file, skip the rest
+                break;
+            }
+        }
+    }
+    if !has_init {
+        return Err(RestoreError::MissingInit { dir: name.into() }.into());
+    }
+    if dumps == 0 {
+        let err = RestoreError::NoDumps { dir: name.into() };
+        if !allow_empty {
+            return Err(err.into());
+        }
+        print::warn!("{err}; only the server configuration was restored.");
+    }
+    Ok(())
+}
+
+/// Restores `restore --all` from a tar archive without extracting it, see
+/// [`read_archive`]. Databases are restored one at a time in archive order,
+/// and are not retried as the archive is read only once.
+async fn restore_archive(
+    cli: &mut Connection,
+    options: &Options,
+    params: &RestoreCmd,
+    format: ArchiveFormat,
+) -> anyhow::Result<()> {
+    if params.dry_run || params.list_databases {
+        anyhow::bail!("`--dry-run` and `--list-databases` need an extracted dump directory");
+    }
+    let path = params.path.clone();
+    let allow_empty = params.allow_empty;
+    let (entries, received) = mpsc::channel(1);
+    let reader = tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&path)
+            .with_context(|| format!("cannot open archive {}", path.display()))?;
+        let file = std::io::BufReader::new(file);
+        match format {
+            ArchiveFormat::Tar => read_archive(file, &path, allow_empty, entries),
+            ArchiveFormat::TarGz => {
+                let file = libflate::gzip::Decoder::new(file)
+                    .with_context(|| format!("cannot read archive {}", path.display()))?;
+                read_archive(file, &path, allow_empty, entries)
+            }
+        }
+    });
+    let mut failures = Failures::new(!params.no_fail_fast);
+    let restored = restore_archive_entries(cli, options, params, received, &mut failures).await;
+    // a broken archive also fails the restore of the file being read, so
+    // its error is the one to report
+    reader.await.context("archive reader failed")??;
+    let mut restored = restored?;
+    failures.finish()?;
+    restored.sort();
+    print::success_json(serde_json::json!({
+        "command": "restore",
+        "databases": restored,
+    }));
+    Ok(())
+}
+
+async fn restore_archive_entries(
+    cli: &mut Connection,
+    options: &Options,
+    params: &RestoreCmd,
+    mut entries: mpsc::Receiver<ArchiveEntry>,
+    failures: &mut Failures,
+) -> anyhow::Result<Vec<String>> {
+    let version = cli
+        .get_version()
+        .await
+        .context("cannot fetch the server version to tell databases from branches")?;
+    let kind = DbKind::of_server(&version.specific());
+    let dbs = list_databases::get_databases(cli).await?;
+    let existing: BTreeSet<_> = dbs.into_iter().collect();
+    let mut conn_params = options.conn_params.clone();
+    conn_params.wait_until_available(restore_wait(&conn_params, params));
+
+    log::info!(target: LOG_TARGET, "Restoring databases from archive {:?}", params.path);
+    let start = Instant::now();
+    let mut total = RestoreStats::default();
+    let mut restored = Vec::new();
+    while let Some(entry) = entries.recv().await {
+        let (dump_name, size, data) = match entry {
+            ArchiveEntry::Init(data) => {
+                let applied = apply_init(cli, data, params)
+                    .await
+                    .context("error applying init.edgeql from archive");
+                if failures.check(applied)?.is_some() {
+                    log::info!(target: LOG_TARGET, "Applied init.edgeql from archive");
+                }
+                continue;
+            }
+            ArchiveEntry::Dump {
+                database,
+                size,
+                data,
+            } => (database, size, data),
+        };
+        let (database, _) = rename_databases(
+            vec![(dump_name, Vec::new())],
+            params.strip_prefix.as_deref(),
+            params.add_prefix.as_deref(),
+        )?
+        .pop()
+        .expect("one database");
+        if restored.contains(&database) {
+            anyhow::bail!("the archive has more than one dump restored into database {database:?}");
+        }
+        let restore = async {
+            if !existing.contains(&database) {
+                create_database(cli, kind, &database).await?;
+            }
+            let mut conn_params = conn_params.clone();
+            conn_params.db(kind.select(&database))?;
+            let mut conn = conn_params
+                .connect()
+                .await
+                .with_context(|| format!("cannot connect to database {database:?}"))?;
+            eprintln!(
+                "\nRestoring database {database:?} from archive. Total size: {:.02} MB",
+                size as f64 / 1048576.0
+            );
+            log::info!(target: LOG_TARGET, "Restoring database {database:?}");
+            let open = |position: Arc<AtomicU64>| async move {
+                let input = CountingReader::new(data, position);
+                let (header, packets) = DumpHeader::read(input)
+                    .await
+                    .context("Failed to read dump from archive")?;
+                anyhow::Ok((header, Box::pin(packets) as Blocks, Some(size)))
+            };
+            let (stats, verification) = restore_dump(&mut conn, params, open)
+                .await
+                .inspect_err(
+                    |e| log::info!(target: LOG_TARGET, "Database {database:?} failed: {e:#}"),
+                )
+                .with_context(|| format!("restoring database {database:?}"))?;
+            log::info!(target: LOG_TARGET, "Restored database {database:?}: {stats}");
+            anyhow::Ok((stats, verification))
+        };
+        let Some((stats, verification)) = failures.check(restore.await)? else {
+            continue;
+        };
+        total.bytes += stats.bytes;
+        if let Some(lines) = verification {
+            eprint!("Database {database:?}: ");
+            print_verification(&lines);
+        }
+        restored.push(database);
+    }
+    total.elapsed = start.elapsed();
+    eprintln!("Restored {} databases: {total}", restored.len());
+    log::info!(target: LOG_TARGET, "Restored {} databases: {total}", restored.len());
+    Ok(restored)
+}
+
+/// A database to restore with `restore --all`
+struct DatabaseDump {
+    name: String,
+    parts: Vec<PathBuf>,
+    /// the database didn't exist and was created for the restore
+    created: bool,
+}
+
+/// First delay before reconnecting when the connection is lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Restores a single database of `restore --all`, holding a job slot while
+/// connected. Returns the database name, the number of retries needed, the
+/// restore stats and the `--verify-after` results.
+async fn restore_database(
+    conn_params: &Connector,
+    options: &Options,
+    params: &RestoreCmd,
+    kind: DbKind,
+    dump: DatabaseDump,
+    limiter: &JobLimiter,
+) -> anyhow::Result<(String, u32, RestoreStats, Option<Vec<String>>)> {
+    let DatabaseDump {
+        name: database,
+        parts,
+        created,
+    } = dump;
+    let mut conn_params = conn_params.clone();
+    conn_params.db(kind.select(&database))?;
+    let mut permit = limiter.acquire().await;
+    log::info!(target: LOG_TARGET, "Restoring database {database:?}");
+    let reconnect = Backoff::new(params.retries + 1, RECONNECT_DELAY);
+    let mut attempt = 0;
+    loop {
+        let result = match conn_params.connect().await {
+            Ok(mut db_conn) => restore_db(&mut db_conn, params, &parts).await,
+            Err(e) if is_connection_limit(&e) && limiter.jobs() > 1 => {
+                limiter.shrink(permit);
+                print::warn!(
+                    "Server connection limit reached, \
+                     continuing with {} concurrent restore jobs",
+                    limiter.jobs()
+                );
+                permit = limiter.acquire().await;
+                continue;
+            }
+            Err(e) => Err(e.context(format!("cannot connect to database {database:?}"))),
+        };
+        match result {
+            Err(e) if attempt < params.retries && is_connection_lost(&e) => {
+                attempt += 1;
+                let delay = reconnect.jittered_delay(attempt);
+                print::warn!(
+                    "Connection lost while restoring database {database:?}: {e:#}. \
+                     Retrying in {} ({attempt}/{})",
+                    humantime::format_duration(delay),
+                    params.retries
+                );
+                log::info!(
+                    target: LOG_TARGET,
+                    "Retrying database {database:?} ({attempt}/{}): {e:#}",
+                    params.retries
+                );
+                tokio::time::sleep(delay).await;
+                let non_empty = async {
+                    let mut db_conn = conn_params.connect().await?;
+                    is_non_empty_db(&mut db_conn).await
+                };
+                match retry_action(&database, created, non_empty).await? {
+                    RetryAction::Recreate => {
+                        let mut cli = options.conn_params.connect().await?;
+                        cli.execute(&kind.drop_statement(&database), &())
+                            .await
+                            .with_context(|| format!("error dropping database {database:?}"))?;
+                        create_database(&mut cli, kind, &database).await?;
+                    }
+                    RetryAction::Reuse => {}
+                }
+            }
+            result => {
+                let (stats, verification) = result
+                    .inspect_err(
+                        |e| log::info!(target: LOG_TARGET, "Database {database:?} failed: {e:#}"),
+                    )
+                    .with_context(|| format!("restoring database {database:?}"))?;
+                log::info!(target: LOG_TARGET, "Restored database {database:?}: {stats}");
+                drop(permit);
+                return Ok((database, attempt, stats, verification));
+            }
+        }
+    }
+}
+
+/// How the target database is prepared for another attempt after the
+/// connection was lost during its restore
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RetryAction {
+    /// The database was created by this restore: drop and create it again
+    Recreate,
+    /// The database existed before but nothing was restored into it yet
+    Reuse,
+}
+
+/// Decides how to retry the restore of `database`. A database that
+/// existed before the restore is never dropped, so once data made it into
+/// it (`non_empty`, only checked for such databases) the restore fails
+/// instead of hitting the non-empty check on the next attempt.
+async fn retry_action(
+    database: &str,
+    created: bool,
+    non_empty: impl Future<Output = anyhow::Result<bool>>,
+) -> anyhow::Result<RetryAction> {
+    if created {
+        return Ok(RetryAction::Recreate);
+    }
+    if non_empty.await? {
+        let err = anyhow::Error::from(RestoreError::PartiallyRestored {
+            database: database.into(),
+        });
+        return Err(err
+            .hint("Drop or empty the database, then restore it again")
+            .into());
+    }
+    Ok(RetryAction::Reuse)
+}
+
+/// Whether the server organizes data in databases or, since 5.0, in
+/// branches. `restore --all` addresses and creates all dumps the same way,
+/// based on the version of the server it first connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DbKind {
+    Database,
+    Branch,
+}
+
+impl DbKind {
+    fn of_server(version: &ver::Specific) -> DbKind {
+        if version.major >= 5 {
+            DbKind::Branch
+        } else {
+            DbKind::Database
+        }
+    }
+    fn select(self, name: &str) -> DatabaseBranch {
+        match self {
+            DbKind::Database => DatabaseBranch::Database(name.into()),
+            DbKind::Branch => DatabaseBranch::Branch(name.into()),
+        }
+    }
+    fn create_statement(self, name: &str) -> String {
+        match self {
+            DbKind::Database => format!("CREATE DATABASE {}", quote_name(name)),
+            DbKind::Branch => format!("CREATE EMPTY BRANCH {}", quote_name(name)),
+        }
+    }
+    fn drop_statement(self, name: &str) -> String {
+        match self {
+            DbKind::Database => format!("DROP DATABASE {}", quote_name(name)),
+            DbKind::Branch => format!("DROP BRANCH {}", quote_name(name)),
+        }
+    }
+}
+
+async fn create_database(cli: &mut Connection, kind: DbKind, database: &str) -> anyhow::Result<()> {
+    cli.execute(&kind.create_statement(database), &())
+        .await
+        .with_context(|| format!("error creating database {database:?}"))?;
+    Ok(())
+}
+
+fn is_connection_lost(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        e.downcast_ref::<Error>()
+            .is_some_and(|e| e.is::<ClientConnectionError>())
+    })
+}
+
+/// Implements `restore --create-instance`: creates a local instance,
+/// restores the dump into it and destroys the instance again if creation
+/// or restore fails, so no half-built instance is left behind.
+pub fn restore_into_new_instance(
+    options: &crate::options::Options,
+    params: &RestoreCmd,
+    name: &str,
+) -> anyhow::Result<()> {
+    let inst_name = InstanceName::from_str(name)?;
+    let InstanceName::Local(local_name) = &inst_name else {
+        anyhow::bail!("`--create-instance` only supports local instances");
+    };
+    if credentials::all_instance_names()?.contains(local_name) {
+        Err(anyhow::anyhow!("instance {local_name:?} already exists")
+            .hint("Restore into the existing instance with `-I <name>` or pick another name"))?;
+    }
+
+    let result = create::run(
+        &create::Command {
+            cloud_opts: options.cloud_options.clone(),
+            name: Some(inst_name.clone()),
+            instance: None,
+            nightly: false,
+            version: None,
+            channel: None,
+            port: None,
+            cloud_params: CloudInstanceParams {
+                region: None,
+                billables: CloudInstanceBillables {
+                    tier: None,
+                    compute_size: None,
+                    storage_size: None,
+                },
+            },
+            cloud_backup_source: create::CloudBackupSourceParams {
+                from_backup_id: None,
+                from_instance: None,
+            },
+            start_conf: None,
+            default_user: None,
+            default_branch: None,
+            non_interactive: true,
+        },
+        options,
+    )
+    .and_then(|()| restore_new_instance(&inst_name, params));
+
+    if let Err(e) = result {
+        print::error!("{e:#}");
+        msg!(
+            "Destroying instance {} ...",
+            local_name.as_str().emphasized()
+        );
+        match destroy::force_by_name(&inst_name, options) {
+            Ok(()) => {}
+            Err(e) if e.is::<destroy::InstanceNotFound>() => {}
+            Err(e) => print::warn!("Could not destroy instance {local_name:?}: {e:#}"),
+        }
+        return Err(ExitCode::new(1).into());
+    }
+
+    msg!(
+        "Dump restored into new instance {}.",
+        local_name.as_str().emphasized()
+    );
+    msg!("To connect to the instance run:");
+    msg!("  {BRANDING_CLI_CMD} -I {local_name}");
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn restore_new_instance(name: &InstanceName, params: &RestoreCmd) -> anyhow::Result<()> {
+    let config = Builder::new().instance_string(&name.to_string()).build()?;
+    let mut cli = Connection::connect(&config, QUERY_TAG).await?;
+    let options = Options {
+        command_line: true,
+        styler: None,
+        conn_params: Connector::new(Ok(config)),
+        instance_name: Some(name.clone()),
+    };
+    restore(&mut cli, &options, params).await
+}
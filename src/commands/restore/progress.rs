@@ -0,0 +1,201 @@
+//! Progress reporting and logging of the blocks sent to the server.
+
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll, ready};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures_util::stream::StreamExt;
+use indicatif::{HumanBytes, ProgressBar};
+use tokio::io::{self, AsyncRead};
+use tokio_stream::Stream;
+
+use gel_errors::Error;
+
+use crate::commands::parser::Restore as RestoreCmd;
+
+/// Counts bytes read from the dump input into a shared position.
+pub(super) struct CountingReader<R> {
+    inner: R,
+    position: Arc<AtomicU64>,
+}
+
+impl<R> CountingReader<R> {
+    pub(super) fn new(inner: R, position: Arc<AtomicU64>) -> Self {
+        Self { inner, position }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = (buf.filled().len() - before) as u64;
+        self.position.fetch_add(read, Ordering::Relaxed);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Renders the progress message from the bytes read and the dump size
+type ProgressTemplate = Box<dyn Fn(u64, Option<u64>) -> String + Send + Sync>;
+
+/// Reports restore progress as the position in the dump input, i.e. the
+/// bytes read from the file or stdin, against the size of that input.
+///
+/// Packets are not counted themselves: once the input is decompressed
+/// they are larger than the file, and the progress would run past the
+/// total.
+pub(super) struct StreamWithProgress<T: Stream<Item = Result<Bytes, Error>> + Unpin> {
+    input: T,
+    pub(super) bar: ProgressBar,
+    position: Arc<AtomicU64>,
+    total: Option<u64>,
+    speed_checkpoint: (Instant, u64),
+    last_estimated_speed: f64,
+    template: Option<ProgressTemplate>,
+}
+
+impl<T: Stream<Item = Result<Bytes, Error>> + Unpin> StreamWithProgress<T> {
+    pub(super) fn new(
+        input: T,
+        bar: ProgressBar,
+        position: Arc<AtomicU64>,
+        total: Option<u64>,
+    ) -> Self {
+        Self {
+            input,
+            bar,
+            position,
+            total,
+            speed_checkpoint: (Instant::now(), 0),
+            last_estimated_speed: 0.0,
+            template: None,
+        }
+    }
+    /// Replaces the default "Restoring database: ..." message, e.g. to
+    /// word it for a branch. The template is given the bytes read so far
+    /// and the dump size, if known.
+    pub(super) fn set_template(
+        &mut self,
+        template: impl Fn(u64, Option<u64>) -> String + Send + Sync + 'static,
+    ) {
+        self.template = Some(Box::new(template));
+    }
+    pub(super) fn progress(&self) -> u64 {
+        self.position.load(Ordering::Relaxed)
+    }
+    fn message(&self, progress: u64, estimated_speed: f64) -> String {
+        if let Some(template) = &self.template {
+            return template(progress, self.total);
+        }
+        if let Some(total) = self.total {
+            format!(
+                "Restoring database: {}/{} of dump read ({}/s)",
+                HumanBytes(progress),
+                HumanBytes(total),
+                HumanBytes(estimated_speed as u64)
+            )
+        } else {
+            format!(
+                "Restoring database: {} of dump read ({}/s)",
+                HumanBytes(progress),
+                HumanBytes(estimated_speed as u64)
+            )
+        }
+    }
+}
+
+impl<T: Stream<Item = Result<Bytes, Error>> + Unpin> Stream for StreamWithProgress<T> {
+    type Item = Result<Bytes, Error>;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let next = ready!(this.input.poll_next_unpin(cx));
+        if let Some(Ok(_)) = &next {
+            this.bar.tick();
+            let progress = this.progress();
+
+            let elapsed = this.speed_checkpoint.0.elapsed().as_secs_f64();
+            let estimated_speed = if elapsed > 1.0 {
+                let estimated_speed = (progress - this.speed_checkpoint.1) as f64 / elapsed;
+                if this.speed_checkpoint.0.elapsed() > Duration::from_secs(30) {
+                    this.speed_checkpoint = (Instant::now(), progress);
+                }
+                (estimated_speed + this.last_estimated_speed) / 2.0
+            } else {
+                this.last_estimated_speed
+            };
+
+            this.last_estimated_speed = estimated_speed;
+            let message = this.message(progress, estimated_speed);
+            this.bar.set_message(message);
+        } else {
+            this.bar.set_message("Processing data");
+            this.bar.finish();
+        }
+        Poll::Ready(next)
+    }
+}
+
+/// Logs every data block to `log` as it is sent to the server, so a
+/// rejected block can be located in the dump.
+pub(super) fn log_blocks<S>(
+    packets: S,
+    mut log: impl FnMut(fmt::Arguments),
+) -> impl Stream<Item = Result<Bytes, Error>> + Unpin
+where
+    S: Stream<Item = Result<Bytes, Error>> + Unpin,
+{
+    let mut index = 0;
+    let mut cumulative = 0;
+    packets.inspect(move |packet| {
+        if let Ok(block) = packet {
+            index += 1;
+            cumulative += block.len() as u64;
+            log(format_args!(
+                "Block {index}: {} bytes, {cumulative} bytes total",
+                block.len()
+            ));
+        }
+    })
+}
+
+pub(super) fn count_blocks<S>(
+    packets: S,
+    blocks: Arc<AtomicU64>,
+) -> impl Stream<Item = Result<Bytes, Error>> + Unpin
+where
+    S: Stream<Item = Result<Bytes, Error>> + Unpin,
+{
+    packets.inspect(move |packet| {
+        if packet.is_ok() {
+            blocks.fetch_add(1, Ordering::Relaxed);
+        }
+    })
+}
+
+pub(super) fn progress_bar(params: &RestoreCmd) -> ProgressBar {
+    if params.no_progress {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new_spinner()
+    }
+}
+
+/// Renders `restore --progress-template` for `read` bytes of a dump of
+/// `total` bytes
+pub(super) fn render_template(template: &str, read: u64, total: Option<u64>) -> String {
+    let total = match total {
+        Some(total) => HumanBytes(total).to_string(),
+        None => "unknown".into(),
+    };
+    template
+        .replace("{read}", &HumanBytes(read).to_string())
+        .replace("{total}", &total)
+}
@@ -0,0 +1,1282 @@
+use std::cell::{Cell, RefCell};
+use std::io::{Cursor, Read};
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::task::{Context, Poll};
+
+use indicatif::ProgressBar;
+use sha1::Digest;
+use tokio::io::AsyncWriteExt;
+
+use gel_errors::{ClientError, ErrorKind};
+
+use super::*;
+use crate::commands::restore::header::{DUMP_MAGIC, Packets};
+use crate::hint::HintedError;
+
+#[tokio::test]
+async fn test_packets() {
+    let mut fake_stream = Vec::new();
+    // The header is 1 + 20 + 4 bytes: the type, a SHA-1 of the data, and a length
+    for packet in 0..100 {
+        let len: u32 = 16 + packet;
+        let data = vec![b'.'; len as usize];
+        let mut buf = BytesMut::with_capacity(1 + 20 + 4 + len as usize);
+        buf.extend_from_slice(&[0; 1 + 20 + 4]);
+        buf[0] = if packet == 0 { b'H' } else { b'D' };
+        buf[1..21].copy_from_slice(&sha1::Sha1::digest(&data)[..]);
+        buf[21..25].copy_from_slice(&len.to_be_bytes());
+        fake_stream.extend_from_slice(&buf.freeze());
+        fake_stream.extend_from_slice(&data);
+    }
+
+    // Use a tokio task with a duplex to feed the fake stream in chunks of 11 bytes
+    let (mut tx, rx) = tokio::io::duplex(100);
+    let task = tokio::spawn(async move {
+        for chunk in fake_stream.chunks(11) {
+            tx.write_all(chunk).await.unwrap();
+        }
+    });
+
+    let mut packets = Packets::new(Box::new(rx));
+    let mut packet = 0;
+    while let Some(data) = packets.next().await {
+        let data = data.unwrap();
+        let expected = Bytes::from(vec![b'.'; 16 + packet]);
+        assert_eq!(data, expected);
+        packet += 1;
+    }
+
+    assert_eq!(packet, 100);
+    task.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_packets_hash_mismatch() {
+    let mut fake_stream = Vec::new();
+    fake_stream.push(b'H');
+    fake_stream.extend_from_slice(&[0; 20]);
+    fake_stream.extend_from_slice(&4u32.to_be_bytes());
+    fake_stream.extend_from_slice(b"data");
+
+    let mut packets = Packets::new(std::io::Cursor::new(fake_stream));
+    let err = packets.next().await.unwrap().unwrap_err();
+    let source = std::error::Error::source(&err).and_then(|e| e.downcast_ref::<RestoreError>());
+    assert!(matches!(
+        source,
+        Some(RestoreError::BlockHashMismatch { packet: 0 })
+    ));
+}
+
+/// Endless repetition of one data packet, counting the bytes read.
+struct RepeatedBlocks {
+    packet: Vec<u8>,
+    remaining: usize,
+    offset: usize,
+    read: Arc<AtomicUsize>,
+}
+
+impl AsyncRead for RepeatedBlocks {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if self.remaining > 0 {
+            let n = buf.remaining().min(self.packet.len() - self.offset);
+            let offset = self.offset;
+            buf.put_slice(&self.packet[offset..][..n]);
+            self.offset += n;
+            if self.offset == self.packet.len() {
+                self.offset = 0;
+                self.remaining -= 1;
+            }
+            self.read.fetch_add(n, Ordering::SeqCst);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn packet(kind: u8, data: &[u8]) -> Vec<u8> {
+    let mut buf = vec![kind];
+    buf.extend_from_slice(&sha1::Sha1::digest(data)[..]);
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+    buf
+}
+
+#[tokio::test]
+async fn test_packets_bounded_read_ahead() {
+    const BLOCK: usize = 1 << 20;
+    const BLOCKS: usize = 48;
+
+    let read = Arc::new(AtomicUsize::new(0));
+    let data = vec![b'x'; BLOCK];
+    let input = std::io::Cursor::new(packet(b'H', b"header")).chain(RepeatedBlocks {
+        packet: packet(b'D', &data),
+        remaining: BLOCKS,
+        offset: 0,
+        read: read.clone(),
+    });
+
+    let mut packets = Packets::new(input);
+    assert_eq!(packets.next().await.unwrap().unwrap(), &b"header"[..]);
+    let mut consumed = 0;
+    let mut max_ahead = 0;
+    while let Some(block) = packets.next().await {
+        let block = block.unwrap();
+        assert_eq!(block.len(), BLOCK);
+        consumed += 1 + 20 + 4 + block.len();
+        max_ahead = max_ahead.max(read.load(Ordering::SeqCst) - consumed);
+    }
+    assert_eq!(consumed, BLOCKS * (1 + 20 + 4 + BLOCK));
+    // never more than a couple of blocks ahead of the consumer
+    assert!(max_ahead <= 2 * BLOCK, "read {max_ahead} bytes ahead");
+}
+
+#[tokio::test]
+async fn test_progress_gzip_dump_within_total() {
+    use libflate::gzip;
+    use std::io::Write;
+
+    let mut dump = Vec::new();
+    for packet in 0..50 {
+        let data = vec![b'.'; 10_000];
+        dump.push(if packet == 0 { b'H' } else { b'D' });
+        dump.extend_from_slice(&sha1::Sha1::digest(&data)[..]);
+        dump.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        dump.extend_from_slice(&data);
+    }
+    let mut encoder = gzip::Encoder::new(Vec::new()).unwrap();
+    encoder.write_all(&dump).unwrap();
+    let compressed = encoder.finish().into_result().unwrap();
+    let total = compressed.len() as u64;
+    // counting decompressed bytes would overshoot by far
+    assert!(dump.len() as u64 > 10 * total);
+
+    struct SyncCounting(Cursor<Vec<u8>>, Arc<AtomicU64>);
+    impl Read for SyncCounting {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.read(buf)?;
+            self.1.fetch_add(n as u64, Ordering::Relaxed);
+            Ok(n)
+        }
+    }
+
+    let position = Arc::new(AtomicU64::new(0));
+    let reader = SyncCounting(Cursor::new(compressed), position.clone());
+    let (mut tx, rx) = tokio::io::duplex(4096);
+    let task = tokio::spawn(async move {
+        let mut decoder = gzip::Decoder::new(reader).unwrap();
+        let mut buf = [0; 1024];
+        loop {
+            let n = decoder.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            tx.write_all(&buf[..n]).await.unwrap();
+        }
+    });
+
+    let packets = Packets::new(Box::new(rx));
+    let mut stream = StreamWithProgress::new(packets, ProgressBar::hidden(), position, Some(total));
+    let mut blocks = 0;
+    while let Some(block) = stream.next().await {
+        block.unwrap();
+        blocks += 1;
+        assert!(stream.progress() <= total);
+    }
+    assert_eq!(blocks, 50);
+    assert_eq!(stream.progress(), total);
+    task.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_read_ahead_blocks() {
+    let read = Arc::new(AtomicUsize::new(0));
+    let source = |items: Vec<Result<&'static [u8], &'static str>>| -> Blocks {
+        let read = read.clone();
+        Box::pin(futures_util::stream::iter(items).map(move |item| {
+            read.fetch_add(1, Ordering::SeqCst);
+            item.map(Bytes::from_static)
+                .map_err(ClientError::with_message)
+        }))
+    };
+
+    // blocks are read ahead up to the window, and yielded in order
+    let items: Vec<_> = (0..10u8)
+        .map(|i| Ok(&b"0123456789"[i as usize..][..1]))
+        .collect();
+    let mut blocks = read_ahead_blocks(source(items), 3);
+    let first = blocks.next().await.unwrap().unwrap();
+    assert_eq!(&first[..], b"0");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let ahead = read.load(Ordering::SeqCst);
+    assert!((2..=5).contains(&ahead), "{ahead} blocks read");
+    let mut rest = Vec::new();
+    while let Some(block) = blocks.next().await {
+        rest.extend_from_slice(&block.unwrap());
+    }
+    assert_eq!(rest, b"123456789");
+
+    // nothing is read past an error, which ends the stream
+    read.store(0, Ordering::SeqCst);
+    let items = vec![Ok(&b"a"[..]), Err("bad block"), Ok(&b"b"[..])];
+    let blocks = read_ahead_blocks(source(items), 4);
+    let results: Vec<_> = blocks.collect().await;
+    assert_eq!(results.len(), 2);
+    assert_eq!(&results[0].as_ref().unwrap()[..], b"a");
+    assert!(
+        results[1]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("bad block")
+    );
+    assert_eq!(read.load(Ordering::SeqCst), 2);
+
+    // a window of one reads on demand, as without read-ahead
+    read.store(0, Ordering::SeqCst);
+    let mut blocks = read_ahead_blocks(source(vec![Ok(&b"x"[..]), Ok(&b"y"[..])]), 1);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(read.load(Ordering::SeqCst), 0);
+    assert_eq!(&blocks.next().await.unwrap().unwrap()[..], b"x");
+    assert_eq!(read.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_restore_timeout() {
+    let position = Arc::new(AtomicU64::new(0));
+    let blocks = Arc::new(AtomicU64::new(0));
+    let slow = futures_util::stream::iter(0..100).then(|_| async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        Ok(Bytes::from_static(b"0123456789"))
+    });
+    let mut stream = count_blocks(Box::pin(slow), blocks.clone());
+    let restore = async {
+        while let Some(block) = stream.next().await {
+            position.fetch_add(block?.len() as u64, Ordering::Relaxed);
+        }
+        anyhow::Ok(())
+    };
+    let err = limit_restore(
+        Some(Duration::from_millis(200)),
+        &position,
+        &blocks,
+        restore,
+    )
+    .await
+    .unwrap_err();
+    let Some(&RestoreError::Timeout {
+        timeout,
+        bytes,
+        blocks: sent,
+    }) = err.downcast_ref()
+    else {
+        panic!("unexpected error: {err:#}");
+    };
+    assert_eq!(timeout, Duration::from_millis(200));
+    assert!(sent > 0 && sent < 100, "{sent}");
+    assert_eq!(bytes, sent * 10);
+    assert!(err.to_string().contains("partially restored"), "{err}");
+
+    limit_restore(Some(Duration::from_secs(10)), &position, &blocks, async {
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_progress_template() {
+    let block = || futures_util::stream::iter([Ok(Bytes::from_static(b"block"))]);
+    let position = Arc::new(AtomicU64::new(42));
+
+    let mut stream =
+        StreamWithProgress::new(block(), ProgressBar::hidden(), position.clone(), Some(100));
+    stream.next().await.unwrap().unwrap();
+    assert_eq!(
+        stream.bar.message(),
+        "Restoring database: 42 B/100 B of dump read (0 B/s)"
+    );
+
+    let mut stream = StreamWithProgress::new(block(), ProgressBar::hidden(), position, Some(100));
+    stream.set_template(|progress, total| {
+        format!("Restoring branch: {progress} of {} bytes", total.unwrap())
+    });
+    stream.next().await.unwrap().unwrap();
+    assert_eq!(stream.bar.message(), "Restoring branch: 42 of 100 bytes");
+
+    assert_eq!(
+        render_template("Restoring branch: {read} of {total}", 2048, Some(1 << 20)),
+        "Restoring branch: 2.00 KiB of 1.00 MiB"
+    );
+    assert_eq!(
+        render_template("{read}/{total}", 512, None),
+        "512 B/unknown"
+    );
+}
+
+#[tokio::test]
+async fn test_find_init_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let err = find_init_files(dir.path()).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::MissingInit { .. })
+    ));
+
+    fs::write(dir.path().join("init.edgeql"), "").await.unwrap();
+    assert_eq!(
+        find_init_files(dir.path()).await.unwrap(),
+        [dir.path().join("init.edgeql")]
+    );
+
+    // `init/` takes precedence, files are applied in name order
+    let init = dir.path().join("init");
+    fs::create_dir(&init).await.unwrap();
+    fs::write(
+        init.join("002-alter-role.edgeql"),
+        "ALTER ROLE r { SET password := 'b' };",
+    )
+    .await
+    .unwrap();
+    fs::write(
+        init.join("001-create-role.edgeql"),
+        "CREATE SUPERUSER ROLE r;",
+    )
+    .await
+    .unwrap();
+    fs::write(init.join("README"), "").await.unwrap();
+    assert_eq!(
+        find_init_files(dir.path()).await.unwrap(),
+        [
+            init.join("001-create-role.edgeql"),
+            init.join("002-alter-role.edgeql"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_find_dumps_empty_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let err = find_dumps(dir.path(), false).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::MissingInit { .. })
+    ));
+
+    fs::write(dir.path().join("init.edgeql"), "").await.unwrap();
+    fs::write(dir.path().join("main.dump.gz"), "")
+        .await
+        .unwrap();
+    let err = find_dumps(dir.path(), false).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::NoDumps { .. })
+    ));
+    assert!(find_dumps(dir.path(), true).await.unwrap().is_empty());
+
+    fs::write(dir.path().join("main.dump"), "").await.unwrap();
+    let dumps = find_dumps(dir.path(), false).await.unwrap();
+    assert_eq!(
+        dumps,
+        vec![("main".into(), vec![dir.path().join("main.dump")])]
+    );
+}
+
+#[tokio::test]
+async fn test_verbose_logs_blocks() {
+    let lines = RefCell::new(Vec::new());
+    let log = |args: fmt::Arguments| lines.borrow_mut().push(args.to_string());
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&1u16.to_be_bytes());
+    header.extend_from_slice(&103u16.to_be_bytes());
+    header.extend_from_slice(&3u32.to_be_bytes());
+    header.extend_from_slice(b"6.1");
+    header.extend_from_slice(&2u16.to_be_bytes());
+    header.extend_from_slice(&0u16.to_be_bytes());
+    header.extend_from_slice(&4u32.to_be_bytes());
+    header.extend_from_slice(b"DDL;");
+    let header = DumpHeader {
+        version: 1,
+        header: Bytes::from(header),
+    };
+    log_header(&header, log);
+
+    let blocks = [Bytes::from_static(b"abc"), Bytes::from_static(b"de")].map(Ok::<_, Error>);
+    let mut stream = log_blocks(tokio_stream::iter(blocks), log);
+    while stream.next().await.is_some() {}
+    drop(stream);
+
+    assert_eq!(
+        lines.into_inner(),
+        [
+            "Dump header server version: 6.1",
+            "Dump header protocol: 2.0",
+            "Dump header schema DDL: 4 bytes",
+            "Block 1: 3 bytes, 3 bytes total",
+            "Block 2: 2 bytes, 5 bytes total",
+        ]
+    );
+}
+
+async fn limited_job(limiter: &JobLimiter, running: &Cell<usize>, max_running: &Cell<usize>) {
+    let _permit = limiter.acquire().await;
+    running.set(running.get() + 1);
+    max_running.set(max_running.get().max(running.get()));
+    for _ in 0..5 {
+        tokio::task::yield_now().await;
+    }
+    running.set(running.get() - 1);
+}
+
+#[tokio::test]
+async fn test_job_limiter_bounds_concurrency() {
+    let limiter = JobLimiter::new(3);
+    let running = Cell::new(0);
+    let max_running = Cell::new(0);
+    let mut jobs = (0..10)
+        .map(|_| limited_job(&limiter, &running, &max_running))
+        .collect::<FuturesUnordered<_>>();
+    while jobs.next().await.is_some() {}
+    assert_eq!(max_running.get(), 3);
+
+    limiter.shrink(limiter.acquire().await);
+    assert_eq!(limiter.jobs(), 2);
+    max_running.set(0);
+    let mut jobs = (0..10)
+        .map(|_| limited_job(&limiter, &running, &max_running))
+        .collect::<FuturesUnordered<_>>();
+    while jobs.next().await.is_some() {}
+    assert_eq!(max_running.get(), 2);
+
+    // the last slot is kept
+    let limiter = JobLimiter::new(1);
+    limiter.shrink(limiter.acquire().await);
+    assert_eq!(limiter.jobs(), 1);
+    drop(limiter.acquire().await);
+}
+
+#[test]
+fn test_jobs_within_limit() {
+    assert_eq!(jobs_within_limit(50, 20), 19);
+    assert_eq!(jobs_within_limit(4, 20), 4);
+    assert_eq!(jobs_within_limit(4, 1), 1);
+    assert_eq!(jobs_within_limit(4, 0), 1);
+}
+
+#[tokio::test]
+async fn test_retry_action() {
+    let not_checked = async { Err(anyhow::anyhow!("created databases are not checked")) };
+    assert_eq!(
+        retry_action("app", true, not_checked).await.unwrap(),
+        RetryAction::Recreate
+    );
+    assert_eq!(
+        retry_action("main", false, async { Ok(false) })
+            .await
+            .unwrap(),
+        RetryAction::Reuse
+    );
+    let err = retry_action("main", false, async { Ok(true) })
+        .await
+        .unwrap_err();
+    let err = &err.downcast_ref::<HintedError>().unwrap().error;
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::PartiallyRestored { database }) if database == "main"
+    ));
+}
+
+#[test]
+fn test_restore_stats() {
+    let stats = RestoreStats {
+        bytes: 1_320_702_444,
+        elapsed: Duration::from_millis(45_600),
+    };
+    assert_eq!(stats.to_string(), "1.23 GiB in 45.6s (27.62 MiB/s)");
+    let stats = RestoreStats {
+        bytes: 512,
+        elapsed: Duration::ZERO,
+    };
+    assert_eq!(stats.to_string(), "512 B in 0.0s (500.00 KiB/s)");
+}
+
+#[tokio::test]
+async fn test_analyze_after() {
+    let mut executed = Vec::new();
+    analyze(|statement| {
+        executed.push(statement);
+        async { Ok(()) }
+    })
+    .await
+    .unwrap();
+    assert_eq!(executed, ["administer statistics_update()"]);
+
+    // unsupported by the server
+    analyze(|_| async {
+        Err(gel_errors::EdgeQLSyntaxError::with_message(
+            "unexpected 'administer'",
+        ))
+    })
+    .await
+    .unwrap();
+
+    let err = analyze(|_| async { Err(ClientConnectionError::with_message("connection lost")) })
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "error updating statistics");
+}
+
+#[test]
+fn test_verify_quote_type() {
+    assert_eq!(quote_type("default::User"), "default::User");
+    assert_eq!(quote_type("my-app::Order"), "`my-app`::Order");
+}
+
+#[test]
+fn test_init_statement_error() {
+    let stmt = "CREATE ROLE admin {\n    SET password_hash := 'x';\n};";
+    let err = init_statement_error(anyhow::anyhow!("invalid syntax"), 3, stmt, None);
+    assert_eq!(
+        err.to_string(),
+        "statement 3 of the init script failed: CREATE ROLE admin {..."
+    );
+    assert!(err.downcast_ref::<crate::hint::HintedError>().is_none());
+
+    let err = init_statement_error(
+        anyhow::anyhow!("extension package 'postgis' does not exist"),
+        1,
+        "CREATE EXTENSION postgis;",
+        None,
+    );
+    assert!(err.downcast_ref::<crate::hint::HintedError>().is_some());
+    assert!(format!("{err:#}").contains("statement 1 of the init script failed"));
+}
+
+#[test]
+fn test_created_extension() {
+    assert_eq!(
+        created_extension("CREATE EXTENSION pgvector VERSION '0.5';").as_deref(),
+        Some("pgvector")
+    );
+    assert_eq!(
+        created_extension("# comment\ncreate extension `postgis`;").as_deref(),
+        Some("postgis")
+    );
+    assert_eq!(
+        created_extension("CREATE EXTENSION PACKAGE foo VERSION '1.0' { };"),
+        None
+    );
+    assert_eq!(created_extension("CREATE TYPE Extension;"), None);
+}
+
+#[test]
+fn test_init_missing_extension_hint() {
+    let script = [
+        "CREATE EXTENSION pgvector;",
+        "CREATE EXTENSION postgis;",
+        "CREATE TYPE Place { CREATE PROPERTY location -> ext::postgis::geometry };",
+    ];
+    let err = anyhow::anyhow!("type 'ext::postgis::geometry' does not exist");
+    assert!(is_missing_extension(&err));
+    let extensions = InitExtensions {
+        required: script.iter().filter_map(|s| created_extension(s)).collect(),
+        installed: Some(["pgvector".to_string()].into()),
+    };
+    let err = init_statement_error(err, 3, script[2], Some(&extensions));
+    let hint = err
+        .downcast_ref::<crate::hint::HintedError>()
+        .expect("hinted")
+        .hint
+        .to_string();
+    assert!(
+        hint.contains("requires these extensions: pgvector, postgis."),
+        "{hint}"
+    );
+    assert!(
+        hint.contains("Installed on this server: pgvector."),
+        "{hint}"
+    );
+    assert!(hint.contains("Install postgis first"), "{hint}");
+
+    assert!(!is_missing_extension(&anyhow::anyhow!(
+        "role 'admin' does not exist"
+    )));
+}
+
+#[test]
+fn test_db_kind_by_server_version() {
+    let old = DbKind::of_server(&"4.8".parse().unwrap());
+    assert_eq!(old, DbKind::Database);
+    assert!(matches!(old.select("app"), DatabaseBranch::Database(name) if name == "app"));
+    assert_eq!(old.create_statement("app"), "CREATE DATABASE app");
+    assert_eq!(old.drop_statement("app"), "DROP DATABASE app");
+
+    let new = DbKind::of_server(&"5.0".parse().unwrap());
+    assert_eq!(new, DbKind::Branch);
+    assert!(matches!(new.select("app"), DatabaseBranch::Branch(name) if name == "app"));
+    assert_eq!(new.create_statement("app"), "CREATE EMPTY BRANCH app");
+    assert_eq!(new.drop_statement("app"), "DROP BRANCH app");
+}
+
+#[test]
+fn test_connect_timeout_propagates() {
+    use crate::commands::parser::Common;
+    use crate::options::{Command, SubcommandOption};
+    use clap::FromArgMatches;
+
+    let parse = |args: &[&str]| {
+        let matches = crate::options::Options::command()
+            .try_get_matches_from(args)
+            .unwrap();
+        let conn = crate::options::RawOptions::from_arg_matches(&matches)
+            .unwrap()
+            .conn;
+        let cmd = SubcommandOption::from_arg_matches(&matches).unwrap();
+        let Some(Command::Common(Common::Restore(params))) = cmd.subcommand else {
+            panic!("not a restore command: {args:?}");
+        };
+        let conn_params = Connector::new(Err(anyhow::anyhow!("not configured")))
+            .with_connect_timeout(conn.connect_timeout);
+        restore_wait(&conn_params, &params)
+    };
+    let timeout = Duration::from_secs(45);
+    assert_eq!(
+        parse(&["gel", "--connect-timeout=45s", "restore", "dump.db"]),
+        timeout
+    );
+    assert_eq!(
+        parse(&["gel", "restore", "--connect-timeout=45s", "dump.db"]),
+        timeout
+    );
+    assert_eq!(parse(&["gel", "restore", "dump.db"]), RESTORE_WAIT);
+}
+
+#[tokio::test]
+async fn test_empty_check_timeout() {
+    let slow = async {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        Ok(false)
+    };
+    let err = check_empty(slow, Duration::from_millis(10))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<RestoreError>(),
+        Some(RestoreError::EmptyCheckTimeout { .. })
+    ));
+    assert!(
+        err.to_string()
+            .starts_with("could not verify target is empty (timed out")
+    );
+
+    check_empty(async { Ok(false) }, Duration::from_secs(1))
+        .await
+        .unwrap();
+    let err = check_empty(async { Ok(true) }, Duration::from_secs(1))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<RestoreError>(),
+        Some(RestoreError::NonEmptyDatabase)
+    ));
+}
+
+fn dump_file(header: &[u8], blocks: &[&[u8]]) -> Vec<u8> {
+    let mut buf = DUMP_MAGIC.to_vec();
+    buf.extend_from_slice(&1i64.to_be_bytes());
+    buf.extend(packet(b'H', header));
+    for block in blocks {
+        buf.extend(packet(b'D', block));
+    }
+    buf
+}
+
+#[tokio::test]
+async fn test_dump_header_read() {
+    let dump = dump_file(b"header", &[b"one", b"two"]);
+    let (header, packets) = DumpHeader::read(Cursor::new(dump)).await.unwrap();
+    assert_eq!(header.version, 1);
+    assert_eq!(&header.header[..], b"header");
+    assert_eq!(header.fields(), None);
+    // restoring continues right after the header
+    let blocks = packets.map(Result::unwrap).collect::<Vec<_>>().await;
+    assert_eq!(blocks, [&b"one"[..], &b"two"[..]]);
+
+    let mut dump = dump_file(b"header", &[]);
+    dump[17..25].copy_from_slice(&2i64.to_be_bytes());
+    let err = DumpHeader::read(Cursor::new(dump)).await.err().unwrap();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::UnsupportedVersion { found: 2, max: 1 })
+    ));
+
+    let err = DumpHeader::read(Cursor::new(DUMP_MAGIC.to_vec()))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "Cannot read header");
+
+    let err = DumpHeader::read(Cursor::new(b"not a dump at all, really".to_vec()))
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err.downcast_ref::<RestoreError>(),
+        Some(RestoreError::BadMagic)
+    ));
+}
+
+#[tokio::test]
+async fn test_dump_header_prefix() {
+    let prefixed = |prefix: &[u8]| {
+        let mut dump = prefix.to_vec();
+        dump.extend(dump_file(b"header", &[b"one"]));
+        Cursor::new(dump)
+    };
+    let is_bad_magic = |err: anyhow::Error| {
+        matches!(
+            err.downcast_ref::<RestoreError>(),
+            Some(RestoreError::BadMagic)
+        )
+    };
+    for prefix in [&b"\xEF\xBB\xBF"[..], b"\r\n  ", b"\xEF\xBB\xBF\n"] {
+        let (header, packets) = DumpHeader::read(prefixed(prefix)).await.unwrap();
+        assert_eq!(&header.header[..], b"header");
+        let blocks = packets.map(Result::unwrap).collect::<Vec<_>>().await;
+        assert_eq!(blocks, [&b"one"[..]]);
+    }
+
+    // only a BOM and a few whitespace bytes are skipped
+    let err = DumpHeader::read(prefixed(b"garbage")).await.err().unwrap();
+    assert!(is_bad_magic(err));
+    let err = DumpHeader::read(prefixed(b"\xEF\xBB")).await.err().unwrap();
+    assert!(is_bad_magic(err));
+    let err = DumpHeader::read(prefixed(b"\n\xEF\xBB\xBF"))
+        .await
+        .err()
+        .unwrap();
+    assert!(is_bad_magic(err));
+    let err = DumpHeader::read(prefixed(&[b' '; 17])).await.err().unwrap();
+    assert!(is_bad_magic(err));
+}
+
+#[tokio::test]
+async fn test_check_connection() {
+    check_connection(async { Ok(()) }, Duration::from_secs(1))
+        .await
+        .unwrap();
+
+    let refused = async {
+        Err(anyhow::Error::from(io::Error::from(
+            io::ErrorKind::ConnectionRefused,
+        )))
+    };
+    let err = check_connection(refused, Duration::from_secs(1))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "cannot connect to the server to restore databases"
+    );
+    assert!(err.root_cause().is::<io::Error>());
+
+    // an unreachable server that never answers
+    let err = check_connection(std::future::pending(), Duration::from_millis(10))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        format!("{err:#}"),
+        "cannot connect to the server to restore databases: timed out after 10ms"
+    );
+}
+
+#[tokio::test]
+async fn test_list_databases() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    fs::write(path("init.edgeql"), "").await.unwrap();
+    fs::write(path("main.dump"), "").await.unwrap();
+    fs::write(path("app%2Fv2.dump"), "").await.unwrap();
+
+    let dumps = find_dumps(dir.path(), false).await.unwrap();
+    let existing = BTreeSet::from(["main".to_string(), "other".to_string()]);
+    let databases = planned_databases(&dumps, &existing);
+    assert_eq!(
+        databases,
+        [
+            PlannedDatabase {
+                name: "app/v2".into(),
+                exists: false,
+            },
+            PlannedDatabase {
+                name: "main".into(),
+                exists: true,
+            },
+        ]
+    );
+
+    let json = format_planned_databases(&databases, true).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+        serde_json::json!([
+            {"name": "app/v2", "exists": false},
+            {"name": "main", "exists": true},
+        ])
+    );
+    let text = format_planned_databases(&databases, false).unwrap();
+    let rows: Vec<_> = text.lines().filter(|l| l.contains("app/v2")).collect();
+    assert_eq!(rows.len(), 1);
+    assert!(rows[0].contains("will create"), "{text}");
+    let rows: Vec<_> = text.lines().filter(|l| l.contains("main")).collect();
+    assert!(rows[0].contains("exists (conflict)"), "{text}");
+}
+
+#[tokio::test]
+async fn test_dry_run_plan() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    let main = dump_file(b"header", &[b"one", b"two"]);
+    fs::write(path("main.dump"), &main).await.unwrap();
+    let part0 = dump_file(b"header", &[b"one"]);
+    let part1 = dump_file(b"header", &[b"two", b"three"]);
+    fs::write(path("app.000.dump"), &part0).await.unwrap();
+    fs::write(path("app.001.dump"), &part1).await.unwrap();
+
+    // single dump
+    let plan = scan_dump(&[path("main.dump")]).await.unwrap();
+    assert_eq!(
+        plan,
+        DumpPlan {
+            version: 1,
+            bytes: main.len() as u64,
+            blocks: 2,
+        }
+    );
+    assert_eq!(
+        plan_lines(&[], &[("main".into(), plan, PlannedTarget::Empty)]),
+        [
+            "Dry run, nothing was restored. The restore would:",
+            "  restore \"main\" (exists and is empty): dump format 1, 112 B in 2 blocks",
+        ]
+    );
+
+    // `--all`, with one new and one existing database
+    let app = scan_dump(&[path("app.000.dump"), path("app.001.dump")])
+        .await
+        .unwrap();
+    assert_eq!(app.blocks, 3);
+    assert_eq!(app.bytes, (part0.len() + part1.len()) as u64);
+    let main = scan_dump(&[path("main.dump")]).await.unwrap();
+    let lines = plan_lines(
+        &[path("init.edgeql")],
+        &[
+            ("app".into(), app, PlannedTarget::Create),
+            ("main".into(), main, PlannedTarget::NotEmpty),
+        ],
+    );
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+        lines[1],
+        format!("  apply init script {}", path("init.edgeql").display())
+    );
+    assert!(lines[2].starts_with("  restore \"app\" (would be created): "));
+    assert!(
+        lines[3]
+            .starts_with("  restore \"main\" (exists and is NOT empty, restoring would fail): ")
+    );
+
+    let mut broken = dump_file(b"header", &[b"one"]);
+    *broken.last_mut().unwrap() ^= 1;
+    fs::write(path("broken.dump"), &broken).await.unwrap();
+    assert!(scan_dump(&[path("broken.dump")]).await.is_err());
+}
+
+#[test]
+fn test_is_instance_config() {
+    assert!(is_instance_config(
+        "CONFIGURE INSTANCE SET listen_addresses := {'0.0.0.0'};"
+    ));
+    assert!(is_instance_config("configure system reset query_work_mem;"));
+    assert!(is_instance_config(
+        "# dumped settings\nCONFIGURE\n  INSTANCE INSERT Auth { priority := 0 };"
+    ));
+    assert!(!is_instance_config(
+        "CONFIGURE CURRENT DATABASE SET allow_user_specified_id := true;"
+    ));
+    assert!(!is_instance_config(
+        "CONFIGURE CURRENT BRANCH SET query_work_mem := <cfg::memory>'4MiB';"
+    ));
+    assert!(!is_instance_config(
+        "CONFIGURE SESSION SET apply_access_policies := false;"
+    ));
+    assert!(!is_instance_config("CREATE SUPERUSER ROLE admin;"));
+    assert!(!is_instance_config("SELECT 'CONFIGURE INSTANCE';"));
+    assert!(!is_instance_config(""));
+}
+
+#[test]
+fn test_init_sed() {
+    let subs = [
+        parse_init_sed(r"prod\.example\.com=staging.example.com").unwrap(),
+        parse_init_sed("secret-([0-9]+)=dummy-$1").unwrap(),
+    ];
+    let stmt = "CONFIGURE INSTANCE SET host := 'prod.example.com';";
+    let (result, replaced) = substitute(stmt, &subs).unwrap();
+    assert_eq!(
+        result,
+        "CONFIGURE INSTANCE SET host := 'staging.example.com';"
+    );
+    assert_eq!(replaced, 1);
+
+    let stmt = "SELECT {'secret-1', 'secret-22', 'prod.example.com'};";
+    let (result, replaced) = substitute(stmt, &subs).unwrap();
+    assert_eq!(
+        result,
+        "SELECT {'dummy-1', 'dummy-22', 'staging.example.com'};"
+    );
+    assert_eq!(replaced, 3);
+
+    // statements without matches are left as they are
+    let stmt = "CREATE SUPERUSER ROLE admin;";
+    let (result, replaced) = substitute(stmt, &subs).unwrap();
+    assert!(matches!(result, Cow::Borrowed(_)));
+    assert_eq!(result, stmt);
+    assert_eq!(replaced, 0);
+
+    // a replacement must not break the quoting of the statement
+    let quote = [parse_init_sed("example=it's").unwrap()];
+    assert!(substitute("SELECT 'example';", &quote).is_err());
+
+    assert!(parse_init_sed("no replacement").is_err());
+    assert!(parse_init_sed("=empty pattern").is_err());
+    assert!(parse_init_sed("(=unbalanced").is_err());
+    assert_eq!(parse_init_sed("a=b=c").unwrap().replacement, "b=c");
+}
+
+#[tokio::test]
+async fn test_check_path_kind() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("main.dump");
+    fs::write(&file, "").await.unwrap();
+
+    let err = check_path_kind(dir.path(), false).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::PathIsDirectory { .. })
+    ));
+    assert!(err.to_string().contains("use `--all`"), "{err}");
+    let err = check_path_kind(&file, true).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::PathIsFile { .. })
+    ));
+    assert!(err.to_string().contains("drop `--all`"), "{err}");
+
+    check_path_kind(dir.path(), true).await.unwrap();
+    check_path_kind(&file, false).await.unwrap();
+    check_path_kind(Path::new("-"), true).await.unwrap();
+    check_path_kind(&dir.path().join("missing.dump"), false)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn test_rename_databases() {
+    let dumps = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    vec![PathBuf::from(format!("{name}.dump"))],
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let names = |dumps: Vec<(String, Vec<PathBuf>)>| {
+        dumps.into_iter().map(|(name, _)| name).collect::<Vec<_>>()
+    };
+
+    let renamed = rename_databases(
+        dumps(&["prod_tenant_001", "prod_tenant_002"]),
+        Some("prod_"),
+        None,
+    )
+    .unwrap();
+    assert_eq!(names(renamed.clone()), ["tenant_001", "tenant_002"]);
+    // the dump files stay the same
+    assert_eq!(renamed[0].1, [PathBuf::from("prod_tenant_001.dump")]);
+
+    let renamed = rename_databases(
+        dumps(&["prod_tenant_001", "main"]),
+        Some("prod_"),
+        Some("staging_"),
+    )
+    .unwrap();
+    // names without the prefix are kept
+    assert_eq!(names(renamed), ["staging_tenant_001", "staging_main"]);
+
+    let renamed = rename_databases(dumps(&["main"]), None, Some("copy_")).unwrap();
+    assert_eq!(names(renamed), ["copy_main"]);
+    let renamed = rename_databases(dumps(&["main"]), None, None).unwrap();
+    assert_eq!(names(renamed), ["main"]);
+
+    let err = rename_databases(dumps(&["prod_"]), Some("prod_"), None).unwrap_err();
+    assert!(err.to_string().contains("invalid database name"), "{err}");
+    let err = rename_databases(dumps(&["prod_app", "app"]), Some("prod_"), None).unwrap_err();
+    assert!(err.to_string().contains("would both"), "{err}");
+}
+
+#[tokio::test]
+async fn test_find_dumps_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    fs::write(path("init.edgeql"), "").await.unwrap();
+    for name in ["beta", "main", "alpha", "gamma"] {
+        fs::write(path(&format!("{name}.dump")), "").await.unwrap();
+    }
+    let names = |dumps: Vec<(String, Vec<PathBuf>)>| {
+        dumps.into_iter().map(|(name, _)| name).collect::<Vec<_>>()
+    };
+
+    let dumps = find_dumps(dir.path(), false).await.unwrap();
+    assert_eq!(names(dumps), ["alpha", "beta", "gamma", "main"]);
+
+    fs::write(path(MANIFEST_FILE), "# restore order\nmain\n\n  gamma\n")
+        .await
+        .unwrap();
+    let dumps = find_dumps(dir.path(), false).await.unwrap();
+    assert_eq!(names(dumps), ["main", "gamma", "alpha", "beta"]);
+
+    fs::write(path(MANIFEST_FILE), "main\ndelta\n")
+        .await
+        .unwrap();
+    let err = find_dumps(dir.path(), false).await.unwrap_err();
+    assert!(
+        format!("{err:#}").contains("\"delta\" is listed"),
+        "{err:#}"
+    );
+
+    fs::write(path(MANIFEST_FILE), "main\nmain\n")
+        .await
+        .unwrap();
+    let err = find_dumps(dir.path(), false).await.unwrap_err();
+    assert!(format!("{err:#}").contains("more than once"), "{err:#}");
+}
+
+#[tokio::test]
+async fn test_split_dump_parts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    fs::write(path("init.edgeql"), "").await.unwrap();
+    let part0 = dump_file(b"header", &[b"one", b"two"]);
+    let part1 = dump_file(b"header", &[b"three"]);
+    fs::write(path("app.000.dump"), &part0).await.unwrap();
+    fs::write(path("app.001.dump"), &part1).await.unwrap();
+    fs::write(path("main.dump"), dump_file(b"header", &[]))
+        .await
+        .unwrap();
+
+    let dumps = find_dumps(dir.path(), false).await.unwrap();
+    assert_eq!(
+        dumps,
+        vec![
+            (
+                "app".into(),
+                vec![path("app.000.dump"), path("app.001.dump")]
+            ),
+            ("main".into(), vec![path("main.dump")]),
+        ]
+    );
+
+    let position = Arc::new(AtomicU64::new(0));
+    let (header, blocks, size) = open_dump_parts(&dumps[0].1, &position).await.unwrap();
+    assert_eq!(header.header, Bytes::from_static(b"header"));
+    let blocks = blocks.map(|b| b.unwrap()).collect::<Vec<_>>().await;
+    assert_eq!(blocks, [&b"one"[..], b"two", b"three"]);
+    let total = (part0.len() + part1.len()) as u64;
+    assert_eq!(size, Some(total));
+    assert_eq!(position.load(Ordering::Relaxed), total);
+
+    fs::write(path("app.001.dump"), dump_file(b"other", &[]))
+        .await
+        .unwrap();
+    let err = open_dump_parts(&dumps[0].1, &position).await.err().unwrap();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::PartHeaderMismatch { .. })
+    ));
+
+    fs::rename(path("app.001.dump"), path("app.002.dump"))
+        .await
+        .unwrap();
+    let err = find_dumps(dir.path(), false).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::MissingPart { part: 1, .. })
+    ));
+
+    // without a part `000` the number is part of the database name
+    fs::remove_file(path("app.000.dump")).await.unwrap();
+    let dumps = find_dumps(dir.path(), false).await.unwrap();
+    assert_eq!(
+        dumps,
+        vec![
+            ("app.002".into(), vec![path("app.002.dump")]),
+            ("main".into(), vec![path("main.dump")]),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_dotted_database_names() {
+    // names as written by `dump --all`, which doesn't encode dots
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    fs::write(path("init.edgeql"), "").await.unwrap();
+    for name in ["app.1", "db.2024", "db.2024.0"] {
+        let file = format!("{}.dump", urlencoding::encode(name));
+        fs::write(path(&file), dump_file(b"header", &[b"data"]))
+            .await
+            .unwrap();
+    }
+    let dumps = find_dumps(dir.path(), false).await.unwrap();
+    assert_eq!(
+        dumps,
+        vec![
+            ("app.1".into(), vec![path("app.1.dump")]),
+            ("db.2024".into(), vec![path("db.2024.dump")]),
+            ("db.2024.0".into(), vec![path("db.2024.0.dump")]),
+        ]
+    );
+    let position = Arc::new(AtomicU64::new(0));
+    let (_, blocks, _) = open_dump_parts(&dumps[0].1, &position).await.unwrap();
+    let blocks = blocks.map(|b| b.unwrap()).collect::<Vec<_>>().await;
+    assert_eq!(blocks, [&b"data"[..]]);
+}
+
+#[tokio::test]
+async fn test_read_archive() {
+    fn tar_file(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, &data[..]).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+    async fn read(archive: Vec<u8>) -> anyhow::Result<Vec<(String, Bytes, Vec<Bytes>)>> {
+        let (entries, mut received) = mpsc::channel(1);
+        let reader = tokio::task::spawn_blocking(move || {
+            read_archive(
+                Cursor::new(archive),
+                Path::new("backup.tar"),
+                false,
+                entries,
+            )
+        });
+        let mut files = Vec::new();
+        while let Some(entry) = received.recv().await {
+            match entry {
+                ArchiveEntry::Init(mut data) => {
+                    let mut init = Vec::new();
+                    data.read_to_end(&mut init).await.unwrap();
+                    files.push((INIT_FILE.to_string(), Bytes::from(init), Vec::new()));
+                }
+                ArchiveEntry::Dump { database, data, .. } => {
+                    let (header, packets) = DumpHeader::read(data).await.unwrap();
+                    let blocks = packets.map(|b| b.unwrap()).collect::<Vec<_>>().await;
+                    files.push((database, header.header, blocks));
+                }
+            }
+        }
+        reader.await.unwrap()?;
+        Ok(files)
+    }
+
+    let archive = tar_file(&[
+        (
+            "backup/init.edgeql",
+            b"configure instance set x := 1;".to_vec(),
+        ),
+        ("backup/readme.txt", b"nightly backup".to_vec()),
+        ("backup/main.dump", dump_file(b"main", &[b"one", b"two"])),
+        ("backup/app%2Fv1.dump", dump_file(b"app", &[b"three"])),
+    ]);
+    let expected = vec![
+        (
+            "init.edgeql".to_string(),
+            Bytes::from_static(b"configure instance set x := 1;"),
+            vec![],
+        ),
+        (
+            "main".to_string(),
+            Bytes::from_static(b"main"),
+            vec![Bytes::from_static(b"one"), Bytes::from_static(b"two")],
+        ),
+        (
+            "app/v1".to_string(),
+            Bytes::from_static(b"app"),
+            vec![Bytes::from_static(b"three")],
+        ),
+    ];
+    assert_eq!(read(archive).await.unwrap(), expected);
+
+    let archive = tar_file(&[
+        ("main.dump", dump_file(b"main", &[])),
+        ("init.edgeql", Vec::new()),
+    ]);
+    let err = read(archive).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(RestoreError::MissingInit { .. })
+    ));
+
+    assert_eq!(
+        ArchiveFormat::of_path(Path::new("/backups/backup.tar.gz")),
+        Some(ArchiveFormat::TarGz)
+    );
+    assert_eq!(ArchiveFormat::of_path(Path::new("/backups/nightly")), None);
+}
+
+#[test]
+fn test_fail_fast() {
+    let results = || {
+        ["main", "broken", "app"].map(|database| match database {
+            "broken" => Err(anyhow::anyhow!("restoring database {database:?}: bad dump")),
+            _ => Ok(database),
+        })
+    };
+
+    let mut failures = Failures::new(true);
+    let mut restored = Vec::new();
+    let err = results()
+        .into_iter()
+        .try_for_each(|result| {
+            restored.extend(failures.check(result)?);
+            anyhow::Ok(())
+        })
+        .unwrap_err();
+    assert_eq!(err.to_string(), "restoring database \"broken\": bad dump");
+    assert_eq!(restored, ["main"]);
+    failures.finish().unwrap();
+
+    let mut failures = Failures::new(false);
+    let mut restored = Vec::new();
+    for result in results() {
+        restored.extend(failures.check(result).unwrap());
+    }
+    assert_eq!(restored, ["main", "app"]);
+    let err = failures.finish().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ExitCode>().map(ExitCode::code),
+        Some(exit_codes::PARTIAL_SUCCESS)
+    );
+}
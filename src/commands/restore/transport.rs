@@ -0,0 +1,168 @@
+//! Opening dumps from files, stdin and `sftp://` URLs.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::task::{Context, Poll, ready};
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use futures_util::stream::StreamExt;
+use tokio::fs;
+use tokio::io::{self, AsyncRead};
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+
+use gel_errors::{ClientError, Error, ErrorKind};
+
+use crate::commands::restore::RestoreError;
+use crate::commands::restore::header::{DumpHeader, Packets};
+use crate::commands::restore::progress::CountingReader;
+use crate::commands::restore::sftp;
+
+type Input = Box<dyn AsyncRead + Unpin + Send>;
+pub(super) type Blocks = Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>>;
+
+/// Opens every part of a dump and chains their data blocks. Returns the
+/// header, the blocks and the total size of the files if known.
+pub(super) async fn open_dump_parts(
+    parts: &[PathBuf],
+    position: &Arc<AtomicU64>,
+) -> anyhow::Result<(DumpHeader, Blocks, Option<u64>)> {
+    let (first, rest) = parts.split_first().expect("at least one dump part");
+    let (header, packets, mut file_size) = open_dump(first, position.clone()).await?;
+    let mut blocks: Blocks = Box::pin(packets);
+    for part in rest {
+        let (part_header, part_packets, part_size) = open_dump(part, position.clone()).await?;
+        if part_header.header != header.header {
+            return Err(RestoreError::PartHeaderMismatch { part: part.clone() }.into());
+        }
+        file_size = file_size.zip(part_size).map(|(total, size)| total + size);
+        blocks = Box::pin(blocks.chain(part_packets));
+    }
+    Ok((header, blocks, file_size))
+}
+
+/// Opens a dump file (or stdin for `-`), checks the format and reads the
+/// header packet. Returns the header, the remaining packets and the file
+/// size if known.
+async fn open_dump(
+    filename: &Path,
+    position: Arc<AtomicU64>,
+) -> anyhow::Result<(DumpHeader, Packets, Option<u64>)> {
+    let file_ctx = &|| format!("Failed to read dump {}", filename.display());
+    let (mut input, file_size) = if filename.to_str() == Some("-") {
+        let stdin = CountingReader::new(io::stdin(), position.clone());
+        (Box::new(stdin) as Input, None)
+    } else {
+        let (file, file_size) = open_file(filename).await.with_context(file_ctx)?;
+        match file_size {
+            Some(file_size) => eprintln!(
+                "\nRestoring database from file `{}`. Total size: {:.02} MB",
+                filename.display(),
+                file_size as f64 / 1048576.0
+            ),
+            None => eprintln!("\nRestoring database from file `{}`.", filename.display()),
+        }
+        let file = CountingReader::new(file, position.clone());
+        (Box::new(file) as Input, file_size)
+    };
+    let (header, packets) = DumpHeader::read(input).await.with_context(file_ctx)?;
+    Ok((header, packets, file_size))
+}
+
+/// Reads a dump held in memory, e.g. from stdin for `--into`, see
+/// [`open_dump`].
+pub(super) async fn open_dump_bytes(
+    data: Bytes,
+    position: Arc<AtomicU64>,
+) -> anyhow::Result<(DumpHeader, Blocks, Option<u64>)> {
+    let size = data.len() as u64;
+    let input = CountingReader::new(std::io::Cursor::new(data), position);
+    let (header, packets) = DumpHeader::read(input)
+        .await
+        .context("Failed to read dump from stdin")?;
+    let blocks: Blocks = Box::pin(packets);
+    Ok((header, blocks, Some(size)))
+}
+
+/// Opens a local file or a remote file given by its `sftp://` URL. Returns
+/// the file and its size if known.
+pub(super) async fn open_file(path: &Path) -> anyhow::Result<(Input, Option<u64>)> {
+    if let Some(url) = sftp::parse_url(path) {
+        let file = sftp::open(&url?).await?;
+        return Ok((Box::new(file), None));
+    }
+    let file = fs::File::open(path).await?;
+    let size = file.metadata().await?.len();
+    Ok((Box::new(file), Some(size)))
+}
+
+/// Path of the file `name` in a dump directory, which may be an SFTP URL
+pub(super) fn dir_file(dir: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    match sftp::parse_url(dir) {
+        Some(url) => Ok(sftp::join(&url?, name).to_string().into()),
+        None => Ok(dir.join(name)),
+    }
+}
+
+/// Reads up to `window` blocks ahead of the consumer in a task of its own,
+/// see `--read-ahead`. Blocks and errors are yielded in the order they are
+/// read; nothing is read past the first error.
+pub(super) fn read_ahead_blocks(blocks: Blocks, window: usize) -> Blocks {
+    if window <= 1 {
+        return blocks;
+    }
+    // the task holds one more block while waiting for room in the channel
+    let (tx, rx) = mpsc::channel(window - 1);
+    let task = tokio::spawn(async move {
+        let mut blocks = blocks;
+        while let Some(block) = blocks.next().await {
+            let failed = block.is_err();
+            if tx.send(block).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    Box::pin(ReadAhead {
+        blocks: rx,
+        task,
+        finished: false,
+    })
+}
+
+struct ReadAhead {
+    blocks: mpsc::Receiver<Result<Bytes, Error>>,
+    task: tokio::task::JoinHandle<()>,
+    finished: bool,
+}
+
+impl Stream for ReadAhead {
+    type Item = Result<Bytes, Error>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.finished {
+            return Poll::Ready(None);
+        }
+        if let Some(block) = ready!(self.blocks.poll_recv(cx)) {
+            return Poll::Ready(Some(block));
+        }
+        // a reader that panicked must fail the restore rather than end the
+        // dump early
+        let result = ready!(Pin::new(&mut self.task).poll(cx));
+        self.finished = true;
+        match result {
+            Ok(()) => Poll::Ready(None),
+            Err(e) => Poll::Ready(Some(Err(ClientError::with_message(format!(
+                "reading the dump failed: {e}"
+            ))))),
+        }
+    }
+}
+
+impl Drop for ReadAhead {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -43,7 +43,8 @@ fn get_cloud_ui_url(
     cfg: &gel_tokio::Config,
     opts: &Options,
 ) -> anyhow::Result<String> {
-    let client = cloud::client::CloudClient::new(&opts.cloud_options)?;
+    let client =
+        cloud::client::CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     client.ensure_authenticated()?;
     let url = if client.is_default_partition {
         format!("https://cloud.edgedb.com/{cloud_name}")
@@ -51,6 +51,7 @@ pub enum ConnectionError {
 #[derive(Debug, Clone)]
 pub struct Connector {
     config: Result<Config, ArcError>,
+    connect_timeout: Option<Duration>,
 }
 
 pub struct Connection {
@@ -148,8 +149,19 @@ impl Connector {
     pub fn new(config: anyhow::Result<Config>) -> Connector {
         Connector {
             config: config.map_err(ArcError::from),
+            connect_timeout: None,
         }
     }
+    /// Sets the `--connect-timeout` given on the command line
+    pub fn with_connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+    /// How long to wait for the server to become available: the
+    /// `--connect-timeout` if given, `default` otherwise
+    pub fn wait_time(&self, default: Duration) -> Duration {
+        self.connect_timeout.unwrap_or(default)
+    }
     pub fn branch(&mut self, name: &str) -> anyhow::Result<&mut Self> {
         if let Ok(cfg) = self.config.as_mut() {
             if name == "__default__" {
@@ -10,11 +10,20 @@ use fs_err as fs;
 use gel_tokio::credentials::Credentials;
 use gel_tokio::{Config, InstanceName};
 
+use crate::cli::env::Env;
 use crate::platform::{config_dir, tmp_file_name};
 use crate::question;
 
+/// Directory of instance credentials, `GEL_CREDENTIALS_DIR` if set
 pub fn base_dir() -> anyhow::Result<PathBuf> {
-    Ok(config_dir()?.join("credentials"))
+    base_dir_with(Env::credentials_dir()?)
+}
+
+fn base_dir_with(dir_override: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    match dir_override {
+        Some(dir) => Ok(dir),
+        None => Ok(config_dir()?.join("credentials")),
+    }
 }
 
 pub fn path(name: &str) -> anyhow::Result<PathBuf> {
@@ -95,3 +104,21 @@ pub fn maybe_update_credentials_file(config: &Config, ask: bool) -> anyhow::Resu
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::base_dir_with;
+    use crate::platform::config_dir;
+
+    #[test]
+    fn credentials_dir_override() {
+        let dir = PathBuf::from("/srv/gel/credentials");
+        assert_eq!(base_dir_with(Some(dir.clone())).unwrap(), dir);
+        assert_eq!(
+            base_dir_with(None).unwrap(),
+            config_dir().unwrap().join("credentials")
+        );
+    }
+}
@@ -2,9 +2,7 @@ use std::str;
 use std::time::Instant;
 
 use anyhow::Context;
-use is_terminal::IsTerminal;
 use terminal_size::{Width, terminal_size};
-use tokio::io::{AsyncWriteExt, stdout};
 use tokio::sync::mpsc::channel;
 use tokio_stream::StreamExt;
 
@@ -106,10 +104,10 @@ pub fn main(options: Options, cfg: Config) -> Result<(), anyhow::Error> {
         .unwrap_or_else(|| Duration::from_micros(5 * 60_000_000));
     let print = print::Config::new()
         .max_items(implicit_limit)
-        .max_vector_length(VectorLimit::Auto)
+        .max_vector_length(print::env_vector_limit().unwrap_or(VectorLimit::Auto))
         .expand_strings(cfg.shell.expand_strings.unwrap_or(true))
         .implicit_properties(cfg.shell.implicit_properties.unwrap_or(false))
-        .colors(std::io::stdout().is_terminal())
+        .colors(print::use_color())
         .clone();
     let conn_config = conn.get()?;
     credentials::maybe_update_credentials_file(conn_config, true)?;
@@ -265,11 +263,16 @@ async fn execute_backslash(state: &mut repl::State, text: &str) -> anyhow::Resul
     Ok(())
 }
 
-async fn write_out(data: &str) -> anyhow::Result<()> {
-    let mut out = stdout();
-    out.write_all(data.as_bytes()).await?;
-    out.flush().await?;
-    Ok(())
+/// Output of the formats rendered to strings. It is shown once the query
+/// completes, or fails, so the pager is only used if it doesn't fit.
+struct PagedOutput(String);
+
+impl Drop for PagedOutput {
+    fn drop(&mut self) {
+        if let Err(e) = print::page(&self.0) {
+            print::error!("Cannot write query output: {e:#}");
+        }
+    }
 }
 
 async fn execute_query(
@@ -289,7 +292,8 @@ async fn execute_query(
 
     let flags = CompilationOptions {
         implicit_limit: state.implicit_limit.map(|x| (x + 1) as u64),
-        implicit_typenames: state.display_typenames && cli.protocol().supports_inline_typenames(),
+        implicit_typenames: (state.display_typenames || output_format == Edgeql)
+            && cli.protocol().supports_inline_typenames(),
         implicit_typeids: false,
         explicit_objectids: true,
         allow_capabilities: Capabilities::ALL,
@@ -361,6 +365,9 @@ async fn execute_query(
         }
     };
 
+    if let Some(echo) = print::query_echo(statement, &state.print) {
+        print!("{echo}");
+    }
     print::warnings(items.warnings(), statement)?;
 
     if !items.can_contain_data() {
@@ -383,8 +390,13 @@ async fn execute_query(
         // update max_width each time
         cfg.max_width(w.into());
     }
+    let mut out = PagedOutput(String::new());
     match output_format {
-        TabSeparated => {
+        TabSeparated | Edgeql => {
+            let format_row = |row: &Value| match output_format {
+                Edgeql => print::edgeql::format_row(row),
+                _ => tab_separated::format_row(row, &cfg, Default::default()),
+            };
             let mut index = 0;
             while let Some(row) = items.next().await.transpose()? {
                 if index == 0 && state.print_stats == Detailed {
@@ -402,7 +414,7 @@ async fn execute_query(
                         return Err(QueryError)?;
                     }
                 }
-                let mut text = match tab_separated::format_row(&row) {
+                let mut text = match format_row(&row) {
                     Ok(text) => text,
                     Err(e) => {
                         eprintln!("Error: {e}");
@@ -412,12 +424,19 @@ async fn execute_query(
                         return Err(QueryError)?;
                     }
                 };
-                // trying to make writes atomic if possible
                 text += "\n";
-                write_out(&text).await?;
+                out.0.push_str(&text);
                 index += 1;
             }
         }
+        Parquet | Msgpack => {
+            eprintln!(
+                "Error: `{}` output can only be written to a file",
+                output_format.as_str()
+            );
+            items.complete().await?;
+            return Err(QueryError)?;
+        }
         Tabular => {
             match print::table_to_stdout(&mut items, &cfg).await {
                 Ok(()) => {}
@@ -436,6 +455,36 @@ async fn execute_query(
             }
             return Err(QueryError)?;
         }
+        Auto => match print::auto_to_stdout(&mut items, &cfg).await {
+            Ok(()) => {}
+            Err(e) => {
+                match e {
+                    PrintError::StreamErr {
+                        source: ref error, ..
+                    } => {
+                        print_query_error(error, statement, state.verbose_errors, "<query>")?;
+                    }
+                    _ => eprintln!("{e:#?}"),
+                }
+                state.last_error = Some(e.into());
+                return Err(QueryError)?;
+            }
+        },
+        Kv => match print::kv_to_stdout(&mut items, &cfg).await {
+            Ok(()) => {}
+            Err(e) => {
+                match e {
+                    PrintError::StreamErr {
+                        source: ref error, ..
+                    } => {
+                        print_query_error(error, statement, state.verbose_errors, "<query>")?;
+                    }
+                    _ => eprintln!("{e:#?}"),
+                }
+                state.last_error = Some(e.into());
+                return Err(QueryError)?;
+            }
+        },
         Default => {
             match print::native_to_stdout(&mut items, &cfg).await {
                 Ok(()) => {}
@@ -484,10 +533,8 @@ async fn execute_query(
                          in JSON mode"
                     )
                 })?;
-                // trying to make writes atomic if possible
-                let mut data = print::json_to_string(jitems, &cfg)?;
-                data += "\n";
-                write_out(&data).await?;
+                out.0.push_str(&print::json_to_string(jitems, &cfg)?);
+                out.0.push('\n');
             }
         }
         JsonPretty | JsonLines => {
@@ -520,15 +567,11 @@ async fn execute_query(
                     }
                 }
                 if state.output_format == JsonLines {
-                    // trying to make writes atomic if possible
                     text += "\n";
-                    write_out(&text).await?;
+                    out.0.push_str(&text);
                 } else {
-                    // trying to make writes atomic if possible
-                    let mut data;
-                    data = print::json_item_to_string(&value, &cfg)?;
-                    data += "\n";
-                    write_out(&data).await?;
+                    out.0.push_str(&print::json_item_to_string(&value, &cfg)?);
+                    out.0.push('\n');
                     index += 1;
                 }
             }
@@ -536,6 +579,7 @@ async fn execute_query(
     }
 
     let _ = items.complete().await?;
+    drop(out);
 
     if state.print_stats != Off {
         eprintln!(
@@ -5,7 +5,16 @@ use crate::options::{Command, Options};
 use crate::portable::instance;
 use crate::portable::project::Subcommands as Project;
 use crate::portable::server::Subcommands as Server;
+use std::fs;
 use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::Context;
+
+/// Target of the records written to `restore --log-file`
+const RESTORE_TARGET: &str = "edgedb::restore";
 
 pub fn init(builder: &mut env_logger::Builder, opt: &Options) {
     if opt.debug_print_frames {
@@ -22,7 +31,7 @@ pub fn init(builder: &mut env_logger::Builder, opt: &Options) {
             _ => {}
         },
         Some(Command::Common(Common::Restore(r))) if r.verbose => {
-            builder.filter_module("edgedb::restore", log::LevelFilter::Info);
+            builder.filter_module(RESTORE_TARGET, log::LevelFilter::Info);
             builder.format(|buf, record| writeln!(buf, "{}", record.args()));
         }
         Some(Command::Watch(w)) if w.verbose => {
@@ -62,3 +71,133 @@ pub fn init(builder: &mut env_logger::Builder, opt: &Options) {
     // we have custom logging infrastructure for edgedb warnings
     builder.filter_module("gel_tokio::warning", log::LevelFilter::Error);
 }
+
+/// Installs the logger built by `builder` as the global logger. With
+/// `restore --log-file` it is wrapped in a [`FileTee`].
+pub fn install(mut builder: env_logger::Builder, opt: &Options) -> anyhow::Result<()> {
+    let log_file = match &opt.subcommand {
+        Some(Command::Common(Common::Restore(r))) => r.log_file.as_deref(),
+        _ => None,
+    };
+    let Some(path) = log_file else {
+        builder.init();
+        return Ok(());
+    };
+    let tee = FileTee::open(builder.build(), RESTORE_TARGET, path)?;
+    log::set_max_level(tee.max_level());
+    log::set_boxed_logger(Box::new(tee))?;
+    Ok(())
+}
+
+/// Passes log records to `inner` and appends the info and more severe
+/// records of `target` to a file, with a timestamp.
+struct FileTee {
+    inner: env_logger::Logger,
+    target: &'static str,
+    file: Mutex<fs::File>,
+}
+
+impl FileTee {
+    fn open(inner: env_logger::Logger, target: &'static str, path: &Path) -> anyhow::Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("cannot open log file {path:?}"))?;
+        Ok(FileTee {
+            inner,
+            target,
+            file: Mutex::new(file),
+        })
+    }
+    fn max_level(&self) -> log::LevelFilter {
+        self.inner.filter().max(log::LevelFilter::Info)
+    }
+    fn writes_file(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Info && metadata.target() == self.target
+    }
+}
+
+impl log::Log for FileTee {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.writes_file(metadata) || self.inner.enabled(metadata)
+    }
+    fn log(&self, record: &log::Record) {
+        self.inner.log(record);
+        if self.writes_file(record.metadata()) {
+            let mut file = self.file.lock().expect("log file is not poisoned");
+            // a failing log file should not fail the restore
+            writeln!(
+                file,
+                "{} {} {}",
+                humantime::format_rfc3339_millis(SystemTime::now()),
+                record.level(),
+                record.args()
+            )
+            .ok();
+        }
+    }
+    fn flush(&self) {
+        self.inner.flush();
+        self.file
+            .lock()
+            .expect("log file is not poisoned")
+            .flush()
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Log;
+
+    use super::{FileTee, RESTORE_TARGET};
+
+    #[test]
+    fn restore_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("restore.log");
+        std::fs::write(&path, "earlier run\n").unwrap();
+        let inner = env_logger::Builder::new()
+            .filter_level(log::LevelFilter::Off)
+            .build();
+        let tee = FileTee::open(inner, RESTORE_TARGET, &path).unwrap();
+        assert_eq!(tee.max_level(), log::LevelFilter::Info);
+
+        let log = |target: &str, level, message: &str| {
+            tee.log(
+                &log::Record::builder()
+                    .target(target)
+                    .level(level)
+                    .args(format_args!("{message}"))
+                    .build(),
+            )
+        };
+        log(
+            RESTORE_TARGET,
+            log::Level::Info,
+            "Restoring database \"app\"",
+        );
+        log(RESTORE_TARGET, log::Level::Debug, "not written");
+        log("edgedb::other", log::Level::Warn, "not written");
+        log(
+            RESTORE_TARGET,
+            log::Level::Warn,
+            "Retrying database \"app\"",
+        );
+        tee.flush();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3, "{contents}");
+        assert_eq!(lines[0], "earlier run");
+        assert!(
+            lines[1].ends_with("Z INFO Restoring database \"app\""),
+            "{contents}"
+        );
+        assert!(
+            lines[2].ends_with("Z WARN Retrying database \"app\""),
+            "{contents}"
+        );
+    }
+}
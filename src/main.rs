@@ -47,6 +47,7 @@ mod process;
 mod prompt;
 mod question;
 mod repl;
+mod retry;
 mod statement;
 mod table;
 mod tty_password;
@@ -143,16 +144,22 @@ fn _main() -> anyhow::Result<()> {
     // Determine the terminal background color, which will influence
     // various printing decisions. (We could let it be done lazily,
     // but I'd rather it be clear when it is run.)
+    if let Some(choice) = options::color_choice_from_args(env::args_os()) {
+        print::set_color_choice(choice);
+    }
     print::init_colors();
 
     let opt = Options::from_args_and_env()?;
     opt.conn_options.validate()?;
+    if opt.no_pager {
+        print::disable_pager();
+    }
     let cfg = config::get_config();
 
     let mut builder =
         env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"));
     log_levels::init(&mut builder, &opt);
-    builder.init();
+    log_levels::install(builder, &opt)?;
 
     let cfg = cfg.unwrap_or_else(|e| {
         log::warn!("Config error: {:#}", e);
@@ -1,6 +1,5 @@
 use std::path::Path;
 
-use dissimilar::{Chunk, diff};
 use tokio::fs;
 use tokio::task::spawn_blocking as unblock;
 
@@ -13,7 +12,7 @@ use crate::migrations::grammar::parse_migration;
 use crate::migrations::migration::{file_num, read_names};
 use crate::migrations::options::MigrationEdit;
 use crate::platform::{spawn_editor, tmp_file_path};
-use crate::print::{Highlight, err_marker, msg};
+use crate::print::{self, Highlight, err_marker, msg};
 use crate::question::Choice;
 
 #[derive(Copy, Clone)]
@@ -43,29 +42,10 @@ enum FailAction {
 fn print_diff(path1: &Path, data1: &str, path2: &Path, data2: &str) {
     println!("--- {}", path1.display());
     println!("+++ {}", path2.display());
-    let changeset = diff(data1, data2);
     let n1 = data1.split('\n').count();
     let n2 = data2.split('\n').count();
     println!("@@ -1,{n1} +1,{n2}");
-    for item in &changeset {
-        match item {
-            Chunk::Equal(block) => {
-                for line in block.split('\n') {
-                    println!(" {line}");
-                }
-            }
-            Chunk::Insert(block) => {
-                for line in block.split('\n') {
-                    println!("+{}", line.success());
-                }
-            }
-            Chunk::Delete(block) => {
-                for line in block.split('\n') {
-                    println!("-{}", line.danger());
-                }
-            }
-        }
-    }
+    print!("{}", print::diff(data1, data2));
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -1,9 +1,11 @@
-use std::io::{Write, stdout};
+use std::fs;
+use std::io::{self, IsTerminal, Write, stdout};
 use std::str;
 
 use anyhow::Context;
 use bytes::BytesMut;
-use is_terminal::IsTerminal;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use terminal_size::{Width, terminal_size};
 use tokio::fs::File as AsyncFile;
 use tokio::io::{AsyncRead, stdin};
@@ -22,7 +24,7 @@ use crate::connect::Connection;
 use crate::error_display::print_query_error;
 use crate::options::Options;
 use crate::options::Query;
-use crate::outputs::tab_separated;
+use crate::outputs::tab_separated::{self, InvalidUtf8};
 use crate::print::{self, PrintError};
 use crate::repl;
 use crate::statement::{EndOfFile, read_statement};
@@ -46,6 +48,15 @@ pub async fn noninteractive_main(q: &Query, options: &Options) -> Result<(), any
         // entrypoint.
         if let Some(fmt) = options.output_format {
             fmt
+        } else if let Some(path) = &q.output_file {
+            print::output_format_for_path(path)?.unwrap_or_else(|| {
+                print::warn!(
+                    "Cannot infer the output format from the name of {}, \
+                     writing the default format. Use `--output-format` to pick one.",
+                    path.display()
+                );
+                repl::OutputFormat::Default
+            })
         } else {
             // Means "native" serialization; for `edgedb query`
             // the default is `json-pretty` for edgeql and `tabular` for SQL.
@@ -57,12 +68,53 @@ pub async fn noninteractive_main(q: &Query, options: &Options) -> Result<(), any
         }
     };
 
+    let mut output = QueryOutput {
+        file: None,
+        #[cfg(feature = "parquet")]
+        parquet_written: false,
+        invalid_utf8: q.invalid_utf8,
+        print: print_config(q),
+        sample: q.sample,
+        seed: q.seed,
+        error_on_empty: q.error_on_empty,
+    };
+    if q.sample.is_some() && fmt == repl::OutputFormat::Json {
+        anyhow::bail!(
+            "`--output-format=json` returns each result as a single row, \
+             use `json-pretty` or `json-lines` with `--sample`"
+        );
+    }
+    if fmt == repl::OutputFormat::Parquet && q.output_file.is_none() {
+        anyhow::bail!("`--output-format=parquet` can only be written to `--output-file`");
+    }
+    if fmt == repl::OutputFormat::Msgpack && q.output_file.is_none() && stdout().is_terminal() {
+        anyhow::bail!(
+            "`--output-format=msgpack` is binary and is not written to a terminal, \
+             use `--output-file` or a pipe"
+        );
+    }
+    if let Some(path) = &q.output_file {
+        if matches!(
+            fmt,
+            repl::OutputFormat::Tabular | repl::OutputFormat::Auto | repl::OutputFormat::Kv
+        ) {
+            anyhow::bail!(
+                "`--output-format={}` can only be written to the terminal, \
+                 not to `--output-file`",
+                fmt.as_str()
+            );
+        }
+        let file = fs::File::create(path)
+            .with_context(|| format!("cannot create output file {}", path.display()))?;
+        output.file = Some(io::BufWriter::new(file));
+    }
+
     if let Some(filename) = &q.file {
         if filename == "-" {
-            interpret_file(&mut stdin(), options, fmt, lang).await?;
+            interpret_file(&mut stdin(), options, fmt, lang, &mut output).await?;
         } else {
             let mut file = AsyncFile::open(filename).await?;
-            interpret_file(&mut file, options, fmt, lang).await?;
+            interpret_file(&mut file, options, fmt, lang, &mut output).await?;
         }
     } else if let Some(queries) = &q.queries {
         let mut conn = options.create_connector().await?.connect().await?;
@@ -73,7 +125,7 @@ pub async fn noninteractive_main(q: &Query, options: &Options) -> Result<(), any
                                Use the dedicated `{BRANDING_CLI_CMD} analyze` command."
                 );
             }
-            run_query(&mut conn, query, options, fmt, lang).await?;
+            run_query(&mut conn, query, options, fmt, lang, &mut output).await?;
         }
     } else {
         print::error!(
@@ -81,17 +133,65 @@ pub async fn noninteractive_main(q: &Query, options: &Options) -> Result<(), any
                      a <queries> positional argument is required."
         );
     }
+    if let Some(file) = output.file.take() {
+        close_output_file(file, q.fsync).context("cannot write output file")?;
+    }
 
     Ok(())
 }
 
+/// Flushes the output file, and with `--fsync` waits until it is on disk
+fn close_output_file(file: io::BufWriter<fs::File>, fsync: bool) -> io::Result<()> {
+    let file = file.into_inner().map_err(io::IntoInnerError::into_error)?;
+    if fsync {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Where query results go: stdout, or the file given with `--output-file`
+struct QueryOutput {
+    file: Option<io::BufWriter<fs::File>>,
+    /// a parquet file holds a single result, so only one query may write it
+    #[cfg(feature = "parquet")]
+    parquet_written: bool,
+    /// `--invalid-utf8`, for tab-separated output
+    invalid_utf8: InvalidUtf8,
+    /// print settings given on the command line, e.g. `--json-style`
+    print: print::Config,
+    /// `--sample` size and `--seed`
+    sample: Option<u64>,
+    seed: Option<u64>,
+    /// `--error-on-empty`
+    error_on_empty: bool,
+}
+
+impl QueryOutput {
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.write_all(data),
+            None => stdout().lock().write_all(data),
+        }
+    }
+}
+
 #[tokio::main(flavor = "current_thread")]
 pub async fn interpret_stdin(
     options: &Options,
     fmt: repl::OutputFormat,
     lang: repl::InputLanguage,
 ) -> Result<(), anyhow::Error> {
-    return interpret_file(&mut stdin(), options, fmt, lang).await;
+    let mut output = QueryOutput {
+        file: None,
+        #[cfg(feature = "parquet")]
+        parquet_written: false,
+        invalid_utf8: InvalidUtf8::default(),
+        print: print::Config::new(),
+        sample: None,
+        seed: None,
+        error_on_empty: false,
+    };
+    return interpret_file(&mut stdin(), options, fmt, lang, &mut output).await;
 }
 
 async fn interpret_file<T>(
@@ -99,6 +199,7 @@ async fn interpret_file<T>(
     options: &Options,
     fmt: repl::OutputFormat,
     lang: repl::InputLanguage,
+    output: &mut QueryOutput,
 ) -> Result<(), anyhow::Error>
 where
     T: AsyncRead + Unpin,
@@ -121,7 +222,7 @@ where
                            Use the dedicated `{BRANDING_CLI_CMD} analyze` command."
             );
         }
-        run_query(&mut conn, stmt, options, fmt, lang).await?;
+        run_query(&mut conn, stmt, options, fmt, lang, output).await?;
     }
     Ok(())
 }
@@ -132,8 +233,9 @@ async fn run_query(
     options: &Options,
     fmt: repl::OutputFormat,
     lang: repl::InputLanguage,
+    output: &mut QueryOutput,
 ) -> Result<(), anyhow::Error> {
-    _run_query(conn, stmt, options, fmt, lang)
+    _run_query(conn, stmt, options, fmt, lang, output)
         .await
         .map_err(|err| {
             if let Some(err) = err.downcast_ref::<gel_errors::Error>() {
@@ -147,18 +249,57 @@ async fn run_query(
         })
 }
 
+/// Print settings given on the command line, layered onto the defaults
+fn print_config(q: &Query) -> print::Config {
+    let overrides = print::ConfigOverrides {
+        expand_json: q.expand_json.then_some(true),
+        null_text: q.null_text.clone(),
+        empty_set_text: q.empty_set_text.clone(),
+        max_output_lines: q.max_output_lines.map(Some),
+        items_window: q.items_window.map(Some),
+        trailing_comma: q.no_trailing_comma.then_some(false),
+        ascii_only: q.ascii.then_some(true),
+        json_envelope: q.json_envelope.clone().map(Some),
+        json_envelope_count: q.json_envelope_count.then_some(true),
+        json_style: Some(q.json_style),
+        empty_result_note: Some(q.empty_note),
+        ..Default::default()
+    };
+    let mut print = print::Config::with_overrides(&print::Config::new(), overrides);
+    print.indent(q.indent);
+    print
+}
+
+async fn native_output<S, I, E>(
+    rows: S,
+    config: &print::Config,
+    output: &mut QueryOutput,
+) -> Result<(), PrintError<E, io::Error>>
+where
+    S: tokio_stream::Stream<Item = Result<I, E>> + Send + Unpin,
+    I: print::FormatExt,
+    E: std::fmt::Debug + std::error::Error + 'static,
+{
+    match &mut output.file {
+        Some(file) => print::native_to_output(rows, config, file).await,
+        None => print::native_to_stdout(rows, config).await,
+    }
+}
+
 async fn _run_query(
     conn: &mut Connection,
     stmt: &str,
     _options: &Options,
     fmt: repl::OutputFormat,
     lang: repl::InputLanguage,
+    output: &mut QueryOutput,
 ) -> Result<(), anyhow::Error> {
     use crate::repl::OutputFormat::*;
 
     let flags = CompilationOptions {
         implicit_limit: None,
-        implicit_typenames: fmt == Default && conn.protocol().supports_inline_typenames(),
+        implicit_typenames: matches!(fmt, Default | Edgeql)
+            && conn.protocol().supports_inline_typenames(),
         implicit_typeids: false,
         explicit_objectids: true,
         allow_capabilities: Capabilities::ALL,
@@ -168,11 +309,11 @@ async fn _run_query(
     };
     let data_description = conn.parse(&flags, stmt).await?;
 
-    let mut cfg = print::Config::new();
+    let mut cfg = output.print.clone();
     if let Some((Width(w), _h)) = terminal_size() {
         cfg.max_width(w.into());
     }
-    cfg.colors(stdout().is_terminal());
+    cfg.colors(output.file.is_none() && print::use_color());
 
     let mut items = conn
         .execute_stream(&flags, stmt, &data_description, &())
@@ -186,16 +327,73 @@ async fn _run_query(
         return Ok(());
     }
 
+    let mut sampled = None;
+    let mut rows: Rows<'_> = match output.sample {
+        Some(size) => {
+            let mut reservoir = Reservoir::new(size, output.seed);
+            while let Some(row) = items.next().await.transpose()? {
+                reservoir.push(row);
+            }
+            let (rows, total) = reservoir.finish();
+            sampled = Some((rows.len(), total));
+            Box::new(tokio_stream::iter(rows.into_iter().map(Ok)))
+        }
+        None => Box::new(&mut items),
+    };
+    // rows are counted as they are printed, for `--empty-note` and
+    // `--error-on-empty`; a JSON result is a single row holding an array
+    let mut count = 0;
+    let mut json_rows = None;
+    let mut rows: Rows<'_> = Box::new(rows.inspect(|row| {
+        if row.is_ok() {
+            count += 1;
+        }
+    }));
+
     match fmt {
         repl::OutputFormat::TabSeparated => {
-            while let Some(row) = items.next().await.transpose()? {
-                let mut text = tab_separated::format_row(&row)?;
+            while let Some(row) = rows.next().await.transpose()? {
+                let mut text = tab_separated::format_row(&row, &cfg, output.invalid_utf8)?;
                 // trying to make writes atomic if possible
                 text += "\n";
-                stdout().lock().write_all(text.as_bytes())?;
+                output.write_all(text.as_bytes())?;
+            }
+        }
+        repl::OutputFormat::Edgeql => {
+            while let Some(row) = rows.next().await.transpose()? {
+                let mut text = print::edgeql::format_row(&row)?;
+                // trying to make writes atomic if possible
+                text += "\n";
+                output.write_all(text.as_bytes())?;
+            }
+        }
+        #[cfg(not(feature = "parquet"))]
+        repl::OutputFormat::Parquet => {
+            anyhow::bail!("this build of {BRANDING_CLI_CMD} was compiled without parquet support");
+        }
+        #[cfg(feature = "parquet")]
+        repl::OutputFormat::Parquet => {
+            let Some(file) = &mut output.file else {
+                anyhow::bail!("`--output-format=parquet` can only be written to `--output-file`");
+            };
+            if output.parquet_written {
+                anyhow::bail!("parquet output can only hold the result of a single query");
+            }
+            output.parquet_written = true;
+            let mut writer = print::parquet::ParquetWriter::new(file);
+            while let Some(row) = rows.next().await.transpose()? {
+                writer.push(row)?;
+            }
+            writer.finish()?;
+        }
+        repl::OutputFormat::Msgpack => {
+            while let Some(row) = rows.next().await.transpose()? {
+                // trying to make writes atomic if possible
+                let data = print::msgpack::encode_row(&row)?;
+                output.write_all(&data)?;
             }
         }
-        repl::OutputFormat::Tabular => match print::table_to_stdout(&mut items, &cfg).await {
+        repl::OutputFormat::Tabular => match print::table_to_stdout(&mut rows, &cfg).await {
             Ok(()) => {}
             Err(e) => {
                 match e {
@@ -211,7 +409,39 @@ async fn _run_query(
                 return Ok(());
             }
         },
-        repl::OutputFormat::Default => match print::native_to_stdout(&mut items, &cfg).await {
+        repl::OutputFormat::Auto => match print::auto_to_stdout(&mut rows, &cfg).await {
+            Ok(()) => {}
+            Err(e) => {
+                match e {
+                    PrintError::StreamErr {
+                        source: ref error, ..
+                    } => {
+                        print::error!("{error}");
+                    }
+                    _ => {
+                        print::error!("{e}");
+                    }
+                }
+                return Ok(());
+            }
+        },
+        repl::OutputFormat::Kv => match print::kv_to_stdout(&mut rows, &cfg).await {
+            Ok(()) => {}
+            Err(e) => {
+                match e {
+                    PrintError::StreamErr {
+                        source: ref error, ..
+                    } => {
+                        print::error!("{error}");
+                    }
+                    _ => {
+                        print::error!("{e}");
+                    }
+                }
+                return Ok(());
+            }
+        },
+        repl::OutputFormat::Default => match native_output(&mut rows, &cfg, output).await {
             Ok(()) => {}
             Err(e) => {
                 match e {
@@ -228,7 +458,7 @@ async fn _run_query(
             }
         },
         repl::OutputFormat::JsonPretty => {
-            while let Some(row) = items.next().await.transpose()? {
+            while let Some(row) = rows.next().await.transpose()? {
                 let text = match row {
                     Value::Str(s) => s,
                     _ => {
@@ -243,11 +473,11 @@ async fn _run_query(
                 // trying to make writes atomic if possible
                 let mut data = print::json_item_to_string(&value, &cfg)?;
                 data += "\n";
-                stdout().lock().write_all(data.as_bytes())?;
+                output.write_all(data.as_bytes())?;
             }
         }
         repl::OutputFormat::JsonLines => {
-            while let Some(row) = items.next().await.transpose()? {
+            while let Some(row) = rows.next().await.transpose()? {
                 let mut text = match row {
                     Value::Str(s) => s,
                     _ => {
@@ -259,11 +489,11 @@ async fn _run_query(
                 };
                 // trying to make writes atomic if possible
                 text += "\n";
-                stdout().lock().write_all(text.as_bytes())?;
+                output.write_all(text.as_bytes())?;
             }
         }
         repl::OutputFormat::Json => {
-            while let Some(row) = items.next().await.transpose()? {
+            while let Some(row) = rows.next().await.transpose()? {
                 let text = match row {
                     Value::Str(s) => s,
                     _ => {
@@ -278,13 +508,150 @@ async fn _run_query(
                 let items = items.as_array().ok_or_else(|| {
                     anyhow::anyhow!("the server returned a non-array value in JSON mode")
                 })?;
+                *json_rows.get_or_insert(0) += items.len() as u64;
                 // trying to make writes atomic if possible
                 let mut data = print::json_to_string(items, &cfg)?;
                 data += "\n";
-                stdout().lock().write_all(data.as_bytes())?;
+                output.write_all(data.as_bytes())?;
             }
         }
     }
+    drop(rows);
     items.complete().await?;
+    if let Some((sampled, total)) = sampled {
+        print::msg!("(sampled {sampled} of {total})");
+    }
+    check_empty(json_rows.unwrap_or(count), fmt, &cfg, output.error_on_empty)
+}
+
+/// Prints the `--empty-note` and fails with `--error-on-empty` if a result
+/// has no rows
+fn check_empty(
+    rows: u64,
+    fmt: repl::OutputFormat,
+    cfg: &print::Config,
+    error_on_empty: bool,
+) -> Result<(), anyhow::Error> {
+    // native output prints the note itself
+    let note = print::empty_result_note(rows, cfg);
+    if let Some(note) = note.filter(|_| fmt != repl::OutputFormat::Default) {
+        eprintln!("{note}");
+    }
+    if rows == 0 && error_on_empty {
+        print::error!("query returned no rows");
+        return Err(ExitCode::new(1).into());
+    }
     Ok(())
 }
+
+/// Rows of a result, either streamed from the server or sampled
+type Rows<'a> =
+    Box<dyn tokio_stream::Stream<Item = Result<Value, gel_errors::Error>> + Send + Unpin + 'a>;
+
+/// Uniform random sample of up to `size` rows of a stream for `--sample`,
+/// picked with reservoir sampling. The sampled rows keep their stream order.
+struct Reservoir<T> {
+    size: u64,
+    rng: StdRng,
+    seen: u64,
+    rows: Vec<(u64, T)>,
+}
+
+impl<T> Reservoir<T> {
+    fn new(size: u64, seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Reservoir {
+            size,
+            rng,
+            seen: 0,
+            rows: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, row: T) {
+        let index = self.seen;
+        self.seen += 1;
+        if (self.rows.len() as u64) < self.size {
+            self.rows.push((index, row));
+        } else {
+            let slot = self.rng.gen_range(0..self.seen);
+            if slot < self.size {
+                self.rows[slot as usize] = (index, row);
+            }
+        }
+    }
+
+    /// Returns the sampled rows and the number of rows seen
+    fn finish(mut self) -> (Vec<T>, u64) {
+        self.rows.sort_by_key(|(index, _)| *index);
+        let rows = self.rows.into_iter().map(|(_, row)| row).collect();
+        (rows, self.seen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{self, Write};
+
+    use super::{Reservoir, check_empty, close_output_file};
+    use crate::commands::ExitCode;
+    use crate::print;
+    use crate::repl::OutputFormat;
+
+    #[test]
+    fn output_file_fsync() {
+        let dir = tempfile::tempdir().unwrap();
+        for fsync in [false, true] {
+            let path = dir.path().join(format!("out-{fsync}.json"));
+            let mut file = io::BufWriter::new(fs::File::create(&path).unwrap());
+            file.write_all(b"[1, 2, 3]\n").unwrap();
+            close_output_file(file, fsync).unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), "[1, 2, 3]\n");
+        }
+    }
+
+    #[test]
+    fn reservoir_sample() {
+        let sample = |seed| {
+            let mut reservoir = Reservoir::new(10, Some(seed));
+            for row in 0..10_000 {
+                reservoir.push(row);
+            }
+            reservoir.finish()
+        };
+        let (rows, total) = sample(42);
+        assert_eq!(total, 10_000);
+        assert_eq!(rows.len(), 10);
+        assert!(rows.is_sorted(), "{rows:?}");
+        // picked across the whole stream, not just the head
+        assert!(rows.iter().any(|&row| row >= 5_000), "{rows:?}");
+        assert_eq!(sample(42), (rows.clone(), total));
+        assert_ne!(sample(7).0, rows);
+
+        let mut reservoir = Reservoir::new(10, Some(42));
+        for row in ["a", "b", "c"] {
+            reservoir.push(row);
+        }
+        assert_eq!(reservoir.finish(), (vec!["a", "b", "c"], 3));
+    }
+
+    #[test]
+    fn error_on_empty() {
+        let mut cfg = print::Config::new();
+        cfg.colors(false);
+        for fmt in [OutputFormat::Default, OutputFormat::Json] {
+            for note in [false, true] {
+                cfg.empty_result_note(note);
+                check_empty(0, fmt, &cfg, false).unwrap();
+                check_empty(3, fmt, &cfg, false).unwrap();
+                check_empty(3, fmt, &cfg, true).unwrap();
+                let err = check_empty(0, fmt, &cfg, true).unwrap_err();
+                assert_eq!(err.downcast_ref::<ExitCode>().unwrap().code(), 1);
+            }
+        }
+    }
+}
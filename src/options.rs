@@ -1,4 +1,5 @@
 use std::env;
+use std::ffi::OsString;
 use std::io::stdin;
 use std::path::PathBuf;
 use std::time::Duration;
@@ -26,11 +27,14 @@ use crate::commands::parser::Common;
 use crate::connect::Connector;
 use crate::hint::HintExt;
 use crate::markdown;
+use crate::outputs::tab_separated::InvalidUtf8;
 use crate::portable;
 use crate::portable::local::runstate_dir;
 use crate::portable::options::InstanceName;
 use crate::portable::project;
-use crate::print::{self, AsRelativeToCurrentDir, Highlight, err_marker};
+use crate::print::{
+    self, AsRelativeToCurrentDir, ColorChoice, Highlight, JsonStyle, MessagesFormat, err_marker,
+};
 use crate::repl::{InputLanguage, OutputFormat};
 use crate::tty_password;
 
@@ -230,6 +234,8 @@ pub struct ConnectionOptions {
 
     /// Fail when no response from EdgeDB for TIMEOUT (default '10s');
     /// alternatively will retry if `--wait-until-available` is also specified.
+    /// Also limits connecting to the Cloud API, and how long `restore`
+    /// waits for the server before restoring each database (default '5m').
     #[arg(
         long,
         value_name="TIMEOUT",
@@ -413,7 +419,8 @@ pub struct CloudOptions {
     #[arg(global = true)]
     pub cloud_secret_key: Option<String>,
 
-    /// Specify the authenticated profile. Defaults to "default".
+    /// Specify the authenticated profile. Defaults to the
+    /// `GEL_CLOUD_PROFILE` environment variable, or "default".
     #[arg(long, value_name="PROFILE", help_heading=Some(CLOUD_OPTIONS_GROUP))]
     #[arg(global = true)]
     pub cloud_profile: Option<String>,
@@ -467,6 +474,18 @@ pub struct RawOptions {
     #[arg(long)]
     pub no_cli_update_check: bool,
 
+    /// Write long output directly to stdout instead of using a pager
+    #[arg(long)]
+    pub no_pager: bool,
+
+    /// When to use colors in the output
+    #[arg(long, global = true, value_enum, default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Format of status messages; `json` adds a JSON line on success
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    pub messages_format: MessagesFormat,
+
     #[command(flatten)]
     pub conn: ConnectionOptions,
 
@@ -523,13 +542,12 @@ pub struct Query {
     #[command(flatten)]
     pub conn: ConnectionOptions,
 
-    /// Output format: `json`, `json-pretty`, `json-lines`, `tab-separated`.
-    /// Default is `json-pretty`.
+    /// Output format. Default is `json-pretty`.
     // todo: can't use `arg(default='json-pretty')` just yet, as we
     // need to see if the user did actually specify some output
     // format or not. We need that to support the now deprecated
     // --json and --tab-separated top-level options.
-    #[arg(short = 'F', long)]
+    #[arg(short = 'F', long, value_enum)]
     pub output_format: Option<OutputFormat>,
 
     /// Input language: `edgeql`, `sql`.
@@ -542,6 +560,100 @@ pub struct Query {
     #[arg(short = 'f', long)]
     pub file: Option<String>,
 
+    /// Write results to this file instead of stdout. Unless
+    /// `--output-format` is given, the format follows the extension:
+    /// `.json`, `.jsonl`/`.ndjson` (json-lines), `.tsv` (tab-separated),
+    /// `.edgeql`, `.parquet` or `.msgpack`; other files get the default
+    /// format.
+    #[arg(short = 'o', long, value_hint = clap::ValueHint::FilePath)]
+    pub output_file: Option<PathBuf>,
+
+    /// Sync `--output-file` to disk before exiting, so the export survives
+    /// a crash or power loss. Off by default: waiting for the disk can take
+    /// a while for large files
+    #[arg(long, requires = "output_file")]
+    pub fsync: bool,
+
+    /// What to do with control characters, such as tabs and newlines, in
+    /// strings of `tab-separated` output: `error` fails the query,
+    /// `replace` writes U+FFFD instead, `escape` writes `\uXXXX`
+    #[arg(long, value_name = "mode", default_value_t, value_enum)]
+    pub invalid_utf8: InvalidUtf8,
+
+    /// Layout of `json` and `json-pretty` output: `pretty` breaks values
+    /// that don't fit the terminal into lines indented by `--indent`,
+    /// `compact` prints every result on a single line
+    #[arg(long, value_name = "style", default_value_t, value_enum)]
+    pub json_style: JsonStyle,
+
+    /// Spaces to indent nested values by with `--json-style=pretty`
+    #[arg(long, value_name = "n", default_value_t = 2)]
+    pub indent: usize,
+
+    /// Render `json` values in `default` output as nested, highlighted
+    /// structures instead of quoted strings
+    #[arg(long)]
+    pub expand_json: bool,
+
+    /// Text printed for missing values in `default`, `table` and
+    /// `tab-separated` output. JSON output always has `null`
+    #[arg(long, value_name = "text")]
+    pub null_text: Option<String>,
+
+    /// Text printed for empty sets in `default` and `table` output
+    #[arg(long, value_name = "text")]
+    pub empty_set_text: Option<String>,
+
+    /// Stop `default` output after this many lines and mark it as
+    /// truncated
+    #[arg(long, value_name = "n")]
+    pub max_output_lines: Option<usize>,
+
+    /// Print only the first `HEAD` and the last `TAIL` rows of each result
+    /// in `default` output, e.g. `10..10`
+    #[arg(long, value_name = "HEAD..TAIL", value_parser = parse_items_window)]
+    pub items_window: Option<(usize, usize)>,
+
+    /// Don't put a comma after the last element of multi-line sets,
+    /// arrays and objects in `default` output
+    #[arg(long)]
+    pub no_trailing_comma: bool,
+
+    /// Draw tables with `+`, `-` and `|` and mark truncated values with
+    /// `...`. By default this is detected from the terminal encoding
+    #[arg(long)]
+    pub ascii: bool,
+
+    /// Wrap `json` output into an object with the result array under
+    /// this key, e.g. `{"data": [...]}`
+    #[arg(long, value_name = "key")]
+    pub json_envelope: Option<String>,
+
+    /// Add the number of rows to the `--json-envelope` object as `count`
+    #[arg(long, requires = "json_envelope")]
+    pub json_envelope_count: bool,
+
+    /// Print a uniform random sample of `n` rows of each result instead of
+    /// all of them, in result order. Unlike `LIMIT`, the rows are picked
+    /// across the whole result, which is read to the end; only the sampled
+    /// rows are kept in memory
+    #[arg(long, value_name = "n", value_parser = clap::value_parser!(u64).range(1..))]
+    pub sample: Option<u64>,
+
+    /// Seed of `--sample`, to pick the same rows on every run
+    #[arg(long, value_name = "n", requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Print a `(no rows)` note to stderr when a query returns no rows.
+    /// The output itself is unchanged, e.g. `[]` in JSON formats
+    #[arg(long)]
+    pub empty_note: bool,
+
+    /// Exit with a non-zero code when a query returns no rows, e.g. for
+    /// health checks
+    #[arg(long)]
+    pub error_on_empty: bool,
+
     pub queries: Option<Vec<String>>,
 }
 
@@ -596,6 +708,7 @@ pub struct Options {
     pub output_format: Option<OutputFormat>,
     pub sql_output_format: Option<OutputFormat>,
     pub no_cli_update_check: bool,
+    pub no_pager: bool,
     pub test_output_conn_params: bool,
 }
 
@@ -618,7 +731,7 @@ impl UsageError {
     }
 }
 
-fn parse_duration(value: &str) -> anyhow::Result<Duration> {
+pub(crate) fn parse_duration(value: &str) -> anyhow::Result<Duration> {
     let value = value.parse::<model::Duration>()?;
     match value.is_negative() {
         false => Ok(value.abs_duration()),
@@ -626,6 +739,13 @@ fn parse_duration(value: &str) -> anyhow::Result<Duration> {
     }
 }
 
+fn parse_items_window(value: &str) -> anyhow::Result<(usize, usize)> {
+    let Some((head, tail)) = value.split_once("..") else {
+        anyhow::bail!("expected `HEAD..TAIL`, e.g. `10..10`");
+    };
+    Ok((head.parse()?, tail.parse()?))
+}
+
 fn say_option_is_deprecated(option_name: &str, suggestion: &str) {
     let error = "warning:".to_string().emphasized().warning();
     let instead = suggestion.to_string().success();
@@ -744,9 +864,7 @@ fn make_subcommand_help(parent: &clap::Command) -> String {
 
 /// Swap the standard subcommand help with expanded subcommand help.
 fn update_main_help(mut app: clap::Command) -> clap::Command {
-    if !print::use_color() {
-        app = app.color(clap::ColorChoice::Never);
-    }
+    app = app.color(clap_color());
     let sub_cmd = make_subcommand_help(&app);
 
     let help = format!("{}", app.render_help().ansi()).to_string();
@@ -814,10 +932,9 @@ fn print_full_connection_options() {
     let mut app = <HelpConnect as clap::CommandFactory>::command();
     update_cmd_about(&mut app);
 
-    let mut new_app = clap::Command::new("edgedb-connect").term_width(term_width());
-    if !print::use_color() {
-        new_app = new_app.color(clap::ColorChoice::Never);
-    }
+    let mut new_app = clap::Command::new("edgedb-connect")
+        .term_width(term_width())
+        .color(clap_color());
 
     for arg in app.get_arguments() {
         let arg_name = arg.get_id();
@@ -839,6 +956,35 @@ fn print_full_connection_options() {
     println!("{help}");
 }
 
+fn clap_color() -> clap::ColorChoice {
+    if print::use_color() {
+        clap::ColorChoice::Always
+    } else {
+        clap::ColorChoice::Never
+    }
+}
+
+/// Finds `--color` in the raw arguments. Help text is rendered before clap
+/// parses anything, so the option has to be applied ahead of parsing.
+pub fn color_choice_from_args(args: impl IntoIterator<Item = OsString>) -> Option<ColorChoice> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.to_str() {
+            Some("--") => return None,
+            Some("--color") => args.next()?,
+            Some(arg) => match arg.strip_prefix("--color=") {
+                Some(value) => value.into(),
+                None => continue,
+            },
+            None => continue,
+        };
+        return value
+            .to_str()
+            .and_then(|v| clap::ValueEnum::from_str(v, false).ok());
+    }
+    None
+}
+
 fn term_width() -> usize {
     // clap::Command::max_term_width() works poorly in conjunction
     // with  clap::Command::term_width(); it appears that one call
@@ -900,6 +1046,8 @@ impl Options {
         let app = Options::command();
         let matches = app.clone().get_matches();
         let args = <RawOptions as clap::FromArgMatches>::from_arg_matches(&matches)?;
+        print::set_color_choice(args.color);
+        print::set_messages_format(args.messages_format);
         let cmd = <SubcommandOption as clap::FromArgMatches>::from_arg_matches(&matches)?;
 
         let subcommand = cmd.subcommand;
@@ -947,6 +1095,24 @@ impl Options {
                 output_format,
                 input_language: Some(InputLanguage::EdgeQl),
                 file: None,
+                output_file: None,
+                fsync: false,
+                invalid_utf8: InvalidUtf8::default(),
+                json_style: JsonStyle::default(),
+                indent: 2,
+                expand_json: false,
+                null_text: None,
+                empty_set_text: None,
+                max_output_lines: None,
+                items_window: None,
+                no_trailing_comma: false,
+                ascii: false,
+                json_envelope: None,
+                json_envelope_count: false,
+                sample: None,
+                seed: None,
+                empty_note: false,
+                error_on_empty: false,
                 conn: args.conn.clone(),
             }))
         } else {
@@ -985,11 +1151,17 @@ impl Options {
             },
             sql_output_format: None,
             no_cli_update_check,
+            no_pager: args.no_pager,
             test_output_conn_params: args.test_output_conn_params,
         })
     }
 
     pub async fn create_connector(&self) -> anyhow::Result<Connector> {
+        let connector = self.create_connector_inner().await?;
+        Ok(connector.with_connect_timeout(self.conn_options.connect_timeout))
+    }
+
+    async fn create_connector_inner(&self) -> anyhow::Result<Connector> {
         let mut builder = prepare_conn_params(self).await?;
         if self.conn_options.password_from_stdin || self.conn_options.password {
             // Temporary set an empty password. It will be overriden by
@@ -1202,3 +1374,43 @@ pub fn load_tls_options(
     }
     Ok(builder)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Options, parse_items_window};
+    use crate::repl::OutputFormat;
+
+    #[test]
+    fn output_format_values() {
+        let err = Options::command()
+            .try_get_matches_from(["gel", "query", "-F", "csv", "select 1"])
+            .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::InvalidValue);
+        let err = err.to_string();
+        assert!(err.contains("possible values"), "{err}");
+        assert!(err.contains("json-pretty"), "{err}");
+
+        let matches = Options::command()
+            .try_get_matches_from(["gel", "query", "--output-format=table", "select 1"])
+            .unwrap();
+        assert!(matches.subcommand_matches("query").is_some());
+
+        assert_eq!(
+            "native".parse::<OutputFormat>().unwrap(),
+            OutputFormat::Default
+        );
+        let err = "csv".parse::<OutputFormat>().unwrap_err().to_string();
+        assert!(
+            err.starts_with("unsupported output mode \"csv\", valid values are: default, json,"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn items_window_values() {
+        assert_eq!(parse_items_window("10..5").unwrap(), (10, 5));
+        assert_eq!(parse_items_window("0..3").unwrap(), (0, 3));
+        assert!(parse_items_window("10").is_err());
+        assert!(parse_items_window("a..3").is_err());
+    }
+}
@@ -1,6 +1,30 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
 use gel_protocol::value::Value::{self, *};
 
-pub fn format_row(v: &Value) -> Result<String, anyhow::Error> {
+use crate::print::Config;
+
+/// What to do with control characters (tabs, newlines and the like) in
+/// strings, which would break the tab-separated layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+#[value(rename_all = "lowercase")]
+pub enum InvalidUtf8 {
+    /// Fail the query
+    Error,
+    /// Replace them with U+FFFD
+    Replace,
+    /// Write them as `\uXXXX`
+    #[default]
+    Escape,
+}
+
+pub fn format_row(
+    v: &Value,
+    config: &Config,
+    invalid: InvalidUtf8,
+) -> Result<String, anyhow::Error> {
+    let null_text = config.null_text.as_deref().unwrap_or("");
     match v {
         Object { shape, fields } => Ok(shape
             .elements
@@ -8,21 +32,49 @@ pub fn format_row(v: &Value) -> Result<String, anyhow::Error> {
             .zip(fields)
             .filter(|(s, _)| !s.flag_implicit)
             .map(|(_, v)| match v {
-                Some(v) => value_to_string(v),
-                None => Ok(String::new()),
+                Some(v) => value_to_string(v, null_text, invalid),
+                None => Ok(null_text.to_string()),
             })
             .collect::<Result<Vec<_>, _>>()?
             .join("\t")),
-        _ => value_to_string(v),
+        _ => value_to_string(v, null_text, invalid),
+    }
+}
+
+fn clean_str(s: &str, invalid: InvalidUtf8) -> anyhow::Result<Cow<'_, str>> {
+    if !s.contains(char::is_control) {
+        return Ok(s.into());
+    }
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        if !c.is_control() {
+            result.push(c);
+            continue;
+        }
+        match invalid {
+            InvalidUtf8::Error => anyhow::bail!(
+                "string {s:?} contains control character U+{:04X}, which cannot be \
+                 printed tab-separated; use `--invalid-utf8=escape` or \
+                 `--invalid-utf8=replace`",
+                u32::from(c)
+            ),
+            InvalidUtf8::Replace => result.push(char::REPLACEMENT_CHARACTER),
+            InvalidUtf8::Escape => write!(&mut result, "\\u{:04x}", u32::from(c))?,
+        }
     }
+    Ok(result.into())
 }
 
-fn value_to_string(v: &Value) -> Result<String, anyhow::Error> {
+fn value_to_string(
+    v: &Value,
+    null_text: &str,
+    invalid: InvalidUtf8,
+) -> Result<String, anyhow::Error> {
     use gel_protocol::value::Value::*;
     match v {
-        Nothing => Ok(String::new()),
+        Nothing => Ok(null_text.to_string()),
         Uuid(uuid) => Ok(uuid.to_string()),
-        Str(s) => Ok(s.clone()),
+        Str(s) => Ok(clean_str(s, invalid)?.into_owned()),
         Int16(v) => Ok(v.to_string()),
         Int32(v) => Ok(v.to_string()),
         Int64(v) => Ok(v.to_string()),
@@ -62,3 +114,38 @@ fn value_to_string(v: &Value) -> Result<String, anyhow::Error> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use gel_protocol::value::Value;
+
+    use super::{InvalidUtf8, format_row};
+    use crate::print::Config;
+
+    fn format(s: &str, invalid: InvalidUtf8) -> anyhow::Result<String> {
+        format_row(&Value::Str(s.into()), &Config::new(), invalid)
+    }
+
+    #[test]
+    fn control_characters() {
+        let text = "a\tb\nc\u{7}d\u{85}é";
+        assert_eq!(
+            format(text, InvalidUtf8::Escape).unwrap(),
+            r"a\u0009b\u000ac\u0007d\u0085é"
+        );
+        assert_eq!(
+            format(text, InvalidUtf8::Replace).unwrap(),
+            "a\u{fffd}b\u{fffd}c\u{fffd}d\u{fffd}é"
+        );
+        let err = format(text, InvalidUtf8::Error).unwrap_err();
+        assert!(err.to_string().contains("control character U+0009"));
+
+        for invalid in [
+            InvalidUtf8::Error,
+            InvalidUtf8::Replace,
+            InvalidUtf8::Escape,
+        ] {
+            assert_eq!(format("plain text ✓", invalid).unwrap(), "plain text ✓");
+        }
+    }
+}
@@ -189,18 +189,6 @@ pub fn editor_path() -> anyhow::Result<String> {
     })
 }
 
-pub fn pager_path() -> anyhow::Result<String> {
-    Ok(if let Some(pager) = Env::pager()? {
-        pager
-    } else if let Some(pager) = Env::system_pager()? {
-        pager
-    } else if cfg!(windows) {
-        String::from("more.com")
-    } else {
-        String::from("less -R")
-    })
-}
-
 pub async fn spawn_editor(path: &Path) -> anyhow::Result<()> {
     let editor = editor_path()?;
     let mut items = editor.split_whitespace();
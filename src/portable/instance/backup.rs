@@ -42,7 +42,8 @@ fn list_cloud_backups_cmd(
     name: &str,
     opts: &crate::options::Options,
 ) -> anyhow::Result<()> {
-    let client = cloud::client::CloudClient::new(&opts.cloud_options)?;
+    let client =
+        cloud::client::CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     client.ensure_authenticated()?;
 
     cloud::backups::list_cloud_instance_backups(&client, org_slug, name, cmd.json)?;
@@ -94,7 +95,8 @@ fn backup_cloud_cmd(
     name: &str,
     opts: &crate::options::Options,
 ) -> anyhow::Result<()> {
-    let client = cloud::client::CloudClient::new(&opts.cloud_options)?;
+    let client =
+        cloud::client::CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     client.ensure_authenticated()?;
 
     let inst_name = InstanceName::Cloud {
@@ -165,7 +167,8 @@ fn restore_cloud_cmd(
 ) -> anyhow::Result<()> {
     let backup = &cmd.backup_spec;
 
-    let client = cloud::client::CloudClient::new(&opts.cloud_options)?;
+    let client =
+        cloud::client::CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     client.ensure_authenticated()?;
 
     let inst_name = InstanceName::Cloud {
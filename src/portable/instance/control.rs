@@ -596,9 +596,12 @@ pub fn restart(cmd: &Restart, options: &crate::Options) -> anyhow::Result<()> {
         InstanceName::Cloud {
             org_slug,
             name: inst_name,
-        } => {
-            crate::cloud::ops::restart_cloud_instance(&inst_name, &org_slug, &options.cloud_options)
-        }
+        } => crate::cloud::ops::restart_cloud_instance(
+            &inst_name,
+            &org_slug,
+            &options.cloud_options,
+            options.conn_options.connect_timeout,
+        ),
     }
 }
 
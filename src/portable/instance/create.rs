@@ -52,7 +52,8 @@ pub fn run(cmd: &Command, opts: &crate::options::Options) -> anyhow::Result<()>
         );
     }
 
-    let mut client = cloud::client::CloudClient::new(&opts.cloud_options)?;
+    let mut client =
+        cloud::client::CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     let inst_name = if let Some(name) = cmd.name.as_ref().or(cmd.instance.as_ref()) {
         name.to_owned()
     } else if cmd.non_interactive {
@@ -117,7 +118,7 @@ pub fn run(cmd: &Command, opts: &crate::options::Options) -> anyhow::Result<()>
     let port = cmd.port.map(Ok).unwrap_or_else(|| allocate_port(&name))?;
 
     let info = if cfg!(windows) {
-        windows::create_instance(cmd, &name, port, &paths)?;
+        windows::create_instance(cmd, &name, port)?;
         InstanceInfo {
             name: name.clone(),
             instance_name: inst_name,
@@ -178,6 +179,10 @@ pub fn run(cmd: &Command, opts: &crate::options::Options) -> anyhow::Result<()>
         }
     }
 
+    print::success_json(serde_json::json!({
+        "command": "instance create",
+        "instance": name,
+    }));
     msg!("Instance {} is up and running.", name.clone().emphasized());
     msg!("To connect to the instance run:");
     msg!("  {BRANDING_CLI_CMD} -I {name}");
@@ -504,6 +509,10 @@ fn create_cloud(
         source_backup_id: cmd.cloud_backup_source.from_backup_id.clone(),
     };
     cloud::ops::create_cloud_instance(client, &request)?;
+    print::success_json(serde_json::json!({
+        "command": "instance create",
+        "instance": inst_name.to_string(),
+    }));
     msg!("{BRANDING_CLOUD} instance {inst_name} is up and running.");
     msg!("To connect to the instance run:");
     msg!("  {BRANDING_CLI_CMD} -I {inst_name}");
@@ -37,6 +37,10 @@ pub fn run(options: &Command, opts: &Options) -> anyhow::Result<()> {
             Err(e) => Err(e),
         }
     })?;
+    print::success_json(serde_json::json!({
+        "command": "instance destroy",
+        "instance": name_str,
+    }));
     if !options.quiet {
         msg!(
             "Instance {} is successfully deleted.",
@@ -177,9 +181,12 @@ fn do_destroy(options: &Command, opts: &Options, name: &InstanceName) -> anyhow:
             name: inst_name,
         } => {
             log::info!("Removing {BRANDING_CLOUD} instance {}", name);
-            if let Err(e) =
-                crate::cloud::ops::destroy_cloud_instance(inst_name, org_slug, &opts.cloud_options)
-            {
+            if let Err(e) = crate::cloud::ops::destroy_cloud_instance(
+                inst_name,
+                org_slug,
+                &opts.cloud_options,
+                opts.conn_options.connect_timeout,
+            ) {
                 let msg = format!("Could not destroy {BRANDING_CLOUD} instance: {e:#}");
                 if options.force {
                     print::warn!("{msg}");
@@ -61,7 +61,8 @@ fn resize_cloud_cmd(
         ))?;
     }
 
-    let client = cloud::client::CloudClient::new(&opts.cloud_options)?;
+    let client =
+        cloud::client::CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     client.ensure_authenticated()?;
 
     let inst_name = InstanceName::Cloud {
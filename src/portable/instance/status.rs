@@ -323,7 +323,7 @@ fn cloud_status(
     name: &str,
     opts: &crate::options::Options,
 ) -> anyhow::Result<()> {
-    let client = CloudClient::new(&opts.cloud_options)?;
+    let client = CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     client.ensure_authenticated()?;
 
     let inst = cloud::ops::find_cloud_instance_by_name(name, org, &client)?
@@ -554,7 +554,7 @@ async fn _get_remote(
     opts: &crate::options::Options,
     errors: &Collector<anyhow::Error>,
 ) -> anyhow::Result<Vec<RemoteStatus>> {
-    let cloud_client = CloudClient::new(&opts.cloud_options)?;
+    let cloud_client = CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     let instances: Vec<_> = credentials::all_instance_names()?
         .into_iter()
         .filter(|name| !visited.contains(name))
@@ -241,7 +241,8 @@ fn upgrade_cloud_cmd(
         || anyhow::Ok(Query::stable()),
     )?;
 
-    let client = cloud::client::CloudClient::new(&opts.cloud_options)?;
+    let client =
+        cloud::client::CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     client.ensure_authenticated()?;
 
     let _inst_name = format!("{org}/{name}");
@@ -561,7 +562,7 @@ fn reinit_and_restore(inst: &InstanceInfo, paths: &Paths) -> anyhow::Result<()>
 }
 
 async fn restore_instance(inst: &InstanceInfo, path: &Path) -> anyhow::Result<()> {
-    use crate::commands::parser::Restore;
+    use crate::commands::parser::{DEFAULT_READ_AHEAD, Restore};
     log::info!("Restoring instance {:?}", inst.name);
     let cfg = inst.admin_conn_params()?;
     let mut cli = Connection::connect(&cfg, QUERY_TAG).await?;
@@ -578,8 +579,32 @@ async fn restore_instance(inst: &InstanceInfo, path: &Path) -> anyhow::Result<()
         &Restore {
             path: path.into(),
             all: true,
+            into: Vec::new(),
             verbose: false,
+            log_file: None,
+            no_progress: false,
+            progress_template: None,
+            retries: 0,
+            jobs: 1,
+            read_ahead: DEFAULT_READ_AHEAD,
+            strip_prefix: None,
+            add_prefix: None,
+            allow_empty: false,
+            fail_fast: false,
+            no_fail_fast: false,
             conn: None,
+            create_instance: None,
+            verify_after: false,
+            verify_query: None,
+            strict_verify: false,
+            analyze_after: false,
+            empty_check_timeout: None,
+            timeout: None,
+            dry_run: false,
+            skip_config: false,
+            init_sed: Vec::new(),
+            list_databases: false,
+            json: false,
         },
     )
     .await?;
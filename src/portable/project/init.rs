@@ -1,6 +1,7 @@
 use std::env;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::Context;
 use clap::ValueHint;
@@ -58,9 +59,19 @@ pub fn run(options: &Command, opts: &crate::options::Options) -> anyhow::Result<
 
     if let Some(project_loc) = project_loc {
         if options.link {
-            link(options, project_loc, &opts.cloud_options)?;
+            link(
+                options,
+                project_loc,
+                &opts.cloud_options,
+                opts.conn_options.connect_timeout,
+            )?;
         } else {
-            init_existing(options, project_loc, &opts.cloud_options)?;
+            init_existing(
+                options,
+                project_loc,
+                &opts.cloud_options,
+                opts.conn_options.connect_timeout,
+            )?;
         }
     } else {
         if options.link {
@@ -133,6 +144,7 @@ pub fn init_existing(
     options: &Command,
     project: project::Location,
     cloud_options: &crate::options::CloudOptions,
+    connect_timeout: Option<Duration>,
 ) -> anyhow::Result<project::ProjectInfo> {
     msg!(
         "Found `{}` in {}",
@@ -163,7 +175,7 @@ pub fn init_existing(
     } else {
         project.manifest.instance.server_version.clone()
     };
-    let mut client = CloudClient::new(cloud_options)?;
+    let mut client = CloudClient::new(cloud_options, connect_timeout)?;
     let (name, exists) = ask_name(&project.location.root, options, &mut client)?;
 
     if exists {
@@ -359,7 +371,6 @@ fn do_init(
             },
             name,
             port,
-            &paths,
         )?;
         create::create_service(&InstanceInfo {
             name: name.into(),
@@ -483,6 +494,7 @@ fn link(
     options: &Command,
     project: project::Location,
     cloud_options: &crate::options::CloudOptions,
+    connect_timeout: Option<Duration>,
 ) -> anyhow::Result<project::ProjectInfo> {
     msg!(
         "Found `{}` in {}",
@@ -503,7 +515,7 @@ fn link(
     let project = project::load_ctx_at(project)?;
     let ver_query = &project.manifest.instance.server_version;
 
-    let mut client = CloudClient::new(cloud_options)?;
+    let mut client = CloudClient::new(cloud_options, connect_timeout)?;
     let name = if let Some(name) = &options.server_instance {
         name.clone()
     } else if options.non_interactive {
@@ -619,7 +631,7 @@ fn init_new(
     let schema_dir_path = location.root.join(schema_dir);
     let schema_files = project::find_schema_files(schema_dir)?;
 
-    let mut client = CloudClient::new(&opts.cloud_options)?;
+    let mut client = CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     let (inst_name, exists) = ask_name(&location.root, options, &mut client)?;
 
     if exists {
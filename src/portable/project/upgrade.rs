@@ -133,7 +133,7 @@ pub fn update_toml(
     } else {
         let name = project::instance_name(&stash_dir)?;
         let database = project::database_name(&stash_dir)?;
-        let client = CloudClient::new(&opts.cloud_options)?;
+        let client = CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
         let mut inst = project::Handle::probe(&name, &project.location.root, &schema_dir, &client)?;
         inst.database = database;
 
@@ -229,7 +229,7 @@ pub fn upgrade_instance(cmd: &Command, opts: &crate::options::Options) -> anyhow
 
     let instance_name = project::instance_name(&stash_dir)?;
     let database = project::database_name(&stash_dir)?;
-    let client = CloudClient::new(&opts.cloud_options)?;
+    let client = CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     let mut inst =
         project::Handle::probe(&instance_name, &project.location.root, &schema_dir, &client)?;
     inst.database = database;
@@ -367,7 +367,8 @@ fn upgrade_cloud(
     to_version: &Query,
     opts: &crate::options::Options,
 ) -> anyhow::Result<upgrade::UpgradeResult> {
-    let client = cloud::client::CloudClient::new(&opts.cloud_options)?;
+    let client =
+        cloud::client::CloudClient::new(&opts.cloud_options, opts.conn_options.connect_timeout)?;
     client.ensure_authenticated()?;
 
     let result = upgrade::upgrade_cloud(org, name, to_version, &client, cmd.force, |target_ver| {
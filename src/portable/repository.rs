@@ -24,6 +24,12 @@ use crate::process::IntoArg;
 
 pub const USER_AGENT: &str = BRANDING_CLI;
 pub const DEFAULT_TIMEOUT: Duration = Duration::new(60, 0);
+/// Package servers holding the same files, tried in order by
+/// [`download_mirrors`]
+const PACKAGE_MIRRORS: &[&str] = &[
+    "https://packages.edgedb.com",
+    "https://packages.geldata.com",
+];
 static PKG_ROOT: OnceCell<Url> = OnceCell::new();
 
 #[derive(thiserror::Error, Debug)]
@@ -502,6 +508,67 @@ pub async fn download(
     Ok(hasher.finalize())
 }
 
+/// `url` followed by the same file on the other [`PACKAGE_MIRRORS`], if it
+/// is on one of them
+pub fn package_mirrors(url: &Url) -> Vec<Url> {
+    let mut urls = vec![url.clone()];
+    if !PACKAGE_MIRRORS
+        .iter()
+        .any(|mirror| Url::parse(mirror).is_ok_and(|m| m.origin() == url.origin()))
+    {
+        return urls;
+    }
+    for mirror in PACKAGE_MIRRORS {
+        let mut mirrored = url.clone();
+        let Ok(root) = Url::parse(mirror) else {
+            continue;
+        };
+        if root.origin() == url.origin() || mirrored.set_host(root.host_str()).is_err() {
+            continue;
+        }
+        urls.push(mirrored);
+    }
+    urls
+}
+
+/// Calls `f` with each of `urls` until it succeeds, returning the result
+/// and the URL used, or the error of the last one
+pub fn try_mirrors<'a, T>(
+    urls: &'a [Url],
+    mut f: impl FnMut(&Url) -> anyhow::Result<T>,
+) -> anyhow::Result<(T, &'a Url)> {
+    let mut last_error = None;
+    for (index, url) in urls.iter().enumerate() {
+        match f(url) {
+            Ok(value) => return Ok((value, url)),
+            Err(e) if index + 1 < urls.len() => {
+                log::warn!("Download from {url} failed: {e:#}. Trying the next mirror.");
+                last_error = Some(e);
+            }
+            Err(e) => {
+                last_error = Some(e);
+            }
+        }
+    }
+    let err = last_error.unwrap_or_else(|| anyhow::anyhow!("no download URLs"));
+    if urls.len() > 1 {
+        Err(err.context(format!("all {} download mirrors failed", urls.len())))
+    } else {
+        Err(err)
+    }
+}
+
+/// Same as [`download`], falling back to the next of `urls` on failure
+pub fn download_mirrors(
+    dest: impl AsRef<Path>,
+    urls: &[Url],
+    quiet: bool,
+) -> anyhow::Result<blake2b_simd::Hash> {
+    let (hash, url) = try_mirrors(urls, |url| download(dest.as_ref(), url, quiet))?;
+    log::info!("Downloaded {url}");
+    Ok(hash)
+}
+
 impl fmt::Display for PackageInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}@{}", self.name, self.version)
@@ -869,3 +936,51 @@ impl fmt::Display for QueryDisplay<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use url::Url;
+
+    use super::{package_mirrors, try_mirrors};
+
+    #[test]
+    fn mirror_fallback() {
+        let url: Url = "https://packages.edgedb.com/archive/x86_64-unknown-linux-musl/gel-cli"
+            .parse()
+            .unwrap();
+        let urls = package_mirrors(&url);
+        assert_eq!(urls.len(), 2);
+        assert_eq!(urls[0], url);
+        assert_eq!(
+            urls[1].as_str(),
+            "https://packages.geldata.com/archive/x86_64-unknown-linux-musl/gel-cli"
+        );
+        let custom: Url = "https://example.com/gel-cli".parse().unwrap();
+        assert_eq!(package_mirrors(&custom), [custom]);
+
+        let tried = RefCell::new(Vec::new());
+        let (value, used) = try_mirrors(&urls, |url| {
+            tried.borrow_mut().push(url.clone());
+            if url.host_str() == Some("packages.edgedb.com") {
+                anyhow::bail!("connection refused");
+            }
+            Ok(42)
+        })
+        .unwrap();
+        assert_eq!(value, 42);
+        assert_eq!(used, &urls[1]);
+        assert_eq!(*tried.borrow(), urls);
+
+        let err = try_mirrors(&urls, |url| -> anyhow::Result<()> {
+            anyhow::bail!("HTTP 503 from {url}")
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "all 2 download mirrors failed");
+        assert!(
+            format!("{err:#}").contains("packages.geldata.com"),
+            "{err:#}"
+        );
+    }
+}
@@ -11,6 +11,7 @@ use std::time::{Duration, SystemTime};
 use anyhow::Context;
 use const_format::formatcp;
 use fn_error_context::context;
+use indicatif::{ProgressBar, ProgressStyle};
 use libflate::gzip;
 use once_cell::sync::{Lazy, OnceCell};
 use url::Url;
@@ -24,6 +25,7 @@ use crate::collect::Collector;
 use crate::commands::ExitCode;
 use crate::credentials;
 use crate::hint::HintExt;
+use crate::interrupt;
 use crate::platform::{cache_dir, config_dir, tmp_file_path, wsl_dir};
 use crate::portable::exit_codes;
 use crate::portable::instance;
@@ -34,7 +36,7 @@ use crate::portable::instance::status;
 use crate::portable::local::{InstanceInfo, NonLocalInstance, Paths, write_json};
 use crate::portable::options;
 use crate::portable::project;
-use crate::portable::repository::{self, PackageHash, PackageInfo, download};
+use crate::portable::repository::{self, PackageHash, PackageInfo, download_mirrors};
 use crate::portable::server;
 use crate::portable::ver;
 use crate::print::{self, Highlight, msg};
@@ -43,10 +45,12 @@ use crate::process;
 use super::extension;
 
 const CURRENT_DISTRO: &str = BRANDING_WSL;
-static DISTRO_URL: Lazy<Url> = Lazy::new(|| {
-    "https://aka.ms/wsl-debian-gnulinux"
-        .parse()
-        .expect("wsl url parsed")
+/// Download locations of the distribution, tried in order
+static DISTRO_URLS: Lazy<Vec<Url>> = Lazy::new(|| {
+    ["https://aka.ms/wsl-debian-gnulinux"]
+        .iter()
+        .map(|url| url.parse().expect("wsl url parsed"))
+        .collect()
 });
 const CERT_UPDATE_INTERVAL: Duration = Duration::from_secs(30 * 86400);
 static IS_IN_WSL: Lazy<bool> = Lazy::new(|| {
@@ -60,21 +64,28 @@ static IS_IN_WSL: Lazy<bool> = Lazy::new(|| {
 });
 
 static WSL: OnceCell<Wsl> = OnceCell::new();
+static WSL_DATA_DIR: OnceCell<String> = OnceCell::new();
 
 #[derive(Debug, thiserror::Error)]
 #[error("WSL distribution is not installed")]
 pub struct NoDistribution;
 
+/// User that owns the CLI and the instances inside the distribution
+const DEFAULT_WSL_USER: &str = "edgedb";
+
 pub struct Wsl {
     #[cfg(windows)]
     #[allow(dead_code)]
     lib: wslapi::Library,
     distribution: String,
+    user: String,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 struct WslInfo {
     distribution: String,
+    #[serde(default = "default_wsl_user")]
+    user: String,
     last_checked_version: Option<ver::Semver>,
     #[serde(skip_serializing_if = "Option::is_none")]
     cli_timestamp: Option<SystemTime>,
@@ -82,10 +93,14 @@ struct WslInfo {
     certs_timestamp: SystemTime,
 }
 
+fn default_wsl_user() -> String {
+    DEFAULT_WSL_USER.into()
+}
+
 impl Wsl {
     pub fn edgedb(&self) -> process::Native {
         let mut pro = process::Native::new("edgedb", "edgedb", "wsl");
-        pro.arg("--user").arg("edgedb");
+        pro.arg("--user").arg(&self.user);
         pro.arg("--distribution").arg(&self.distribution);
         pro.arg("_EDGEDB_FROM_WINDOWS=1");
         if let Some(log_env) = env::var_os("RUST_LOG") {
@@ -100,7 +115,7 @@ impl Wsl {
     }
     pub fn sh(&self, _current_dir: &Path) -> process::Native {
         let mut pro = process::Native::new("sh", "sh", "wsl");
-        pro.arg("--user").arg("edgedb");
+        pro.arg("--user").arg(&self.user);
         pro.arg("--distribution").arg(&self.distribution);
         pro.arg("_EDGEDB_FROM_WINDOWS=1");
         if let Some(log_env) = env::var_os("RUST_LOG") {
@@ -136,7 +151,7 @@ impl Wsl {
     fn read_text_file(&self, linux_path: impl AsRef<Path>) -> anyhow::Result<String> {
         process::Native::new("read file", "wsl", "wsl")
             .arg("--user")
-            .arg("edgedb")
+            .arg(&self.user)
             .arg("--distribution")
             .arg(&self.distribution)
             .arg("cat")
@@ -147,7 +162,7 @@ impl Wsl {
     fn check_path_exist(&self, linux_path: impl AsRef<Path>) -> bool {
         process::Native::new("ls file", "wsl", "wsl")
             .arg("--user")
-            .arg("edgedb")
+            .arg(&self.user)
             .arg("--distribution")
             .arg(&self.distribution)
             .arg("ls")
@@ -164,10 +179,41 @@ impl Wsl {
     ) -> anyhow::Result<()> {
         unreachable!();
     }
-}
 
-fn credentials_linux(instance: &str) -> String {
-    format!("/home/edgedb/.config/edgedb/credentials/{instance}.json")
+    fn home_dir(&self) -> String {
+        format!("/home/{}", self.user)
+    }
+
+    /// Base directory of instance data, as reported by the CLI inside WSL
+    fn data_dir(&self) -> &str {
+        WSL_DATA_DIR.get_or_init(|| {
+            let reported = self
+                .edgedb()
+                .arg("info")
+                .arg("--get")
+                .arg("data-dir")
+                .get_stdout_text();
+            resolve_data_dir(reported, &self.home_dir())
+        })
+    }
+
+    /// Copies the credentials of `instance` out of the distribution to
+    /// [`credentials::path`], where connections look them up, so that
+    /// `GEL_CREDENTIALS_DIR` set on the Windows side is honored.
+    fn copy_out_credentials(&self, instance: &str) -> anyhow::Result<()> {
+        let destination = credentials::path(instance)?;
+        if let Some(dir) = destination.parent() {
+            fs_err::create_dir_all(dir)?;
+        }
+        self.copy_out(self.credentials_linux(instance), &destination)
+    }
+
+    fn credentials_linux(&self, instance: &str) -> String {
+        format!(
+            "{}/.config/edgedb/credentials/{instance}.json",
+            self.home_dir()
+        )
+    }
 }
 
 #[context("cannot convert to linux (WSL) path {:?}", path)]
@@ -221,12 +267,7 @@ pub fn path_to_windows(path: &Path) -> anyhow::Result<PathBuf> {
     Ok(result)
 }
 
-pub fn create_instance(
-    options: &create::Command,
-    name: &str,
-    port: u16,
-    paths: &Paths,
-) -> anyhow::Result<()> {
+pub fn create_instance(options: &create::Command, name: &str, port: u16) -> anyhow::Result<()> {
     let wsl = ensure_wsl()?;
 
     let inner_options = create::Command {
@@ -241,10 +282,7 @@ pub fn create_instance(
         .args(&inner_options)
         .run()?;
 
-    if let Some(dir) = paths.credentials.parent() {
-        fs_err::create_dir_all(dir)?;
-    }
-    wsl.copy_out(credentials_linux(name), &paths.credentials)?;
+    wsl.copy_out_credentials(name)?;
 
     Ok(())
 }
@@ -299,34 +337,61 @@ fn read_wsl(path: &Path) -> anyhow::Result<WslInfo> {
     Ok(serde_json::from_reader(reader)?)
 }
 
+fn find_zip_entry<R: io::Read + io::Seek>(
+    zip: &zip::ZipArchive<R>,
+    description: &str,
+    pred: impl Fn(&str) -> bool,
+) -> anyhow::Result<String> {
+    if let Some(name) = zip.file_names().find(|name| pred(name)) {
+        return Ok(name.to_string());
+    }
+    let mut names = zip.file_names().collect::<Vec<_>>();
+    names.sort();
+    anyhow::bail!(
+        "file `{description}` is not found in archive; archive contains: {}",
+        names.join(", ")
+    );
+}
+
+fn unpack_bar(len: u64) -> ProgressBar {
+    let bar = ProgressBar::new(len);
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("Unpacking [{bar}] {bytes:>7.dim}/{total_bytes:7}")
+            .expect("template is ok")
+            .progress_chars("=> "),
+    );
+    bar
+}
+
 #[context("cannot unpack debian distro from {:?}", zip_path)]
 fn unpack_appx(zip_path: &Path, dest: &Path) -> anyhow::Result<()> {
     let mut zip = zip::ZipArchive::new(io::BufReader::new(fs::File::open(zip_path)?))?;
-    let name = zip
-        .file_names()
-        .find(|name| {
-            let lower = name.to_lowercase();
-            lower.starts_with("distrolauncher-") && lower.ends_with("_x64.appx")
-        })
-        .ok_or_else(|| anyhow::anyhow!("file `DistroLauncher-*_x64.appx` is not found in archive"))?
-        .to_string();
-    let mut inp = zip.by_name(&name)?;
+    let name = find_zip_entry(&zip, "DistroLauncher-*_x64.appx", |name| {
+        let lower = name.to_lowercase();
+        lower.starts_with("distrolauncher-") && lower.ends_with("_x64.appx")
+    })?;
+    let inp = zip.by_name(&name)?;
+    let bar = unpack_bar(inp.size());
     let mut out = fs::File::create(dest)?;
-    io::copy(&mut inp, &mut out)?;
+    io::copy(&mut bar.wrap_read(inp), &mut out)?;
+    bar.finish_and_clear();
     Ok(())
 }
 
 #[context("cannot unpack root filesystem from {:?}", zip_path)]
 fn unpack_root(zip_path: &Path, dest: &Path) -> anyhow::Result<()> {
     let mut zip = zip::ZipArchive::new(io::BufReader::new(fs::File::open(zip_path)?))?;
-    let name = zip
-        .file_names()
-        .find(|name| name.eq_ignore_ascii_case("install.tar.gz"))
-        .ok_or_else(|| anyhow::anyhow!("file `install.tar.gz` is not found in archive"))?
-        .to_string();
-    let mut inp = gzip::Decoder::new(io::BufReader::new(zip.by_name(&name)?))?;
+    let name = find_zip_entry(&zip, "install.tar.gz", |name| {
+        name.eq_ignore_ascii_case("install.tar.gz")
+    })?;
+    let entry = zip.by_name(&name)?;
+    // progress is tracked on the compressed stream, its size is known upfront
+    let bar = unpack_bar(entry.size());
+    let mut inp = gzip::Decoder::new(io::BufReader::new(bar.wrap_read(entry)))?;
     let mut out = fs::File::create(dest)?;
     io::copy(&mut inp, &mut out)?;
+    bar.finish_and_clear();
     Ok(())
 }
 
@@ -342,13 +407,13 @@ fn wsl_check_cli(_wsl: &wslapi::Library, wsl_info: &WslInfo) -> anyhow::Result<b
 
 #[cfg(windows)]
 #[context("cannot check linux CLI version")]
-fn wsl_cli_version(distro: &str) -> anyhow::Result<ver::Semver> {
+fn wsl_cli_version(distro: &str, user: &str) -> anyhow::Result<ver::Semver> {
     // Note: cannot capture output using wsl.launch
 
     use const_format::concatcp;
     let data = process::Native::new("check version", "edgedb", "wsl")
         .arg("--user")
-        .arg("edgedb")
+        .arg(user)
         .arg("--distribution")
         .arg(distro)
         .arg("/usr/bin/edgedb")
@@ -405,13 +470,34 @@ fn download_binary(dest: &Path) -> anyhow::Result<()> {
 
     let down_path = dest.with_extension("download");
     let tmp_path = tmp_file_path(&dest);
-    download(&down_path, &pkg.url, false)?;
+    download_mirrors(&down_path, &repository::package_mirrors(&pkg.url), false)?;
     upgrade::unpack_file(&down_path, &tmp_path, pkg.compression)?;
     fs_err::rename(&tmp_path, dest)?;
 
     Ok(())
 }
 
+/// Custom distributions may already have the user or have uid 1000 taken,
+/// so only the default user gets a fixed uid and existing users are kept.
+#[cfg(windows)]
+#[context("cannot create user {:?} in WSL distribution", user)]
+fn create_wsl_user(wsl: &wslapi::Library, distro: &str, user: &str) -> anyhow::Result<()> {
+    let escaped = shell_escape::unix::escape(user.into());
+    let uid = if user == DEFAULT_WSL_USER {
+        " --uid 1000"
+    } else {
+        ""
+    };
+    wsl_simple_cmd(
+        wsl,
+        distro,
+        &format!("id -u {escaped} >/dev/null 2>&1 || useradd {escaped}{uid} --create-home"),
+    )?;
+    wsl_simple_cmd(wsl, distro, &format!("id -u {escaped}"))
+        .context("user does not exist after creation")?;
+    Ok(())
+}
+
 #[cfg(windows)]
 fn wsl_simple_cmd(wsl: &wslapi::Library, distro: &str, cmd: &str) -> anyhow::Result<()> {
     let code = wsl.launch_interactive(distro, cmd, /* current_working_dir */ false)?;
@@ -421,7 +507,7 @@ fn wsl_simple_cmd(wsl: &wslapi::Library, distro: &str, cmd: &str) -> anyhow::Res
     Ok(())
 }
 
-fn utf16_contains(bytes: &[u8], needle: &str) -> bool {
+fn decode_utf16_le(bytes: &[u8]) -> String {
     use std::char::{REPLACEMENT_CHARACTER, decode_utf16};
     decode_utf16(
         bytes
@@ -429,16 +515,238 @@ fn utf16_contains(bytes: &[u8], needle: &str) -> bool {
             .map(|a| u16::from_le_bytes([a[0], a[1]])),
     )
     .map(|r| r.unwrap_or(REPLACEMENT_CHARACTER))
-    .collect::<String>()
-    .contains(needle)
+    .collect()
+}
+
+fn utf16_contains(bytes: &[u8], needle: &str) -> bool {
+    decode_utf16_le(bytes).contains(needle)
+}
+
+/// Distribution names from the UTF-16 output of `wsl --list --quiet`
+fn parse_wsl_distros(output: &[u8]) -> Vec<String> {
+    decode_utf16_le(output)
+        .lines()
+        .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '\u{feff}' || c == '\0'))
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Finds a distribution set up by an earlier install but registered under
+/// another name than [`CURRENT_DISTRO`], e.g. imported manually. Ours is
+/// a Debian root filesystem with the Linux CLI at `/usr/bin/edgedb`.
+#[cfg(windows)]
+fn find_registered_distro(wsl: &wslapi::Library) -> anyhow::Result<Option<String>> {
+    let output = match process::Native::new("wsl list", "wsl", "wsl")
+        .arg("--list")
+        .arg("--quiet")
+        .get_output()
+    {
+        Ok(output) => output,
+        Err(e) => {
+            log::warn!("Cannot list WSL distributions: {e:#}");
+            return Ok(None);
+        }
+    };
+    for name in parse_wsl_distros(&output.stdout) {
+        if name == CURRENT_DISTRO {
+            continue;
+        }
+        let code = wsl.launch_interactive(
+            &name,
+            "grep -q '^ID=debian' /etc/os-release && test -x /usr/bin/edgedb",
+            /* current_working_dir */ false,
+        )?;
+        if code == 0 {
+            return Ok(Some(name));
+        }
+    }
+    Ok(None)
+}
+
+/// Steps of the first-run WSL setup, reported as
+/// `{"step": "unpack", "status": "start"}` and then `"done"` with
+/// `--messages-format=json`, so that installers can show the progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(not(windows), allow(dead_code))]
+enum WslStep {
+    /// Downloading the Debian distribution
+    Download,
+    /// Unpacking the root filesystem from the download
+    Unpack,
+    /// Importing the distribution into WSL
+    Initialize,
+    /// Creating the user running the servers
+    CreateUser,
+    /// Installing this version of the CLI into the distribution
+    UpdateCli,
+    /// Updating the CA certificates of the distribution
+    UpdateCerts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum StepStatus {
+    Start,
+    Done,
+}
+
+#[derive(serde::Serialize)]
+struct StepEvent {
+    step: WslStep,
+    status: StepStatus,
+}
+
+impl WslStep {
+    /// Message printed when the step starts
+    #[cfg_attr(not(windows), allow(dead_code))]
+    fn message(self) -> Option<&'static str> {
+        match self {
+            WslStep::Download | WslStep::CreateUser => None,
+            WslStep::Unpack => Some("Unpacking WSL distribution..."),
+            WslStep::Initialize => Some("Initializing WSL distribution..."),
+            WslStep::UpdateCli => Some("Updating container CLI version..."),
+            WslStep::UpdateCerts => Some("Checking certificate updates..."),
+        }
+    }
+    /// The CLI and certificate updates run at the same time, so they print
+    /// plain lines instead of spinners that would overwrite each other
+    fn is_concurrent(self) -> bool {
+        matches!(self, WslStep::UpdateCli | WslStep::UpdateCerts)
+    }
+}
+
+/// Prints the messages of [`WslStep`]s and passes their JSON events to
+/// `emit`, if any.
+struct StepReporter<E: Fn(&str)> {
+    emit: Option<E>,
+}
+
+fn step_reporter() -> StepReporter<impl Fn(&str)> {
+    let json = print::messages_format() == print::MessagesFormat::Json;
+    StepReporter {
+        emit: json.then_some(|line: &str| msg!("{line}")),
+    }
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl<E: Fn(&str)> StepReporter<E> {
+    /// Starts `step`, showing its message as a [`print::Activity`]
+    fn start(&self, step: WslStep) -> Step<'_, E> {
+        let activity = step.message().map(|message| {
+            if step.is_concurrent() {
+                print::Activity::plain(message)
+            } else {
+                print::Activity::start(message)
+            }
+        });
+        self.event(step, StepStatus::Start);
+        Step {
+            reporter: self,
+            step,
+            activity,
+        }
+    }
+    fn event(&self, step: WslStep, status: StepStatus) {
+        if let Some(emit) = &self.emit {
+            let event = StepEvent { step, status };
+            emit(&serde_json::to_string(&event).expect("step event is serializable"));
+        }
+    }
+}
+
+/// A [`WslStep`] in progress. It is reported as failed if dropped without
+/// calling [`Step::done`].
+struct Step<'a, E: Fn(&str)> {
+    reporter: &'a StepReporter<E>,
+    step: WslStep,
+    activity: Option<print::Activity>,
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl<E: Fn(&str)> Step<'_, E> {
+    /// Hides the spinner while `f` shows its own progress
+    fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        match &self.activity {
+            Some(activity) => activity.suspend(f),
+            None => f(),
+        }
+    }
+    fn done(self) {
+        if let Some(activity) = self.activity {
+            activity.done();
+        }
+        self.reporter.event(self.step, StepStatus::Done);
+    }
+}
+
+#[cfg(windows)]
+#[context("cannot update CLI in WSL distribution")]
+fn update_wsl_cli(wsl: &wslapi::Library, distro: &str, download_dir: &Path) -> anyhow::Result<()> {
+    if let Some(bin_path) = Env::_wsl_linux_binary()? {
+        let bin_path = fs::canonicalize(bin_path)?;
+        wsl_simple_cmd(
+            wsl,
+            distro,
+            &format!(
+                "cp {} /usr/bin/edgedb && chmod 755 /usr/bin/edgedb",
+                shell_escape::unix::escape(path_to_linux(&bin_path)?.into()),
+            ),
+        )?;
+    } else {
+        let cache_path = download_dir.join("edgedb");
+        download_binary(&cache_path)?;
+        wsl_simple_cmd(
+            wsl,
+            distro,
+            &format!(
+                "mv {} /usr/bin/edgedb && chmod 755 /usr/bin/edgedb",
+                shell_escape::unix::escape(path_to_linux(&cache_path)?.into()),
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn update_wsl_certs(distro: &str) -> anyhow::Result<()> {
+    process::Native::new("update certificates", "apt", "wsl")
+        .arg("--distribution")
+        .arg(distro)
+        .arg("bash")
+        .arg("-c")
+        .arg(
+            "export DEBIAN_FRONTEND=noninteractive; \
+              apt-get update -qq && \
+              apt-get install -y ca-certificates -qq -o=Dpkg::Use-Pty=0 && \
+              apt-get clean -qq && \
+              rm -rf /var/lib/apt/lists/*",
+        )
+        .run_abortable()
+        .map_err(|e| {
+            if e.is::<interrupt::InterruptError>() {
+                // certs_timestamp is not saved, so the next run retries
+                e.context(
+                    "aborted certificate update; \
+                     certificates will be updated on the next run",
+                )
+            } else {
+                e
+            }
+        })
 }
 
 #[cfg(windows)]
 #[context("cannot initialize WSL2 (windows subsystem for linux)")]
-fn get_wsl_distro(install: bool) -> anyhow::Result<Wsl> {
+fn get_wsl_distro(
+    install: bool,
+    steps: &StepReporter<impl Fn(&str) + Sync>,
+) -> anyhow::Result<Wsl> {
     let wsl = wslapi::Library::new()?;
     let meta_path = config_dir()?.join("wsl.json");
     let mut distro = None;
+    let mut user = None;
     let mut update_cli = true;
     let mut certs_timestamp = None;
     if meta_path.exists() {
@@ -451,12 +759,14 @@ fn get_wsl_distro(install: bool) -> anyhow::Result<Wsl> {
                     return Ok(Wsl {
                         lib: wsl,
                         distribution: wsl_info.distribution,
+                        user: wsl_info.user,
                     });
                 }
                 if !update_certs {
                     certs_timestamp = Some(wsl_info.certs_timestamp);
                 }
                 distro = Some(wsl_info.distribution);
+                user = Some(wsl_info.user);
             }
             Ok(_) => {}
             Err(e) => {
@@ -465,6 +775,10 @@ fn get_wsl_distro(install: bool) -> anyhow::Result<Wsl> {
         }
     }
     let mut distro = distro.unwrap_or(CURRENT_DISTRO.to_string());
+    let user = match user {
+        Some(user) => user,
+        None => Env::_wsl_user()?.unwrap_or_else(default_wsl_user),
+    };
 
     let download_dir = cache_dir()?.join("downloads");
     fs::create_dir_all(&download_dir)?;
@@ -478,21 +792,27 @@ fn get_wsl_distro(install: bool) -> anyhow::Result<Wsl> {
 
         if let Some(use_distro) = Env::_wsl_distro()? {
             distro = use_distro;
+        } else if let Some(existing) = find_registered_distro(&wsl)? {
+            msg!("Using existing WSL distribution {existing:?}");
+            distro = existing;
         } else {
             let download_dir = cache_dir()?.join("downloads");
             fs::create_dir_all(&download_dir)?;
 
             let download_path = download_dir.join("debian.zip");
-            download(&download_path, &*DISTRO_URL, false)?;
-            msg!("Unpacking WSL distribution...");
+            let download = steps.start(WslStep::Download);
+            download_mirrors(&download_path, &DISTRO_URLS, false)?;
+            download.done();
+            let unpack = steps.start(WslStep::Unpack);
             let appx_path = download_dir.join("debian.appx");
-            unpack_appx(&download_path, &appx_path)?;
+            unpack.suspend(|| unpack_appx(&download_path, &appx_path))?;
             let root_path = download_dir.join("install.tar");
-            unpack_root(&appx_path, &root_path)?;
+            unpack.suspend(|| unpack_root(&appx_path, &root_path))?;
+            unpack.done();
 
             let distro_path = wsl_dir()?.join(CURRENT_DISTRO);
             fs::create_dir_all(&distro_path)?;
-            msg!("Initializing WSL distribution...");
+            let initialize = steps.start(WslStep::Initialize);
 
             let result = process::Native::new("wsl check", "wsl", "wsl")
                 .arg("--help")
@@ -518,71 +838,70 @@ fn get_wsl_distro(install: bool) -> anyhow::Result<Wsl> {
                 }
             }
 
-            process::Native::new("wsl import", "wsl", "wsl")
-                .arg("--import")
-                .arg(CURRENT_DISTRO)
-                .arg(&distro_path)
-                .arg(&root_path)
-                .arg("--version=2")
-                .run()?;
+            // the output of `wsl` is passed through
+            initialize.suspend(|| {
+                process::Native::new("wsl import", "wsl", "wsl")
+                    .arg("--import")
+                    .arg(CURRENT_DISTRO)
+                    .arg(&distro_path)
+                    .arg(&root_path)
+                    .arg("--version=2")
+                    .run()
+            })?;
 
             fs::remove_file(&download_path)?;
             fs::remove_file(&appx_path)?;
             fs::remove_file(&root_path)?;
+            initialize.done();
 
             distro = CURRENT_DISTRO.into();
         };
 
-        wsl_simple_cmd(&wsl, &distro, "useradd edgedb --uid 1000 --create-home")?;
+        let create_user = steps.start(WslStep::CreateUser);
+        create_wsl_user(&wsl, &distro, &user)?;
+        create_user.done();
     }
 
-    if update_cli {
-        msg!("Updating container CLI version...");
-        if let Some(bin_path) = Env::_wsl_linux_binary()? {
-            let bin_path = fs::canonicalize(bin_path)?;
-            wsl_simple_cmd(
-                &wsl,
-                &distro,
-                &format!(
-                    "cp {} /usr/bin/edgedb && chmod 755 /usr/bin/edgedb",
-                    shell_escape::unix::escape(path_to_linux(&bin_path)?.into()),
-                ),
-            )?;
-        } else {
-            let cache_path = download_dir.join("edgedb");
-            download_binary(&cache_path)?;
-            wsl_simple_cmd(
-                &wsl,
-                &distro,
-                &format!(
-                    "mv {} /usr/bin/edgedb && chmod 755 /usr/bin/edgedb",
-                    shell_escape::unix::escape(path_to_linux(&cache_path)?.into()),
-                ),
-            )?;
+    // The CLI download and the certificate update are independent and are
+    // the slowest steps of the first run, so they run in parallel. Both are
+    // always waited for, so a failure of one never leaves the other
+    // running in the background.
+    let certs_timestamp = std::thread::scope(|scope| {
+        let cli = update_cli.then(|| {
+            scope.spawn(|| -> anyhow::Result<()> {
+                let step = steps.start(WslStep::UpdateCli);
+                update_wsl_cli(&wsl, &distro, &download_dir)?;
+                step.done();
+                Ok(())
+            })
+        });
+        let certs = match certs_timestamp {
+            Some(ts) => Ok(ts),
+            None => {
+                let step = steps.start(WslStep::UpdateCerts);
+                update_wsl_certs(&distro).map(|()| {
+                    step.done();
+                    SystemTime::now()
+                })
+            }
         };
-    }
-
-    let certs_timestamp = if let Some(ts) = certs_timestamp {
-        ts
-    } else {
-        msg!("Checking certificate updates...");
-        process::Native::new("update certificates", "apt", "wsl")
-            .arg("--distribution")
-            .arg(&distro)
-            .arg("bash")
-            .arg("-c")
-            .arg(
-                "export DEBIAN_FRONTEND=noninteractive; \
-                  apt-get update -qq && \
-                  apt-get install -y ca-certificates -qq -o=Dpkg::Use-Pty=0 && \
-                  apt-get clean -qq && \
-                  rm -rf /var/lib/apt/lists/*",
-            )
-            .run()?;
-        SystemTime::now()
-    };
+        let cli = match cli {
+            Some(handle) => handle
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e)),
+            None => Ok(()),
+        };
+        match (cli, certs) {
+            (Ok(()), certs) => certs,
+            (Err(e), Ok(_)) => Err(e),
+            (Err(cli), Err(certs)) => {
+                log::warn!("Error updating certificates: {certs:#}");
+                Err(cli)
+            }
+        }
+    })?;
 
-    let cli_version = wsl_cli_version(&distro)?;
+    let cli_version = wsl_cli_version(&distro, &user)?;
     let my_ver = self_version()?;
     if cli_version < my_ver {
         return Err(bug::error(format!(
@@ -593,6 +912,7 @@ fn get_wsl_distro(install: bool) -> anyhow::Result<Wsl> {
     }
     let info = WslInfo {
         distribution: distro.into(),
+        user,
         last_checked_version: Some(my_ver),
         cli_timestamp: None,
         cli_version,
@@ -602,20 +922,24 @@ fn get_wsl_distro(install: bool) -> anyhow::Result<Wsl> {
     return Ok(Wsl {
         lib: wsl,
         distribution: info.distribution,
+        user: info.user,
     });
 }
 
 #[cfg(unix)]
-fn get_wsl_distro(_install: bool) -> anyhow::Result<Wsl> {
+fn get_wsl_distro(
+    _install: bool,
+    _steps: &StepReporter<impl Fn(&str) + Sync>,
+) -> anyhow::Result<Wsl> {
     Err(bug::error("WSL on unix is unupported"))
 }
 
 pub fn ensure_wsl() -> anyhow::Result<&'static Wsl> {
-    WSL.get_or_try_init(|| get_wsl_distro(true))
+    WSL.get_or_try_init(|| get_wsl_distro(true, &step_reporter()))
 }
 
 fn get_wsl() -> anyhow::Result<Option<&'static Wsl>> {
-    match WSL.get_or_try_init(|| get_wsl_distro(false)) {
+    match WSL.get_or_try_init(|| get_wsl_distro(false, &step_reporter())) {
         Ok(v) => Ok(Some(v)),
         Err(e) if e.is::<NoDistribution>() => Ok(None),
         Err(e) => Err(e),
@@ -623,7 +947,7 @@ fn get_wsl() -> anyhow::Result<Option<&'static Wsl>> {
 }
 
 pub fn try_get_wsl() -> anyhow::Result<&'static Wsl> {
-    match WSL.get_or_try_init(|| get_wsl_distro(false)) {
+    match WSL.get_or_try_init(|| get_wsl_distro(false, &step_reporter())) {
         Ok(v) => Ok(v),
         Err(e) if e.is::<NoDistribution>() => Err(e).hint(formatcp!(
             "WSL is initialized automatically on \
@@ -667,9 +991,9 @@ fn create_and_start(wsl: &Wsl, name: &str) -> anyhow::Result<()> {
         service_file(name)?,
         format!(
             "wsl \
-        --distribution {} --user edgedb \
+        --distribution {} --user {} \
         /usr/bin/edgedb instance start -I {}",
-            &wsl.distribution, &name
+            &wsl.distribution, &wsl.user, &name
         ),
     )?;
     Ok(())
@@ -690,7 +1014,7 @@ pub fn server_cmd(instance: &str, _is_shutdown_supported: bool) -> anyhow::Resul
     let instance = String::from(instance);
     pro.set_stop_process_command(move || {
         let mut cmd = tokio::process::Command::new("wsl");
-        cmd.arg("--user").arg("edgedb");
+        cmd.arg("--user").arg(&wsl.user);
         cmd.arg("--distribution").arg(&wsl.distribution);
         cmd.arg("_EDGEDB_FROM_WINDOWS=1");
         cmd.arg("/usr/bin/edgedb");
@@ -804,7 +1128,7 @@ pub fn reset_password(
             .arg("reset-password")
             .args(options)
             .run()?;
-        wsl.copy_out(credentials_linux(name), credentials::path(name)?)?;
+        wsl.copy_out_credentials(name)?;
     } else {
         anyhow::bail!(
             "WSL distribution is not installed, \
@@ -948,20 +1272,70 @@ fn list_local(options: &status::List) -> anyhow::Result<Vec<status::JsonStatus>>
         ..options.clone()
     };
     let local: Vec<status::JsonStatus> = if let Some(wsl) = get_wsl()? {
-        let text = wsl
+        let output = wsl
             .edgedb()
             .arg("instance")
             .arg("list")
             .args(&inner_opts)
-            .get_stdout_text()?;
+            .get_output()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
         log::info!("WSL list returned {:?}", text);
-        serde_json::from_str(&text).context("cannot decode json from `instance list` in WSL")?
+        if !stderr.trim().is_empty() {
+            log::info!("WSL list error output: {:?}", stderr);
+        }
+        let list = decode_wsl_list(&text, &stderr);
+        if !output.status.success() {
+            list.with_context(|| format!("`instance list` in WSL failed: {}", output.status))?
+        } else {
+            list?
+        }
     } else {
         Vec::new()
     };
     Ok(local)
 }
 
+/// Longest piece of inner CLI output quoted in error messages
+const WSL_OUTPUT_SNIPPET: usize = 200;
+
+fn output_snippet(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(WSL_OUTPUT_SNIPPET) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Decodes `instance list --json` output of the CLI in WSL. A crashed or
+/// mismatched inner CLI prints partial output or a backtrace instead, so
+/// the error quotes what was received along with its error output.
+fn decode_wsl_list(stdout: &str, stderr: &str) -> anyhow::Result<Vec<status::JsonStatus>> {
+    let problem = if !stdout.trim_start().starts_with(['[', '{']) {
+        format!(
+            "inner CLI did not produce JSON (got: {:?})",
+            output_snippet(stdout)
+        )
+    } else {
+        match serde_json::from_str(stdout) {
+            Ok(list) => return Ok(list),
+            Err(e) => format!(
+                "cannot decode json from `instance list` in WSL: {e} (got: {:?})",
+                output_snippet(stdout)
+            ),
+        }
+    };
+    let error = if stderr.trim().is_empty() {
+        anyhow::anyhow!(problem)
+    } else {
+        anyhow::anyhow!("{problem}\nError output:\n{}", output_snippet(stderr))
+    };
+    Err(error.hint(formatcp!(
+        "{BRANDING_CLI} in Windows and in {BRANDING_WSL} may be of different versions, \
+         run `{BRANDING_CLI_CMD} cli upgrade` and try again."
+    )))?
+}
+
 pub fn list(options: &status::List, opts: &crate::Options) -> anyhow::Result<()> {
     let errors = Collector::new();
     let local = match list_local(options) {
@@ -1037,7 +1411,7 @@ pub fn upgrade(options: &instance::upgrade::Command, name: &str) -> anyhow::Resu
         .args(options)
         .run()?;
     // credentials might be updated on upgrade if we change format somehow
-    wsl.copy_out(credentials_linux(name), credentials::path(name)?)?;
+    wsl.copy_out_credentials(name)?;
     Ok(())
 }
 
@@ -1049,16 +1423,45 @@ pub fn revert(options: &instance::revert::Command, name: &str) -> anyhow::Result
         .args(options)
         .run()?;
     // credentials might be updated on upgrade if we change format somehow
-    wsl.copy_out(credentials_linux(name), credentials::path(name)?)?;
+    wsl.copy_out_credentials(name)?;
     Ok(())
 }
 
+fn default_data_dir(home_dir: &str) -> String {
+    format!("{home_dir}/.local/share/edgedb/data")
+}
+
+/// Picks the data directory reported by `info --get data-dir` in WSL,
+/// falling back to the default location if it can't be used.
+fn resolve_data_dir(reported: anyhow::Result<String>, home_dir: &str) -> String {
+    match reported {
+        Ok(dir) if dir.trim().starts_with('/') => dir.trim().trim_end_matches('/').to_string(),
+        Ok(dir) => {
+            log::warn!("Unexpected data directory {dir:?} reported in WSL, using the default");
+            default_data_dir(home_dir)
+        }
+        Err(e) => {
+            log::warn!("Cannot get data directory in WSL: {e:#}, using the default");
+            default_data_dir(home_dir)
+        }
+    }
+}
+
+fn instance_data_dir(name: &str, data_dir: &str) -> String {
+    if name == "_localdev" {
+        // `_localdev` lives next to `data`, not inside of it
+        let base = data_dir.rsplit_once('/').map_or("", |(base, _)| base);
+        format!("{base}/_localdev/")
+    } else {
+        format!("{data_dir}/{name}/")
+    }
+}
+
 fn get_instance_data_dir(name: &str, wsl: &Wsl) -> anyhow::Result<PathBuf> {
     let data_dir = if name == "_localdev" {
-        Env::server_dev_dir()?
-            .unwrap_or_else(|| "/home/edgedb/.local/share/edgedb/_localdev/".into())
+        Env::server_dev_dir()?.unwrap_or_else(|| instance_data_dir(name, wsl.data_dir()).into())
     } else {
-        format!("/home/edgedb/.local/share/edgedb/data/{name}/").into()
+        instance_data_dir(name, wsl.data_dir()).into()
     };
 
     if !wsl.check_path_exist(&data_dir) {
@@ -1082,7 +1485,8 @@ pub fn read_jws_key(name: &str) -> anyhow::Result<String> {
 pub fn get_instance_info(name: &str) -> anyhow::Result<String> {
     let wsl = try_get_wsl()?;
     wsl.read_text_file(format!(
-        "/home/edgedb/.local/share/edgedb/data/{name}/instance_info.json"
+        "{}instance_info.json",
+        instance_data_dir(name, wsl.data_dir())
     ))
 }
 
@@ -1111,3 +1515,123 @@ pub fn extension_uninstall(cmd: &extension::ExtensionUninstall) -> anyhow::Resul
         .run()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::{
+        StepReporter, WslStep, decode_wsl_list, instance_data_dir, parse_wsl_distros,
+        resolve_data_dir,
+    };
+
+    #[test]
+    fn wsl_step_events() {
+        let events = RefCell::new(Vec::new());
+        let steps = StepReporter {
+            emit: Some(|line: &str| events.borrow_mut().push(line.to_string())),
+        };
+        for step in [
+            WslStep::Download,
+            WslStep::Unpack,
+            WslStep::Initialize,
+            WslStep::CreateUser,
+            WslStep::UpdateCli,
+        ] {
+            steps.start(step).done();
+        }
+        // a step that fails is never reported as done
+        drop(steps.start(WslStep::UpdateCerts));
+        assert_eq!(
+            events.into_inner(),
+            [
+                r#"{"step":"download","status":"start"}"#,
+                r#"{"step":"download","status":"done"}"#,
+                r#"{"step":"unpack","status":"start"}"#,
+                r#"{"step":"unpack","status":"done"}"#,
+                r#"{"step":"initialize","status":"start"}"#,
+                r#"{"step":"initialize","status":"done"}"#,
+                r#"{"step":"create_user","status":"start"}"#,
+                r#"{"step":"create_user","status":"done"}"#,
+                r#"{"step":"update_cli","status":"start"}"#,
+                r#"{"step":"update_cli","status":"done"}"#,
+                r#"{"step":"update_certs","status":"start"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn wsl_list_utf16() {
+        let utf16 = |text: &str| {
+            text.encode_utf16()
+                .flat_map(|c| c.to_le_bytes())
+                .collect::<Vec<u8>>()
+        };
+        assert_eq!(
+            parse_wsl_distros(&utf16("\u{feff}Ubuntu\r\nEdgeDB.WSL.1\r\n\r\n")),
+            ["Ubuntu", "EdgeDB.WSL.1"]
+        );
+        assert!(parse_wsl_distros(&utf16("\r\n")).is_empty());
+        assert!(parse_wsl_distros(b"").is_empty());
+    }
+
+    #[test]
+    fn decode_malformed_wsl_list() {
+        assert!(decode_wsl_list("[]", "").unwrap().is_empty());
+        assert!(
+            decode_wsl_list("  \n[]\n", "some warning")
+                .unwrap()
+                .is_empty()
+        );
+
+        let err = decode_wsl_list("[{\"name\": \"inst", "").unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.starts_with("cannot decode json"), "{msg}");
+        assert!(msg.contains("inst"), "{msg}");
+
+        let err = decode_wsl_list(
+            "thread 'main' panicked at src/main.rs",
+            "note: run with `RUST_BACKTRACE=1`",
+        )
+        .unwrap_err();
+        let msg = err.to_string();
+        assert!(
+            msg.starts_with("inner CLI did not produce JSON (got: \"thread 'main' panicked"),
+            "{msg}"
+        );
+        assert!(msg.contains("RUST_BACKTRACE"), "{msg}");
+
+        let err = decode_wsl_list("", "").unwrap_err();
+        assert!(err.to_string().contains("(got: \"\")"), "{err}");
+        let long = "x".repeat(1000);
+        let err = decode_wsl_list(&long, "").unwrap_err();
+        assert!(err.to_string().len() < 300, "{err}");
+    }
+
+    #[test]
+    fn wsl_data_dir_fallback() {
+        let home = "/home/edgedb";
+        let dir = resolve_data_dir(Ok("/data/xdg/edgedb/data\n".into()), home);
+        assert_eq!(dir, "/data/xdg/edgedb/data");
+        assert_eq!(
+            instance_data_dir("inst", &dir),
+            "/data/xdg/edgedb/data/inst/"
+        );
+        assert_eq!(
+            instance_data_dir("_localdev", &dir),
+            "/data/xdg/edgedb/_localdev/"
+        );
+
+        let default = "/home/edgedb/.local/share/edgedb/data";
+        assert_eq!(resolve_data_dir(Ok("".into()), home), default);
+        assert_eq!(resolve_data_dir(Ok("relative/dir".into()), home), default);
+        assert_eq!(
+            resolve_data_dir(Err(anyhow::anyhow!("no such command")), home),
+            default
+        );
+        assert_eq!(
+            instance_data_dir("_localdev", default),
+            "/home/edgedb/.local/share/edgedb/_localdev/"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+const TICK: Duration = Duration::from_millis(100);
+
+/// Feedback for a long operation that can't report its progress, such as
+/// unpacking or a command that takes a while.
+///
+/// On a terminal the message is shown with a spinner, otherwise it is
+/// printed as a single line, so logs don't fill up with control characters.
+/// Call [`Activity::done`] when the operation succeeds: an activity that is
+/// dropped without it, e.g. by returning an error with `?`, is reported as
+/// failed.
+pub struct Activity {
+    message: String,
+    display: Display,
+    finished: bool,
+}
+
+enum Display {
+    Spinner(ProgressBar),
+    Lines(Box<dyn FnMut(&str) + Send>),
+}
+
+impl Activity {
+    /// Shows `message` with a spinner if stderr is a terminal, as a line
+    /// otherwise
+    pub fn start(message: impl Into<String>) -> Activity {
+        if io::stderr().is_terminal() {
+            Activity::spinner(message.into())
+        } else {
+            Activity::plain(message)
+        }
+    }
+
+    /// Prints `message` as a line even on a terminal. For operations that
+    /// run concurrently, as their spinners would overwrite each other.
+    pub fn plain(message: impl Into<String>) -> Activity {
+        Activity::with_lines(message.into(), Box::new(|line| eprintln!("{line}")))
+    }
+
+    fn spinner(message: String) -> Activity {
+        let bar = ProgressBar::new_spinner().with_message(message.clone());
+        bar.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner} {msg}")
+                .expect("template is ok"),
+        );
+        bar.enable_steady_tick(TICK);
+        Activity {
+            message,
+            display: Display::Spinner(bar),
+            finished: false,
+        }
+    }
+
+    fn with_lines(message: String, mut write: Box<dyn FnMut(&str) + Send>) -> Activity {
+        write(&message);
+        Activity {
+            message,
+            display: Display::Lines(write),
+            finished: false,
+        }
+    }
+
+    /// Hides the spinner while `f` runs, e.g. to show a progress bar of a
+    /// part of the operation
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        match &self.display {
+            Display::Spinner(bar) => bar.suspend(f),
+            Display::Lines(_) => f(),
+        }
+    }
+
+    /// Reports the operation as finished successfully
+    pub fn done(mut self) {
+        self.finish("done");
+    }
+
+    fn finish(&mut self, status: &str) {
+        self.finished = true;
+        let line = format!("{} {status}", self.message);
+        match &mut self.display {
+            Display::Spinner(bar) => bar.abandon_with_message(line),
+            Display::Lines(write) => write(&line),
+        }
+    }
+}
+
+impl Drop for Activity {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finish("failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::Activity;
+
+    #[test]
+    fn plain_lines() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let activity = |message: &str| {
+            let lines = lines.clone();
+            Activity::with_lines(
+                message.into(),
+                Box::new(move |line| lines.lock().unwrap().push(line.to_string())),
+            )
+        };
+
+        let unpack = activity("Unpacking WSL distribution...");
+        assert_eq!(unpack.suspend(|| 42), 42);
+        unpack.done();
+        let failing = || -> anyhow::Result<()> {
+            let _init = activity("Initializing WSL distribution...");
+            anyhow::bail!("import failed");
+        };
+        failing().unwrap_err();
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(
+            *lines,
+            [
+                "Unpacking WSL distribution...",
+                "Unpacking WSL distribution... done",
+                "Initializing WSL distribution...",
+                "Initializing WSL distribution... failed",
+            ]
+        );
+        for line in lines.iter() {
+            assert!(!line.contains(['\x1b', '\r']), "{line:?}");
+        }
+    }
+}
@@ -95,7 +95,29 @@ impl<T, E> WrapErr<T, E> for std::result::Result<T, E> {
 impl<T: Output> Printer<T> {
     pub(in crate::print) fn flush_buf(&mut self) -> Result<T::Error> {
         debug_assert_eq!(self.committed, self.buffer.len());
-        self.stream.write(&self.buffer).map_err(Exception::Error)?;
+        let mut end = self.buffer.len();
+        if let Some(limit) = self.max_output_lines {
+            // keep whole lines up to the limit, drop everything after them
+            let remaining = limit.saturating_sub(self.lines_written);
+            let cut = match remaining {
+                0 => Some(0),
+                _ => self
+                    .buffer
+                    .match_indices('\n')
+                    .nth(remaining - 1)
+                    .map(|(idx, _)| idx + 1),
+            };
+            if let Some(cut) = cut.filter(|&cut| cut < end) {
+                end = cut;
+                self.truncated = true;
+            }
+            self.lines_written += self.buffer[..end].matches('\n').count();
+        }
+        if end > 0 {
+            self.stream
+                .write(&self.buffer[..end])
+                .map_err(Exception::Error)?;
+        }
         self.buffer.clear();
         self.committed = 0;
         Ok(())
@@ -209,6 +231,18 @@ impl<T: Output> Printer<T> {
         }
         Ok(())
     }
+    /// Marks `count` rows left out by `items_window`
+    pub(in crate::print) fn hidden_items(&mut self, count: usize) -> Result<T::Error> {
+        self.delimit()?;
+        self.write("...".unstyled())?;
+        if self.flow {
+            self.delim = Comma;
+            Ok(())
+        } else {
+            self.write(format!(" ({count} hidden)").muted())?;
+            self.commit_line()
+        }
+    }
     pub(in crate::print) fn field(&mut self) -> Result<T::Error> {
         self.delim = Field;
         self.write(": ".unstyled())
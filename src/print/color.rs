@@ -52,7 +52,8 @@ pub trait Highlight: colorful::Colorful + colorful::core::StrMarker + Sized {
 impl<T: colorful::Colorful + colorful::core::StrMarker + Sized> Highlight for T {}
 
 pub static TERMINAL_LUMA: once_cell::sync::Lazy<Option<f32>> = once_cell::sync::Lazy::new(|| {
-    if !concolor::get(concolor::Stream::Stdout).color() {
+    // only query a real terminal, `--color=always` may be writing to a pipe
+    if !concolor::get(concolor::Stream::Stdout).color() || !super::use_color() {
         return None;
     }
 
@@ -60,13 +61,44 @@ pub static TERMINAL_LUMA: once_cell::sync::Lazy<Option<f32>> = once_cell::sync::
 });
 
 static THEME: once_cell::sync::Lazy<Option<Theme>> = once_cell::sync::Lazy::new(|| {
-    if !concolor::get(concolor::Stream::Stdout).color() {
+    if !super::use_color() {
         return None;
     }
 
-    let is_term_light = TERMINAL_LUMA.map_or(false, |x| x > 0.6);
+    Some(if is_term_light() {
+        Theme::light()
+    } else {
+        Theme::dark()
+    })
+});
+
+fn is_term_light() -> bool {
+    TERMINAL_LUMA.map_or(false, |x| x > 0.6)
+}
+
+struct Theme {
+    muted: Color,
+    danger: Color,
+    success: Color,
+    warning: Color,
+
+    syntax_string: Color,
+    syntax_set: Color,
+    syntax_object: Color,
+    syntax_link_property: Color,
+    syntax_number: Color,
+    syntax_boolean: Color,
+    syntax_enum: Color,
+    syntax_uuid: Color,
+    syntax_keyword: Color,
+    syntax_operator: Color,
+    syntax_comment: Color,
+    syntax_cast: Color,
+    syntax_backslash: Color,
+}
 
-    Some(if is_term_light {
+impl Theme {
+    fn light() -> Theme {
         Theme {
             muted: Color::Grey63,
             danger: Color::DarkRed1,
@@ -87,7 +119,9 @@ static THEME: once_cell::sync::Lazy<Option<Theme>> = once_cell::sync::Lazy::new(
             syntax_cast: Color::DarkRed2,
             syntax_backslash: Color::DarkRed1,
         }
-    } else {
+    }
+
+    fn dark() -> Theme {
         Theme {
             muted: Color::Grey37,
             danger: Color::LightRed,
@@ -108,32 +142,15 @@ static THEME: once_cell::sync::Lazy<Option<Theme>> = once_cell::sync::Lazy::new(
             syntax_cast: Color::IndianRed1b,
             syntax_backslash: Color::IndianRed1c,
         }
-    })
-});
-
-struct Theme {
-    muted: Color,
-    danger: Color,
-    success: Color,
-    warning: Color,
-
-    syntax_string: Color,
-    syntax_set: Color,
-    syntax_object: Color,
-    syntax_link_property: Color,
-    syntax_number: Color,
-    syntax_boolean: Color,
-    syntax_enum: Color,
-    syntax_uuid: Color,
-    syntax_keyword: Color,
-    syntax_operator: Color,
-    syntax_comment: Color,
-    syntax_cast: Color,
-    syntax_backslash: Color,
+    }
 }
 
 pub(super) fn apply_syntax_style(style: Style, data: &str) -> CString {
-    if let Some(theme) = super::color::THEME.as_ref() {
+    syntax_style(THEME.as_ref(), style, data)
+}
+
+fn syntax_style(theme: Option<&Theme>, style: Style, data: &str) -> CString {
+    if let Some(theme) = theme {
         match style {
             Style::Comment => data.color(theme.syntax_comment),
             Style::String => data.color(theme.syntax_string),
@@ -163,9 +180,141 @@ pub(super) fn apply_syntax_style(style: Style, data: &str) -> CString {
     }
 }
 
+/// Prints how colors were detected and a sample of every output style
+/// (`cli color-test`). Samples are only colored if colors are enabled.
+pub fn print_color_test() {
+    let enabled = super::use_color();
+    let stdout = concolor::get(concolor::Stream::Stdout);
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    println!("Colors: {}", if enabled { "enabled" } else { "disabled" });
+    println!("  --color: {:?}", super::color_choice());
+    println!(
+        "  stdout is a terminal: {}",
+        yes_no(std::io::IsTerminal::is_terminal(&std::io::stdout()))
+    );
+    println!("  ANSI colors supported: {}", yes_no(stdout.ansi_color()));
+    for var in [
+        "NO_COLOR",
+        "CLICOLOR",
+        "CLICOLOR_FORCE",
+        "TERM",
+        "COLORTERM",
+    ] {
+        match std::env::var_os(var) {
+            Some(value) => println!("  {var}={value:?}"),
+            None => println!("  {var} is not set"),
+        }
+    }
+    if !enabled {
+        println!();
+        print!("{}", style_samples("Styles", None));
+        return;
+    }
+    match *TERMINAL_LUMA {
+        Some(luma) => println!("Terminal luma: {luma:.2}"),
+        None => println!("Terminal luma: unknown"),
+    }
+    let (active, other, other_theme) = if is_term_light() {
+        ("light", "dark", Theme::dark())
+    } else {
+        ("dark", "light", Theme::light())
+    };
+    println!();
+    print!(
+        "{}",
+        style_samples(&format!("Styles, {active} theme (active)"), THEME.as_ref())
+    );
+    println!();
+    print!(
+        "{}",
+        style_samples(&format!("Styles, {other} theme"), Some(&other_theme))
+    );
+}
+
+fn style_samples(title: &str, theme: Option<&Theme>) -> String {
+    let mut out = format!("{title}:\n");
+    for &style in Style::ALL {
+        let name = format!("{style:?}");
+        out.push_str(&format!(
+            "  {name:<16} {}\n",
+            syntax_style(theme, style, "sample text")
+        ));
+    }
+    out
+}
+
+/// Line diff of `old` and `new`: unchanged lines are prefixed with a
+/// space, added ones with `+` in green and removed ones with `-` in red.
+/// Colors are only used if enabled, see [`super::use_color`].
+pub fn diff(old: &str, new: &str) -> String {
+    render_diff(THEME.as_ref(), old, new)
+}
+
+fn render_diff(theme: Option<&Theme>, old: &str, new: &str) -> String {
+    use similar::{ChangeTag, TextDiff};
+
+    let mut out = String::new();
+    for change in TextDiff::from_lines(old, new).iter_all_changes() {
+        let (sign, color) = match change.tag() {
+            ChangeTag::Equal => (' ', None),
+            ChangeTag::Insert => ('+', theme.map(|t| t.success)),
+            ChangeTag::Delete => ('-', theme.map(|t| t.danger)),
+        };
+        let line = format!("{sign}{}", change.value().trim_end_matches('\n'));
+        match color {
+            Some(color) => out.push_str(&line.color(color).to_string()),
+            None => out.push_str(&line),
+        }
+        out.push('\n');
+    }
+    out
+}
+
 #[macro_export]
 macro_rules! msg {
     ($($tt:tt)*) => {
         eprintln!($($tt)*);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use colorful::Colorful;
+
+    use super::{Style, Theme, render_diff, style_samples};
+
+    #[test]
+    fn color_test_samples() {
+        let plain = style_samples("Styles", None);
+        assert!(!plain.contains('\x1b'), "{plain:?}");
+        assert_eq!(plain.lines().count(), Style::ALL.len() + 1);
+        assert!(
+            plain.contains("  BackslashCommand sample text\n"),
+            "{plain}"
+        );
+
+        let colored = style_samples("Styles", Some(&Theme::dark()));
+        assert!(colored.contains('\x1b'), "{colored:?}");
+    }
+
+    #[test]
+    fn diff_lines() {
+        let old = "type A;\ntype B;\ntype C;\n";
+        let new = "type A;\ntype C;\ntype D;\n";
+        assert_eq!(
+            render_diff(None, old, new),
+            " type A;\n-type B;\n type C;\n+type D;\n"
+        );
+        // the last line doesn't need a newline
+        assert_eq!(render_diff(None, "a", "b"), "-a\n+b\n");
+
+        let theme = Theme::dark();
+        let colored = render_diff(Some(&theme), old, new);
+        let lines: Vec<_> = colored.lines().collect();
+        assert_eq!(lines[0], " type A;");
+        assert_eq!(lines[2], " type C;");
+        assert_eq!(lines[1], "-type B;".color(theme.danger).to_string());
+        assert_eq!(lines[3], "+type D;".color(theme.success).to_string());
+        assert_ne!(lines[1], "-type B;");
+    }
+}
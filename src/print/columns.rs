@@ -0,0 +1,174 @@
+use terminal_size::{Width, terminal_size};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::print::style::{Style, Styler};
+
+/// Columns are never shrunk below this width when fitting the terminal
+const MIN_COLUMN_WIDTH: usize = 3;
+
+/// Characters a table is drawn with
+struct BoxChars {
+    horizontal: &'static str,
+    vertical: char,
+    // left, middle and right corners of each separator line
+    top: [char; 3],
+    middle: [char; 3],
+    bottom: [char; 3],
+    ellipsis: &'static str,
+}
+
+const UNICODE_BOX: BoxChars = BoxChars {
+    horizontal: "─",
+    vertical: '│',
+    top: ['┌', '┬', '┐'],
+    middle: ['├', '┼', '┤'],
+    bottom: ['└', '┴', '┘'],
+    ellipsis: "…",
+};
+
+const ASCII_BOX: BoxChars = BoxChars {
+    horizontal: "-",
+    vertical: '|',
+    top: ['+', '+', '+'],
+    middle: ['+', '+', '+'],
+    bottom: ['+', '+', '+'],
+    ellipsis: "...",
+};
+
+/// A table with explicit headers and single-line string cells, for the
+/// CLI's own listings. Query results use [`table_to_stdout`] instead.
+///
+/// [`table_to_stdout`]: crate::print::table_to_stdout
+pub struct ColumnTable<'a> {
+    headers: &'a [&'a str],
+    rows: &'a [Vec<String>],
+    styler: Styler,
+}
+
+/// Starts a table with the given headers and rows. Rows shorter than
+/// `headers` are padded with empty cells.
+pub fn table<'a>(headers: &'a [&'a str], rows: &'a [Vec<String>]) -> ColumnTable<'a> {
+    ColumnTable {
+        headers,
+        rows,
+        styler: Styler::new(),
+    }
+}
+
+impl ColumnTable<'_> {
+    /// Renders for stdout: fits the terminal width, uses colors if enabled
+    /// and draws with ASCII characters when the terminal can't show
+    /// box-drawing characters
+    pub fn render(&self) -> String {
+        let max_width = terminal_size().map(|(Width(w), _h)| w.into()).unwrap_or(80);
+        self.render_with(max_width, super::use_color(), !super::use_utf8())
+    }
+    pub(in crate::print) fn render_with(
+        &self,
+        max_width: usize,
+        colors: bool,
+        ascii_only: bool,
+    ) -> String {
+        let chars = if ascii_only { &ASCII_BOX } else { &UNICODE_BOX };
+        let widths = column_widths(self.headers, self.rows, max_width);
+
+        let mut out = String::new();
+        out.push_str(&separator(chars, chars.top, &widths));
+        out.push(chars.vertical);
+        for (title, &width) in self.headers.iter().zip(&widths) {
+            let cell = pad_cell(title, width, chars.ellipsis);
+            out.push(' ');
+            if colors {
+                out.push_str(&self.styler.apply(Style::Comment, &cell).to_string());
+            } else {
+                out.push_str(&cell);
+            }
+            out.push(' ');
+            out.push(chars.vertical);
+        }
+        out.push('\n');
+        out.push_str(&separator(chars, chars.middle, &widths));
+        for row in self.rows {
+            out.push(chars.vertical);
+            for (idx, &width) in widths.iter().enumerate() {
+                let value = row.get(idx).map(|s| s.as_str()).unwrap_or("");
+                out.push(' ');
+                out.push_str(&pad_cell(value, width, chars.ellipsis));
+                out.push(' ');
+                out.push(chars.vertical);
+            }
+            out.push('\n');
+        }
+        out.push_str(&separator(chars, chars.bottom, &widths));
+        out
+    }
+    pub fn print(&self) {
+        print!("{}", self.render());
+    }
+}
+
+/// Natural width of every column, shrinking the widest ones until the
+/// whole table fits into `max_width` or all columns reach the minimum.
+pub(in crate::print) fn column_widths(
+    headers: &[&str],
+    rows: &[Vec<String>],
+    max_width: usize,
+) -> Vec<usize> {
+    let mut widths = headers.iter().map(|h| h.width()).collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    // "│ " before each column, " │" after the last one and between them
+    let budget = max_width.saturating_sub(3 * widths.len() + 1);
+    while widths.iter().sum::<usize>() > budget {
+        let Some(widest) = widths.iter_mut().max() else {
+            break;
+        };
+        if *widest <= MIN_COLUMN_WIDTH {
+            break;
+        }
+        *widest -= 1;
+    }
+    widths
+}
+
+fn separator(chars: &BoxChars, [left, middle, right]: [char; 3], widths: &[usize]) -> String {
+    let mut line = String::new();
+    line.push(left);
+    for (idx, width) in widths.iter().enumerate() {
+        if idx > 0 {
+            line.push(middle);
+        }
+        line.push_str(&chars.horizontal.repeat(width + 2));
+    }
+    line.push(right);
+    line.push('\n');
+    line
+}
+
+fn pad_cell(value: &str, width: usize, ellipsis: &str) -> String {
+    let value = truncate_cell(value, width, ellipsis);
+    let padding = " ".repeat(width.saturating_sub(value.width()));
+    value + &padding
+}
+
+fn truncate_cell(value: &str, width: usize, ellipsis: &str) -> String {
+    if value.width() <= width {
+        return value.to_string();
+    }
+    let mut result = String::new();
+    let mut used = 0;
+    for c in value.chars() {
+        let w = c.width().unwrap_or(0);
+        // leave room for the ellipsis
+        if used + w + ellipsis.width() > width {
+            break;
+        }
+        result.push(c);
+        used += w;
+    }
+    result.push_str(ellipsis);
+    result
+}
@@ -0,0 +1,215 @@
+//! Best-effort rendering of query results as EdgeQL statements
+//! (`--output-format=edgeql`).
+//!
+//! Every object row becomes an `INSERT Type { prop := value, ... };`
+//! statement, anything else a `SELECT <literal>;`. This is scaffolding for
+//! moving small amounts of data around, not a replacement for `dump`:
+//!
+//! * `id` and other implicit fields are not emitted, so inserted objects
+//!   get new ids;
+//! * links are emitted by id reference, as
+//!   `(SELECT Target FILTER .id = <uuid>'...')`, which only resolves if the
+//!   target object was inserted with the same id or already exists;
+//! * link properties are dropped;
+//! * enum values are emitted as plain strings and need a cast to the enum
+//!   type before the statement is accepted.
+//!
+//! Object type names are only known when the server sends them, so the
+//! query is always compiled with implicit type names in this mode.
+
+use bigdecimal::BigDecimal;
+use edgeql_parser::helpers::{quote_name, quote_string};
+use num_bigint::BigInt;
+
+use gel_protocol::value::Value;
+
+pub fn format_row(v: &Value) -> Result<String, anyhow::Error> {
+    match v {
+        Value::Object { .. } => insert_statement(v),
+        _ => Ok(format!("SELECT {};", literal(v)?)),
+    }
+}
+
+pub fn insert_statement(v: &Value) -> Result<String, anyhow::Error> {
+    let Value::Object { shape, fields } = v else {
+        anyhow::bail!("only objects can be written as INSERT statements");
+    };
+    let type_name = object_type_name(v).ok_or_else(|| {
+        anyhow::anyhow!("the server did not send the object type name, cannot write INSERT")
+    })?;
+    let mut props = Vec::new();
+    for (fld, value) in shape.elements.iter().zip(fields) {
+        if fld.flag_implicit || fld.flag_link_property || fld.name == "id" {
+            continue;
+        }
+        let value = match value {
+            None => continue,
+            Some(Value::Set(items)) if items.is_empty() => continue,
+            Some(value) if fld.flag_link => link_reference(value)?,
+            Some(value) => literal(value)?,
+        };
+        props.push(format!("{} := {}", quote_name(&fld.name), value));
+    }
+    if props.is_empty() {
+        Ok(format!("INSERT {type_name};"))
+    } else {
+        Ok(format!("INSERT {type_name} {{ {} }};", props.join(", ")))
+    }
+}
+
+fn object_type_name(v: &Value) -> Option<&str> {
+    let Value::Object { shape, fields } = v else {
+        return None;
+    };
+    shape
+        .elements
+        .iter()
+        .zip(fields)
+        .find(|(f, _)| f.name == "__tname__")
+        .and_then(|(_, v)| match v {
+            Some(Value::Str(type_name)) => Some(type_name.as_str()),
+            _ => None,
+        })
+}
+
+fn link_reference(v: &Value) -> Result<String, anyhow::Error> {
+    match v {
+        Value::Set(items) if items.len() == 1 => link_reference(&items[0]),
+        Value::Set(items) => Ok(format!(
+            "{{{}}}",
+            items
+                .iter()
+                .map(link_reference)
+                .collect::<Result<Vec<_>, _>>()?
+                .join(", ")
+        )),
+        Value::Object { shape, fields } => {
+            let type_name = object_type_name(v).ok_or_else(|| {
+                anyhow::anyhow!("the server did not send the link target type name")
+            })?;
+            let id = shape
+                .elements
+                .iter()
+                .zip(fields)
+                .find_map(|(f, v)| match v {
+                    Some(Value::Uuid(id)) if f.name == "id" => Some(id),
+                    _ => None,
+                })
+                .ok_or_else(|| anyhow::anyhow!("linked {type_name} object has no id"))?;
+            Ok(format!("(SELECT {type_name} FILTER .id = <uuid>'{id}')"))
+        }
+        _ => Err(anyhow::anyhow!(
+            "link value {v:?} cannot be written as an id reference"
+        )),
+    }
+}
+
+fn typed(type_name: &str, text: &str) -> String {
+    format!("<{type_name}>{}", quote_string(text))
+}
+
+fn float_literal(type_name: &str, text: String, finite: bool) -> String {
+    if finite {
+        format!("<{type_name}>{text}")
+    } else {
+        // `NaN` and `inf` have no literal syntax, but cast from strings
+        typed(type_name, &text)
+    }
+}
+
+/// Renders a scalar or collection value as an EdgeQL expression that
+/// evaluates to the same value and type.
+pub fn literal(v: &Value) -> Result<String, anyhow::Error> {
+    use Value as V;
+    let text = match v {
+        V::Nothing => "{}".into(),
+        V::Uuid(u) => format!("<uuid>'{u}'"),
+        V::Str(s) => quote_string(s),
+        V::Bytes(b) => {
+            let mut text = String::with_capacity(b.len() * 4 + 3);
+            text.push_str("b'");
+            for byte in b.iter() {
+                text.push_str(&format!("\\x{byte:02x}"));
+            }
+            text.push('\'');
+            text
+        }
+        V::Int16(v) => format!("<int16>{v}"),
+        V::Int32(v) => format!("<int32>{v}"),
+        V::Int64(v) => v.to_string(),
+        V::Float32(v) => float_literal("float32", format!("{v:?}"), v.is_finite()),
+        V::Float64(v) => float_literal("float64", format!("{v:?}"), v.is_finite()),
+        V::BigInt(v) => format!("{}n", BigInt::from(v)),
+        V::Decimal(v) => typed("decimal", &BigDecimal::from(v).to_string()),
+        V::Bool(v) => v.to_string(),
+        V::ConfigMemory(t) => typed("cfg::memory", &t.to_string()),
+        V::Datetime(t) => typed("datetime", &format!("{t:?}")),
+        V::LocalDatetime(t) => typed("cal::local_datetime", &format!("{t:?}")),
+        V::LocalDate(d) => typed("cal::local_date", &format!("{d:?}")),
+        V::LocalTime(t) => typed("cal::local_time", &format!("{t:?}")),
+        V::Duration(d) => typed("duration", &d.to_string()),
+        V::RelativeDuration(d) => typed("cal::relative_duration", &d.to_string()),
+        V::DateDuration(d) => typed("cal::date_duration", &d.to_string()),
+        V::Json(d) => format!("to_json({})", quote_string(d)),
+        V::Enum(v) => quote_string(v),
+        V::Set(items) => format!("{{{}}}", literals(items)?),
+        V::Array(items) => format!("[{}]", literals(items)?),
+        V::Vector(items) => format!(
+            "<ext::pgvector::vector>[{}]",
+            items
+                .iter()
+                .map(|v| format!("{v:?}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        V::Tuple(items) if items.len() == 1 => format!("({},)", literal(&items[0])?),
+        V::Tuple(items) => format!("({})", literals(items)?),
+        V::NamedTuple { shape, fields } => format!(
+            "({})",
+            shape
+                .elements
+                .iter()
+                .zip(fields)
+                .map(|(fld, value)| Ok(format!("{} := {}", quote_name(&fld.name), literal(value)?)))
+                .collect::<Result<Vec<_>, anyhow::Error>>()?
+                .join(", ")
+        ),
+        V::Range(rng) if !rng.is_empty() => {
+            let bound = |b: Option<&Value>| match b {
+                Some(b) => literal(b),
+                None => Ok("{}".into()),
+            };
+            let lower = rng.lower().map(|x| &**x);
+            let upper = rng.upper().map(|x| &**x);
+            if lower.is_none() && upper.is_none() {
+                anyhow::bail!("unbounded ranges cannot be written as EdgeQL literals");
+            }
+            format!(
+                "range({}, {}, inc_lower := {}, inc_upper := {})",
+                bound(lower)?,
+                bound(upper)?,
+                rng.inc_lower(),
+                rng.inc_upper(),
+            )
+        }
+        V::Range(_)
+        | V::Object { .. }
+        | V::SparseObject(..)
+        | V::SQLRow { .. }
+        | V::PostGisGeometry(..)
+        | V::PostGisGeography(..)
+        | V::PostGisBox2d(..)
+        | V::PostGisBox3d(..) => {
+            anyhow::bail!("{v:?} cannot be written as an EdgeQL literal");
+        }
+    };
+    Ok(text)
+}
+
+fn literals(items: &[Value]) -> Result<String, anyhow::Error> {
+    Ok(items
+        .iter()
+        .map(literal)
+        .collect::<Result<Vec<_>, _>>()?
+        .join(", "))
+}
@@ -1,7 +1,10 @@
-use crate::print::Printer;
+use std::cmp::min;
+
+use crate::print::native::{format_bytes, format_bytes_base64, format_bytes_hex, hexdump_lines};
 use crate::print::stream::Output;
+use crate::print::{BytesFormat, Printer};
 
-use crate::print::buffer::{Exception, Result};
+use crate::print::buffer::{Delim, Exception, Result};
 
 use crate::print::style::Style;
 use crate::repl::VectorLimit;
@@ -12,10 +15,13 @@ pub trait Formatter {
     type Error;
     fn const_number<T: ToString>(&mut self, s: T) -> Result<Self::Error>;
     fn const_string<T: ToString>(&mut self, s: T) -> Result<Self::Error>;
+    fn const_bytes(&mut self, b: &[u8]) -> Result<Self::Error>;
     fn const_uuid<T: ToString>(&mut self, s: T) -> Result<Self::Error>;
     fn const_bool<T: ToString>(&mut self, s: T) -> Result<Self::Error>;
     fn const_enum<T: ToString>(&mut self, s: T) -> Result<Self::Error>;
     fn nil(&mut self) -> Result<Self::Error>;
+    fn empty_set(&mut self) -> Result<Self::Error>;
+    fn json_null(&mut self) -> Result<Self::Error>;
     fn typed<S: ToString>(&mut self, typ: &str, s: S) -> Result<Self::Error>;
     #[allow(dead_code)]
     fn error<S: ToString>(&mut self, typ: &str, s: S) -> Result<Self::Error>;
@@ -32,12 +38,18 @@ pub trait Formatter {
         &mut self,
         iter: impl IntoIterator<Item = &'x f32> + Copy,
     ) -> Result<Self::Error>;
+    /// Vector with only the first `head` and last `tail` components shown,
+    /// followed by the number of components
+    fn head_tail_vector(&mut self, items: &[f32], head: usize, tail: usize) -> Result<Self::Error>;
     fn object<F>(&mut self, type_id: Option<&str>, f: F) -> Result<Self::Error>
     where
         F: FnMut(&mut Self) -> Result<Self::Error>;
     fn json_object<F>(&mut self, f: F) -> Result<Self::Error>
     where
         F: FnMut(&mut Self) -> Result<Self::Error>;
+    fn json<F>(&mut self, f: F) -> Result<Self::Error>
+    where
+        F: FnOnce(&mut Self) -> Result<Self::Error>;
     fn named_tuple<F>(&mut self, f: F) -> Result<Self::Error>
     where
         F: FnMut(&mut Self) -> Result<Self::Error>;
@@ -50,7 +62,11 @@ pub trait Formatter {
     fn tuple_field(&mut self, f: &str) -> Result<Self::Error>;
 
     fn implicit_properties(&self) -> bool;
+    fn link_properties(&self) -> bool;
     fn expand_strings(&self) -> bool;
+    fn expand_json(&self) -> bool;
+    fn max_string_length(&self) -> Option<usize>;
+    fn ascii_only(&self) -> bool;
     fn max_items(&self) -> Option<usize>;
     fn max_vector_length(&self) -> VectorLimit;
 }
@@ -68,6 +84,30 @@ where
         self.delimit()?;
         self.write(self.styler.apply(Style::String, &s.to_string()))
     }
+    fn const_bytes(&mut self, b: &[u8]) -> Result<Self::Error> {
+        self.delimit()?;
+        match self.bytes_format {
+            BytesFormat::Escaped => self.write(self.styler.apply(Style::String, &format_bytes(b))),
+            BytesFormat::Base64 => {
+                self.write(self.styler.apply(Style::String, &format_bytes_base64(b)))
+            }
+            BytesFormat::Hex => self.write(self.styler.apply(Style::String, &format_bytes_hex(b))),
+            BytesFormat::HexDump => {
+                // lines are indented one level deeper than the current value
+                let indent = self.cur_indent + self.indent;
+                let width = self.max_width.saturating_sub(indent);
+                self.write(self.styler.apply(Style::Cast, "hexdump("))?;
+                for line in hexdump_lines(b, width) {
+                    self.write("\n".unstyled())?;
+                    self.write(" ".repeat(indent).unstyled())?;
+                    self.write(self.styler.apply(Style::String, &line))?;
+                }
+                self.write("\n".unstyled())?;
+                self.write(" ".repeat(self.cur_indent).unstyled())?;
+                self.write(self.styler.apply(Style::Cast, ")"))
+            }
+        }
+    }
     fn const_uuid<S: ToString>(&mut self, s: S) -> Result<Self::Error> {
         self.delimit()?;
         self.write(self.styler.apply(Style::UUID, &s.to_string()))
@@ -82,7 +122,22 @@ where
     }
     fn nil(&mut self) -> Result<Self::Error> {
         self.delimit()?;
-        self.write(self.styler.apply(Style::Set, "{}"))
+        let text = self
+            .styler
+            .apply(Style::Set, self.null_text.as_deref().unwrap_or("{}"));
+        self.write(text)
+    }
+    fn empty_set(&mut self) -> Result<Self::Error> {
+        self.delimit()?;
+        let text = self
+            .styler
+            .apply(Style::Set, self.empty_set_text.as_deref().unwrap_or("{}"));
+        self.write(text)
+    }
+    fn json_null(&mut self) -> Result<Self::Error> {
+        self.delimit()?;
+        // `null_text` would make the output invalid JSON
+        self.write(self.styler.apply(Style::Boolean, "null"))
     }
     fn typed<S: ToString>(&mut self, typ: &str, s: S) -> Result<Self::Error> {
         self.delimit()?;
@@ -162,6 +217,16 @@ where
         )?;
         Ok(())
     }
+    fn json<F>(&mut self, f: F) -> Result<Self::Error>
+    where
+        F: FnOnce(&mut Self) -> Result<Self::Error>,
+    {
+        self.delimit()?;
+        self.write(self.styler.apply(Style::Cast, "<json>"))?;
+        // the value follows the cast on the same line, like a field value
+        self.delim = Delim::Field;
+        f(self)
+    }
     fn object_field(&mut self, f: &str, linkprop: bool) -> Result<Self::Error> {
         self.delimit()?;
         if linkprop {
@@ -303,14 +368,49 @@ where
         Ok(())
     }
 
+    fn head_tail_vector(&mut self, items: &[f32], head: usize, tail: usize) -> Result<Self::Error> {
+        self.array(Some("ext::pgvector::vector"), |prn| {
+            let hidden = items.len().saturating_sub(head.saturating_add(tail));
+            for item in &items[..min(head, items.len())] {
+                prn.const_number(item)?;
+                prn.comma()?;
+            }
+            if hidden > 0 {
+                prn.hidden_items(hidden)?;
+            }
+            for item in &items[min(head, items.len()) + hidden..] {
+                prn.const_number(item)?;
+                prn.comma()?;
+            }
+            Ok(())
+        })?;
+        self.write(format!(" ({} dims)", items.len()).muted())
+    }
+
     fn implicit_properties(&self) -> bool {
         self.implicit_properties
     }
 
+    fn link_properties(&self) -> bool {
+        self.link_properties
+    }
+
     fn expand_strings(&self) -> bool {
         self.expand_strings
     }
 
+    fn expand_json(&self) -> bool {
+        self.expand_json
+    }
+
+    fn max_string_length(&self) -> Option<usize> {
+        self.max_string_length
+    }
+
+    fn ascii_only(&self) -> bool {
+        self.ascii_only
+    }
+
     fn max_items(&self) -> Option<usize> {
         self.max_items
     }
@@ -1,15 +1,22 @@
 use serde_json::Value;
 
 use crate::print::buffer::Result;
+use crate::print::native::{truncate_string, truncation_marker};
 use crate::print::{FormatExt, Formatter};
 
 impl FormatExt for Value {
     fn format<F: Formatter>(&self, prn: &mut F) -> Result<F::Error> {
         use Value as V;
         match self {
-            V::Null => prn.const_bool("null"),
+            V::Null => prn.json_null(),
             V::Bool(v) => prn.const_bool(v),
-            s @ V::String(_) => prn.const_string(s),
+            V::String(s) => match prn.max_string_length().map(|lim| truncate_string(s, lim)) {
+                Some((kept, dropped)) if dropped > 0 => {
+                    let marker = truncation_marker(dropped, prn.ascii_only());
+                    prn.const_string(V::String(format!("{kept}{marker}")))
+                }
+                _ => prn.const_string(self),
+            },
             s @ V::Number(_) => prn.const_number(s),
             V::Array(items) => prn.array(None, |prn| {
                 for item in items {
@@ -1,8 +1,17 @@
+//! Rendering of query results for the terminal and for text formats.
+
+mod activity;
 mod buffer;
 mod color;
+mod columns;
+pub mod edgeql;
 mod formatter;
 mod json;
+pub mod msgpack;
 mod native;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod settings;
 mod stream;
 pub mod style;
 #[cfg(test)]
@@ -11,30 +20,40 @@ mod tests;
 pub use crate::error_display::print_query_warning as warning;
 pub use crate::error_display::print_query_warnings as warnings;
 pub use crate::msg;
+pub use activity::Activity;
+pub use buffer::Exception;
 pub use color::Highlight;
 pub use color::TERMINAL_LUMA;
-
+pub use color::diff;
+pub use color::print_color_test;
+pub use columns::table;
+pub use formatter::Formatter;
+pub use native::FormatExt;
+pub use stream::Output;
+
+use std::collections::VecDeque;
 use std::convert::Infallible;
 use std::error::Error;
 use std::fmt;
-use std::io;
-use std::sync::OnceLock;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Once, OnceLock};
 
 use const_format::concatcp;
-use is_terminal::IsTerminal;
 use snafu::{AsErrorSource, ResultExt, Snafu};
-use terminal_size::{Width, terminal_size};
+use terminal_size::{Height, Width, terminal_size};
 use tokio_stream::{Stream, StreamExt};
+use unicode_width::UnicodeWidthStr;
 
 use gel_errors::display::display_error;
 
 use crate::branding::BRANDING_CLI_CMD;
-use crate::repl::VectorLimit;
+use crate::cli::env::Env;
+use crate::repl::{OutputFormat, VectorLimit};
 
-use buffer::{Delim, Exception, UnwrapExc, UnwrapInfallible, WrapErr};
-use formatter::Formatter;
-use native::FormatExt;
-use stream::Output;
+use buffer::{Delim, UnwrapExc, UnwrapInfallible, WrapErr};
 
 use crate::table::{self, Cell, Row, Table};
 use gel_protocol::value::Value;
@@ -53,23 +72,115 @@ pub struct Config {
     pub colors: Option<bool>,
     pub indent: usize,
     pub expand_strings: bool,
+    pub max_string_length: Option<usize>,
+    pub truncate_json_strings: bool,
     pub max_width: Option<usize>,
     pub implicit_properties: bool,
+    pub link_properties: bool,
     pub max_items: Option<usize>,
+    pub items_window: Option<(usize, usize)>,
     pub max_vector_length: VectorLimit,
+    pub bytes_format: BytesFormat,
+    pub expand_json: bool,
+    pub null_text: Option<String>,
+    pub empty_set_text: Option<String>,
+    pub max_output_lines: Option<usize>,
+    pub trailing_comma: Option<bool>,
+    pub ascii_only: Option<bool>,
+    pub json_envelope: Option<String>,
+    pub json_envelope_count: bool,
+    pub json_style: JsonStyle,
+    pub echo_query: bool,
+    pub empty_result_note: bool,
     pub styler: style::Styler,
 }
 
+/// Settings to layer onto a [`Config`] with [`Config::with_overrides`].
+/// Every field is applied like the builder method of the same name, and
+/// only if it is `Some`.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub colors: Option<bool>,
+    pub expand_strings: Option<bool>,
+    pub max_string_length: Option<Option<usize>>,
+    pub truncate_json_strings: Option<bool>,
+    pub max_width: Option<usize>,
+    pub implicit_properties: Option<bool>,
+    pub link_properties: Option<bool>,
+    pub max_items: Option<Option<usize>>,
+    pub items_window: Option<Option<(usize, usize)>>,
+    pub max_vector_length: Option<VectorLimit>,
+    pub bytes_format: Option<BytesFormat>,
+    pub expand_json: Option<bool>,
+    pub null_text: Option<String>,
+    pub empty_set_text: Option<String>,
+    pub max_output_lines: Option<Option<usize>>,
+    pub trailing_comma: Option<bool>,
+    pub ascii_only: Option<bool>,
+    pub json_envelope: Option<Option<String>>,
+    pub json_envelope_count: Option<bool>,
+    pub json_style: Option<JsonStyle>,
+    pub echo_query: Option<bool>,
+    pub empty_result_note: Option<bool>,
+}
+
+/// Layout of JSON output
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    clap::ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+    schemars::JsonSchema,
+)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum JsonStyle {
+    /// Break values that don't fit into `max_width` into lines, indented
+    /// by `indent` spaces
+    #[default]
+    Pretty,
+    /// Every item on a single line
+    Compact,
+}
+
+/// How `bytes` values are rendered in native output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BytesFormat {
+    /// Bytes literal with escaped non-printable characters: `b'a\x00'`
+    #[default]
+    Escaped,
+    /// Base64-encoded: `b64'YQA='`
+    Base64,
+    /// Hex-encoded: `x'6100'`
+    Hex,
+    /// Multi-line dump with offset, hex and ASCII columns
+    HexDump,
+}
+
 pub(in crate::print) struct Printer<T> {
     // config
     colors: bool,
     indent: usize,
     expand_strings: bool,
+    max_string_length: Option<usize>,
     max_width: usize,
     implicit_properties: bool,
+    link_properties: bool,
     max_items: Option<usize>,
+    items_window: Option<(usize, usize)>,
     max_vector_length: VectorLimit,
+    bytes_format: BytesFormat,
+    expand_json: bool,
+    null_text: Option<String>,
+    empty_set_text: Option<String>,
+    max_output_lines: Option<usize>,
     trailing_comma: bool,
+    ascii_only: bool,
 
     // state
     buffer: String,
@@ -81,25 +192,179 @@ pub(in crate::print) struct Printer<T> {
     committed_column: usize,
     column: usize,
     cur_indent: usize,
+    lines_written: usize,
+    truncated: bool,
 
     styler: style::Styler,
 }
 
 struct Stdout {}
 
+impl<T> Printer<T> {
+    /// Printer writing to `stream` with the settings of `config`, for the
+    /// formats to adjust: no colors, no width limit, no limit on output
+    /// lines and no trailing commas.
+    fn new(config: &Config, stream: T) -> Printer<T> {
+        Printer {
+            colors: false,
+            indent: config.indent,
+            expand_strings: config.expand_strings,
+            max_string_length: config.max_string_length,
+            max_width: usize::MAX,
+            implicit_properties: config.implicit_properties,
+            link_properties: config.link_properties,
+            max_items: config.max_items,
+            items_window: config.items_window,
+            max_vector_length: config.max_vector_length,
+            bytes_format: config.bytes_format,
+            expand_json: config.expand_json,
+            null_text: config.null_text.clone(),
+            empty_set_text: config.empty_set_text.clone(),
+            max_output_lines: None,
+            trailing_comma: false,
+            ascii_only: config.ascii_only.unwrap_or(false),
+
+            buffer: String::with_capacity(8192),
+            stream,
+            delim: Delim::None,
+            flow: false,
+            committed: 0,
+            committed_indent: 0,
+            committed_column: 0,
+            column: 0,
+            cur_indent: 0,
+            lines_written: 0,
+            truncated: false,
+
+            styler: config.styler.clone(),
+        }
+    }
+}
+
 impl Config {
     pub fn new() -> Config {
         Config {
             colors: None,
             indent: 2,
             expand_strings: true,
+            max_string_length: None,
+            truncate_json_strings: false,
             max_width: None,
             implicit_properties: false,
+            link_properties: true,
             max_items: None,
-            max_vector_length: VectorLimit::Unlimited,
+            items_window: None,
+            max_vector_length: env_vector_limit().unwrap_or(VectorLimit::Unlimited),
+            bytes_format: BytesFormat::Escaped,
+            expand_json: false,
+            null_text: None,
+            empty_set_text: None,
+            max_output_lines: None,
+            trailing_comma: None,
+            ascii_only: None,
+            json_envelope: None,
+            json_envelope_count: false,
+            json_style: JsonStyle::Pretty,
+            echo_query: false,
+            empty_result_note: false,
             styler: style::Styler::new(),
         }
     }
+    /// A copy of `base` with the settings given in `overrides` changed
+    pub fn with_overrides(base: &Config, overrides: ConfigOverrides) -> Config {
+        let ConfigOverrides {
+            colors,
+            expand_strings,
+            max_string_length,
+            truncate_json_strings,
+            max_width,
+            implicit_properties,
+            link_properties,
+            max_items,
+            items_window,
+            max_vector_length,
+            bytes_format,
+            expand_json,
+            null_text,
+            empty_set_text,
+            max_output_lines,
+            trailing_comma,
+            ascii_only,
+            json_envelope,
+            json_envelope_count,
+            json_style,
+            echo_query,
+            empty_result_note,
+        } = overrides;
+        let mut config = base.clone();
+        if let Some(value) = colors {
+            config.colors(value);
+        }
+        if let Some(value) = expand_strings {
+            config.expand_strings(value);
+        }
+        if let Some(value) = max_string_length {
+            config.max_string_length(value);
+        }
+        if let Some(value) = truncate_json_strings {
+            config.truncate_json_strings(value);
+        }
+        if let Some(value) = max_width {
+            config.max_width(value);
+        }
+        if let Some(value) = implicit_properties {
+            config.implicit_properties(value);
+        }
+        if let Some(value) = link_properties {
+            config.link_properties(value);
+        }
+        if let Some(value) = max_items {
+            config.max_items(value);
+        }
+        if let Some(value) = items_window {
+            config.items_window(value);
+        }
+        if let Some(value) = max_vector_length {
+            config.max_vector_length(value);
+        }
+        if let Some(value) = bytes_format {
+            config.bytes_format(value);
+        }
+        if let Some(value) = expand_json {
+            config.expand_json(value);
+        }
+        if let Some(value) = null_text {
+            config.null_text(value);
+        }
+        if let Some(value) = empty_set_text {
+            config.empty_set_text(value);
+        }
+        if let Some(value) = max_output_lines {
+            config.max_output_lines(value);
+        }
+        if let Some(value) = trailing_comma {
+            config.trailing_comma(value);
+        }
+        if let Some(value) = ascii_only {
+            config.ascii_only(value);
+        }
+        if let Some(value) = json_envelope {
+            config.json_envelope(value);
+        }
+        if let Some(value) = json_envelope_count {
+            config.json_envelope_count(value);
+        }
+        if let Some(value) = json_style {
+            config.json_style(value);
+        }
+        if let Some(value) = echo_query {
+            config.echo_query(value);
+        }
+        if let Some(value) = empty_result_note {
+            config.empty_result_note(value);
+        }
+        config
+    }
     #[allow(dead_code)]
     pub fn max_width(&mut self, value: usize) -> &mut Config {
         self.max_width = Some(value);
@@ -109,10 +374,95 @@ impl Config {
         self.max_items = value;
         self
     }
+    /// Show only the first `head` and the last `tail` rows of the result,
+    /// with an ellipsis in place of the rows in between. Takes precedence
+    /// over `max_items` for the top-level rows. Up to `tail` rows are kept
+    /// in memory until the end of the result is reached.
+    pub fn items_window(&mut self, value: Option<(usize, usize)>) -> &mut Config {
+        self.items_window = value;
+        self
+    }
     pub fn max_vector_length(&mut self, value: VectorLimit) -> &mut Config {
         self.max_vector_length = value;
         self
     }
+    pub fn bytes_format(&mut self, value: BytesFormat) -> &mut Config {
+        self.bytes_format = value;
+        self
+    }
+    /// Render `json` values as nested, highlighted structures instead of
+    /// a single quoted string
+    pub fn expand_json(&mut self, value: bool) -> &mut Config {
+        self.expand_json = value;
+        self
+    }
+    /// Text for missing values in native output, tables and tab-separated
+    /// output, where it defaults to `{}` and an empty cell respectively.
+    /// JSON output always uses `null`
+    pub fn null_text(&mut self, value: String) -> &mut Config {
+        self.null_text = Some(value);
+        self
+    }
+    /// Text for empty sets in native output and tables, `{}` by default
+    pub fn empty_set_text(&mut self, value: String) -> &mut Config {
+        self.empty_set_text = Some(value);
+        self
+    }
+    /// Stop native output after this many lines, however many rows they
+    /// hold, and mark the output as truncated
+    pub fn max_output_lines(&mut self, value: Option<usize>) -> &mut Config {
+        self.max_output_lines = value;
+        self
+    }
+    /// Put a comma after the last element of multi-line blocks in native
+    /// output, which is the default. JSON never has trailing commas.
+    pub fn trailing_comma(&mut self, value: bool) -> &mut Config {
+        self.trailing_comma = Some(value);
+        self
+    }
+    /// Draw tables with `+`, `-` and `|` and mark truncated strings with
+    /// `...` instead of `…`. When printing to stdout this is detected from
+    /// the terminal encoding unless set.
+    pub fn ascii_only(&mut self, value: bool) -> &mut Config {
+        self.ascii_only = Some(value);
+        self
+    }
+    /// Wrap JSON output of [`json_to_string`] into an object with the
+    /// array under this key: `{"data": [...]}`. Output is a bare array if
+    /// `None`, which is the default.
+    pub fn json_envelope(&mut self, value: Option<String>) -> &mut Config {
+        self.json_envelope = value;
+        self
+    }
+    /// Add the number of items to the `json_envelope` object as `"count"`
+    pub fn json_envelope_count(&mut self, value: bool) -> &mut Config {
+        self.json_envelope_count = value;
+        self
+    }
+    /// Layout of [`json_to_string`] and [`json_item_to_string`] output:
+    /// `Compact` puts every item on a single line whatever its width,
+    /// `Pretty` (the default) breaks it into lines indented by `indent`.
+    pub fn json_style(&mut self, value: JsonStyle) -> &mut Config {
+        self.json_style = value;
+        self
+    }
+    /// Print each query, syntax highlighted, above its result, see
+    /// [`query_echo`]. Off by default.
+    pub fn echo_query(&mut self, value: bool) -> &mut Config {
+        self.echo_query = value;
+        self
+    }
+    /// Print a dim `(no rows)` note to stderr when a result is empty, see
+    /// [`empty_result_note`]. The output itself is unchanged.
+    pub fn empty_result_note(&mut self, value: bool) -> &mut Config {
+        self.empty_result_note = value;
+        self
+    }
+    /// Spaces to indent nested values by in multi-line output
+    pub fn indent(&mut self, value: usize) -> &mut Config {
+        self.indent = value;
+        self
+    }
     pub fn colors(&mut self, value: bool) -> &mut Config {
         self.colors = Some(value);
         self
@@ -121,20 +471,143 @@ impl Config {
         self.expand_strings = value;
         self
     }
+    /// Truncate string values longer than this many graphemes
+    pub fn max_string_length(&mut self, value: Option<usize>) -> &mut Config {
+        self.max_string_length = value;
+        self
+    }
+    /// Also apply `max_string_length` to JSON output. Truncated strings
+    /// stay valid JSON strings with `…(+N chars)` appended to the value.
+    pub fn truncate_json_strings(&mut self, value: bool) -> &mut Config {
+        self.truncate_json_strings = value;
+        self
+    }
     pub fn implicit_properties(&mut self, value: bool) -> &mut Config {
         self.implicit_properties = value;
         self
     }
+    /// Print link properties of objects, as `@name`. On by default.
+    pub fn link_properties(&mut self, value: bool) -> &mut Config {
+        self.link_properties = value;
+        self
+    }
+}
+
+/// Output format implied by the extension of an output file: `.json`,
+/// `.jsonl` or `.ndjson` (JSON lines), `.tsv` (tab-separated), `.edgeql`,
+/// `.parquet` and `.msgpack`. Other extensions give `None`, except `.csv`
+/// and `.yaml`, which have no matching format and are an error.
+pub fn output_format_for_path(path: &Path) -> anyhow::Result<Option<OutputFormat>> {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return Ok(None);
+    };
+    let format = match &ext.to_ascii_lowercase()[..] {
+        "json" => OutputFormat::Json,
+        "jsonl" | "ndjson" => OutputFormat::JsonLines,
+        "tsv" => OutputFormat::TabSeparated,
+        "edgeql" => OutputFormat::Edgeql,
+        "parquet" => OutputFormat::Parquet,
+        "msgpack" => OutputFormat::Msgpack,
+        "csv" | "yaml" | "yml" => anyhow::bail!(
+            "cannot write `.{ext}` files: there is no such output format. \
+             Use `--output-format` to pick one, e.g. `tab-separated` or `json`."
+        ),
+        _ => return Ok(None),
+    };
+    Ok(Some(format))
 }
 
 pub fn completion<B: AsRef<[u8]>>(res: B) {
     msg!("OK: {}", String::from_utf8_lossy(res.as_ref()).emphasized());
 }
 
+/// The query to print above its result with [`Config::echo_query`], or
+/// `None` if echo is off. Highlighted like the REPL prompt when colors are
+/// enabled.
+pub fn query_echo(query: &str, config: &Config) -> Option<String> {
+    if !config.echo_query {
+        return None;
+    }
+    let mut buf = String::new();
+    if config.colors.unwrap_or_else(use_color) {
+        crate::highlight::edgeql(&mut buf, query.trim(), &config.styler);
+    } else {
+        buf.push_str(query.trim());
+    }
+    buf.push('\n');
+    Some(buf)
+}
+
+/// The note to print to stderr after a result of `rows` rows with
+/// [`Config::empty_result_note`], or `None` if the result isn't empty or the
+/// note is off.
+pub fn empty_result_note(rows: u64, config: &Config) -> Option<String> {
+    if rows > 0 || !config.empty_result_note {
+        return None;
+    }
+    if config.colors.unwrap_or_else(use_color) {
+        Some("(no rows)".muted().to_string())
+    } else {
+        Some("(no rows)".into())
+    }
+}
+
+/// The last rows of a result shown with [`Config::items_window`], and the
+/// number of rows dropped before them
+struct TailRows<I> {
+    rows: VecDeque<I>,
+    len: usize,
+    hidden: usize,
+}
+
+impl<I> TailRows<I> {
+    fn new(len: usize) -> TailRows<I> {
+        TailRows {
+            rows: VecDeque::with_capacity(len),
+            len,
+            hidden: 0,
+        }
+    }
+    fn push(&mut self, row: I) {
+        if self.rows.len() == self.len {
+            self.hidden += 1;
+            if self.rows.pop_front().is_none() {
+                return;
+            }
+        }
+        self.rows.push_back(row);
+    }
+}
+
+fn format_tail<I, E, O>(
+    prn: &mut Printer<O>,
+    hidden: usize,
+    rows: &[I],
+) -> Result<(), Exception<PrintError<E, O::Error>>>
+where
+    I: FormatExt,
+    E: fmt::Debug + Error + 'static,
+    O: Output,
+    O::Error: fmt::Debug + Error + 'static,
+{
+    if hidden > 0 {
+        prn.hidden_items(hidden).wrap_err(PrintErr)?;
+    }
+    for v in rows {
+        v.format(prn).wrap_err(PrintErr)?;
+        prn.comma().wrap_err(PrintErr)?;
+    }
+    Ok(())
+}
+
+/// Formats rows on a single line while they fit. With `items_window`,
+/// the whole result is read here and `window` is set to the index of the
+/// first tail row in `row_buf` and the number of rows hidden before it.
 async fn format_rows_buf<S, I, E, O>(
     prn: &mut Printer<O>,
     rows: &mut S,
     row_buf: &mut Vec<I>,
+    window: &mut Option<(usize, usize)>,
     end_of_stream: &mut bool,
 ) -> Result<(), Exception<PrintError<E, O::Error>>>
 where
@@ -149,9 +622,16 @@ where
         .wrap_err(PrintErr)?;
 
     debug_assert!(branch);
+    let mut tail = prn.items_window.map(|(_, len)| TailRows::new(len));
     while let Some(v) = rows.next().await.transpose().wrap_err(StreamErr)? {
+        if let (Some(tail), Some((head, _))) = (&mut tail, prn.items_window) {
+            if row_buf.len() >= head {
+                tail.push(v);
+                continue;
+            }
+        }
         row_buf.push(v);
-        if let Some(limit) = prn.max_items {
+        if let Some(limit) = prn.max_items.filter(|_| prn.items_window.is_none()) {
             if row_buf.len() > limit {
                 prn.ellipsis().wrap_err(PrintErr)?;
                 // consume extra items if any
@@ -165,6 +645,13 @@ where
         // Buffer rows up to one visual line.
         // After line is reached we get Exception::DisableFlow
     }
+    if let Some(tail) = tail {
+        // the stream is exhausted, keep the tail for `format_rows`
+        let head = row_buf.len();
+        *window = Some((head, tail.hidden));
+        row_buf.extend(tail.rows);
+        format_tail(prn, tail.hidden, &row_buf[head..])?;
+    }
     *end_of_stream = true;
     prn.close_block(&prn.styler.apply(style::Style::Set, "}"), true)
         .wrap_err(PrintErr)?;
@@ -174,6 +661,7 @@ where
 async fn format_rows<S, I, E, O>(
     prn: &mut Printer<O>,
     buffered_rows: Vec<I>,
+    window: Option<(usize, usize)>,
     rows: &mut S,
 ) -> Result<(), Exception<PrintError<E, O::Error>>>
 where
@@ -184,10 +672,26 @@ where
     O::Error: fmt::Debug + Error + 'static,
 {
     prn.reopen_block().wrap_err(PrintErr)?;
+    if let Some((head, hidden)) = window {
+        // the whole result is buffered already
+        for v in &buffered_rows[..head] {
+            v.format(prn).wrap_err(PrintErr)?;
+            prn.comma().wrap_err(PrintErr)?;
+        }
+        format_tail(prn, hidden, &buffered_rows[head..])?;
+        prn.close_block(&prn.styler.apply(style::Style::Set, "}"), true)
+            .wrap_err(PrintErr)?;
+        return Ok(());
+    }
+    let max_items = prn.max_items.filter(|_| prn.items_window.is_none());
+    let mut tail = prn.items_window.map(|(_, len)| TailRows::new(len));
     let mut counter: usize = 0;
     for v in buffered_rows {
+        if prn.truncated {
+            break;
+        }
         counter += 1;
-        if let Some(limit) = prn.max_items {
+        if let Some(limit) = max_items {
             if counter > limit {
                 prn.ellipsis().wrap_err(PrintErr)?;
                 break;
@@ -197,8 +701,19 @@ where
         prn.comma().wrap_err(PrintErr)?;
     }
     while let Some(v) = rows.next().await.transpose().wrap_err(StreamErr)? {
+        if prn.truncated {
+            // nothing more is shown, but the rest of the rows are consumed
+            while rows.next().await.transpose().wrap_err(StreamErr)?.is_some() {}
+            break;
+        }
         counter += 1;
-        if let Some(limit) = prn.max_items {
+        if let (Some(tail), Some((head, _))) = (&mut tail, prn.items_window) {
+            if counter > head {
+                tail.push(v);
+                continue;
+            }
+        }
+        if let Some(limit) = max_items {
             if counter > limit {
                 prn.ellipsis().wrap_err(PrintErr)?;
                 // consume extra items if any
@@ -209,6 +724,9 @@ where
         v.format(prn).wrap_err(PrintErr)?;
         prn.comma().wrap_err(PrintErr)?;
     }
+    if let Some(mut tail) = tail.filter(|_| !prn.truncated) {
+        format_tail(prn, tail.hidden, tail.rows.make_contiguous())?;
+    }
     prn.close_block(&prn.styler.apply(style::Style::Set, "}"), true)
         .wrap_err(PrintErr)?;
     Ok(())
@@ -226,8 +744,38 @@ where
     let w = config
         .max_width
         .unwrap_or_else(|| terminal_size().map(|(Width(w), _h)| w.into()).unwrap_or(80));
-    let colors = config.colors.unwrap_or_else(|| io::stdout().is_terminal());
-    _native_format(rows, config, w, colors, Stdout {}).await
+    let colors = config.colors.unwrap_or_else(use_color);
+    let ascii_only = config.ascii_only.unwrap_or_else(|| !use_utf8());
+    let count = _native_format(rows, config, w, colors, ascii_only, Stdout {}).await?;
+    if let Some(note) = empty_result_note(count, config) {
+        eprintln!("{note}");
+    }
+    Ok(())
+}
+
+/// Same as [`native_to_stdout`], but writes into `output`. Colors and
+/// ASCII-only output are only used when enabled explicitly in `config`,
+/// and the width defaults to 80 columns.
+pub async fn native_to_output<S, I, E, O>(
+    rows: S,
+    config: &Config,
+    output: O,
+) -> Result<(), PrintError<E, O::Error>>
+where
+    S: Stream<Item = Result<I, E>> + Send + Unpin,
+    I: FormatExt,
+    E: fmt::Debug + Error + 'static,
+    O: Output,
+    O::Error: Error + 'static,
+{
+    let w = config.max_width.unwrap_or(80);
+    let colors = config.colors.unwrap_or(false);
+    let ascii_only = config.ascii_only.unwrap_or(false);
+    let count = _native_format(rows, config, w, colors, ascii_only, output).await?;
+    if let Some(note) = empty_result_note(count, config) {
+        eprintln!("{note}");
+    }
+    Ok(())
 }
 
 fn get_printer_string(prn: &mut Printer<&mut String>) -> String {
@@ -254,12 +802,19 @@ fn is_numeric(v: &Value) -> bool {
 fn to_cell(prn: &mut Printer<&mut String>, v: &Option<Value>) -> table::Cell {
     match v {
         Some(Value::Str(s)) => {
-            let s = native::format_string(s, prn.expand_strings());
+            let s = native::format_string(
+                s,
+                prn.expand_strings(),
+                prn.max_string_length(),
+                prn.ascii_only(),
+            );
             prn.const_string(&s[1..s.len() - 1])
                 .unwrap_exc()
                 .unwrap_infallible()
         }
         Some(vi) => vi.format(prn).unwrap_exc().unwrap_infallible(),
+        // cells are left empty unless asked otherwise
+        None if prn.null_text.is_some() => prn.nil().unwrap_exc().unwrap_infallible(),
         None => {}
     };
     let mut cell = Cell::new(&get_printer_string(prn));
@@ -286,7 +841,7 @@ where
     let mut counter: usize = 0;
 
     let mut table = Table::new();
-    table.set_format(*table::FORMAT);
+    table.set_format(table::format(prn.ascii_only));
 
     let mut title_row = Vec::new();
     let mut titles_set = false;
@@ -339,6 +894,20 @@ where
     Ok(table)
 }
 
+// This is kind of hacky, but for ~performance~ and to avoid
+// needing to pass around enough config info to recreate new ones,
+// we repeatedly invoke a single Printer and then pull the strings
+// out and put them in a table we are building. Tables are only ever
+// printed to stdout, so ASCII output is detected like for stdout.
+fn cell_printer<'a>(config: &Config, buf: &'a mut String) -> Printer<&'a mut String> {
+    // We don't use colors yet because the table library gets
+    // confused.
+    Printer {
+        ascii_only: config.ascii_only.unwrap_or_else(|| !use_utf8()),
+        ..Printer::new(config, buf)
+    }
+}
+
 async fn _table_format<S, I, E>(
     mut rows: S,
     config: &Config,
@@ -350,35 +919,8 @@ where
     I: FormatExt + Into<Value>,
     E: fmt::Debug + Error + 'static,
 {
-    // This is kind of hacky, but for ~performance~ and to avoid
-    // needing to pass around enough config info to recreate new ones,
-    // we repeatedly invoke a single Printer and then pull the strings
-    // out and put them in a table we are building.
     let mut buf = String::new();
-    let mut prn = Printer {
-        // We don't use colors yet because the table library gets
-        // confused.
-        colors: false,
-        indent: config.indent,
-        expand_strings: config.expand_strings,
-        max_width: usize::MAX,
-        implicit_properties: config.implicit_properties,
-        max_items: config.max_items,
-        max_vector_length: config.max_vector_length,
-        trailing_comma: false,
-
-        buffer: String::with_capacity(128),
-        stream: &mut buf,
-        delim: Delim::None,
-        flow: false,
-        committed: 0,
-        committed_indent: 0,
-        committed_column: 0,
-        column: 0,
-        cur_indent: 0,
-
-        styler: config.styler.clone(),
-    };
+    let mut prn = cell_printer(config, &mut buf);
 
     let table = format_table_rows(&mut prn, &mut rows).await?;
 
@@ -397,7 +939,7 @@ where
     let w = config
         .max_width
         .unwrap_or_else(|| terminal_size().map(|(Width(w), _h)| w.into()).unwrap_or(80));
-    let colors = config.colors.unwrap_or_else(|| io::stdout().is_terminal());
+    let colors = config.colors.unwrap_or_else(use_color);
     let table = _table_format(rows, config, w, colors)
         .await
         .map_err(|e| PrintError::StreamErr { source: e })?;
@@ -411,13 +953,239 @@ where
     Ok(())
 }
 
-async fn _native_format<S, I, E, O>(
+/// Layout picked by [`auto_to_stdout`] from the shape of a result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoLayout {
+    /// A single scalar, printed as is
+    Raw,
+    /// A single object, printed as `name = value` lines like
+    /// [`kv_to_stdout`] does
+    Vertical,
+    /// Several objects of the same flat shape
+    Table,
+    /// Anything else
+    Native,
+}
+
+fn is_scalar(v: &Value) -> bool {
+    !matches!(
+        v,
+        Value::Set(_)
+            | Value::Object { .. }
+            | Value::SparseObject(_)
+            | Value::Tuple(_)
+            | Value::NamedTuple { .. }
+            | Value::SQLRow { .. }
+            | Value::Array(_)
+            | Value::Vector(_)
+            | Value::Range(_)
+    )
+}
+
+fn object_fields(v: &Value) -> Option<(Vec<&str>, &[Option<Value>])> {
+    match v {
+        Value::Object { shape, fields } => {
+            Some((shape.elements.iter().map(|s| &s.name[..]).collect(), fields))
+        }
+        Value::SQLRow { shape, fields } => {
+            Some((shape.elements.iter().map(|s| &s.name[..]).collect(), fields))
+        }
+        _ => None,
+    }
+}
+
+/// Field names of an object with only scalar fields
+fn flat_fields(v: &Value) -> Option<Vec<&str>> {
+    let (names, fields) = object_fields(v)?;
+    fields.iter().flatten().all(is_scalar).then_some(names)
+}
+
+pub fn auto_layout(rows: &[Value]) -> AutoLayout {
+    match rows {
+        [Value::Object { .. } | Value::SQLRow { .. }] => AutoLayout::Vertical,
+        [v] if is_scalar(v) => AutoLayout::Raw,
+        [first, rest @ ..] if !rest.is_empty() => {
+            let Some(names) = flat_fields(first) else {
+                return AutoLayout::Native;
+            };
+            if rest.iter().all(|v| flat_fields(v).as_ref() == Some(&names)) {
+                AutoLayout::Table
+            } else {
+                AutoLayout::Native
+            }
+        }
+        _ => AutoLayout::Native,
+    }
+}
+
+/// Collects the whole result and prints it with the layout picked by
+/// [`auto_layout`].
+pub async fn auto_to_stdout<S, I, E>(
     mut rows: S,
     config: &Config,
+) -> Result<(), PrintError<E, io::Error>>
+where
+    S: Stream<Item = Result<I, E>> + Send + Unpin,
+    I: FormatExt + Into<Value>,
+    E: fmt::Debug + Error + 'static,
+{
+    let mut items: Vec<Value> = Vec::new();
+    while let Some(v) = rows.next().await.transpose().context(StreamErr)? {
+        items.push(v.into());
+    }
+    match auto_layout(&items) {
+        AutoLayout::Raw => {
+            let text = match &items[0] {
+                Value::Str(s) => s.clone(),
+                v => {
+                    let mut buf = String::new();
+                    let mut prn = cell_printer(config, &mut buf);
+                    v.format(&mut prn).unwrap_exc().unwrap_infallible();
+                    get_printer_string(&mut prn)
+                }
+            };
+            println!("{text}");
+        }
+        AutoLayout::Vertical => print_kv(&items, config),
+        AutoLayout::Table => {
+            table_to_stdout(
+                tokio_stream::iter(items.into_iter().map(Ok::<_, E>)),
+                config,
+            )
+            .await?;
+        }
+        AutoLayout::Native => {
+            native_to_stdout(
+                tokio_stream::iter(items.into_iter().map(Ok::<_, E>)),
+                config,
+            )
+            .await?;
+            println!();
+        }
+    }
+    Ok(())
+}
+
+/// Properties of an object shown by [`kv_to_string`]
+fn kv_fields(v: &Value, implicit_properties: bool) -> Option<Vec<(&str, &Option<Value>)>> {
+    match v {
+        Value::Object { shape, fields } => Some(
+            shape
+                .elements
+                .iter()
+                .zip(fields)
+                .filter(|(el, _)| implicit_properties || !el.flag_implicit)
+                .map(|(el, value)| (&el.name[..], value))
+                .collect(),
+        ),
+        Value::SQLRow { shape, fields } => Some(
+            shape
+                .elements
+                .iter()
+                .map(|el| &el.name[..])
+                .zip(fields)
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Renders an object as a `name = value` line per property, with the `=`
+/// of all lines aligned. Values that don't fit a line are laid out like
+/// native output, indented to start after the `=`. Returns `None` for
+/// values other than objects.
+pub fn kv_to_string(
+    value: &Value,
+    config: &Config,
     max_width: usize,
     colors: bool,
+) -> Option<String> {
+    const MIN_VALUE_WIDTH: usize = 20;
+    let fields = kv_fields(value, config.implicit_properties)?;
+    let name_width = fields
+        .iter()
+        .map(|(name, _)| name.width())
+        .max()
+        .unwrap_or(0);
+    let indent = " ".repeat(name_width + " = ".len());
+    let mut out = String::new();
+    for (name, value) in fields {
+        let mut buf = String::new();
+        let mut prn = cell_printer(config, &mut buf);
+        prn.colors = colors;
+        prn.max_width = max_width.saturating_sub(indent.len()).max(MIN_VALUE_WIDTH);
+        prn.trailing_comma = config.trailing_comma.unwrap_or(true);
+        value.format(&mut prn).unwrap_exc().unwrap_infallible();
+        let text = get_printer_string(&mut prn);
+        if colors {
+            config.styler.write(style::Style::Pointer, name, &mut out);
+        } else {
+            out.push_str(name);
+        }
+        out.push_str(&" ".repeat(name_width - name.width()));
+        out.push_str(" = ");
+        for (i, line) in text.lines().enumerate() {
+            if i > 0 {
+                out.push_str(&indent);
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    Some(out)
+}
+
+/// Prints each object of the result with [`kv_to_string`], separated by
+/// empty lines. If the result has anything but objects, it is printed as
+/// native output with a warning instead.
+pub async fn kv_to_stdout<S, I, E>(
+    mut rows: S,
+    config: &Config,
+) -> Result<(), PrintError<E, io::Error>>
+where
+    S: Stream<Item = Result<I, E>> + Send + Unpin,
+    I: FormatExt + Into<Value>,
+    E: fmt::Debug + Error + 'static,
+{
+    let mut items: Vec<Value> = Vec::new();
+    while let Some(v) = rows.next().await.transpose().context(StreamErr)? {
+        items.push(v.into());
+    }
+    if items.iter().any(|v| kv_fields(v, false).is_none()) {
+        warn!("The result is not a set of objects, printing it in the default format");
+        native_to_stdout(
+            tokio_stream::iter(items.into_iter().map(Ok::<_, E>)),
+            config,
+        )
+        .await?;
+        println!();
+        return Ok(());
+    }
+    print_kv(&items, config);
+    Ok(())
+}
+
+/// Prints objects with [`kv_to_string`], separated by empty lines
+fn print_kv(items: &[Value], config: &Config) {
+    let w = config
+        .max_width
+        .unwrap_or_else(|| terminal_size().map(|(Width(w), _h)| w.into()).unwrap_or(80));
+    let colors = config.colors.unwrap_or_else(use_color);
+    let blocks = items
+        .iter()
+        .filter_map(|v| kv_to_string(v, config, w, colors))
+        .collect::<Vec<_>>();
+    print!("{}", blocks.join("\n"));
+}
+
+async fn _native_format<S, I, E, O>(
+    rows: S,
+    config: &Config,
+    max_width: usize,
+    colors: bool,
+    ascii_only: bool,
     output: O,
-) -> Result<(), PrintError<E, O::Error>>
+) -> Result<u64, PrintError<E, O::Error>>
 where
     S: Stream<Item = Result<I, E>> + Send + Unpin,
     I: FormatExt,
@@ -425,35 +1193,29 @@ where
     O: Output,
     O::Error: Error + 'static,
 {
+    // rows are counted as they are read, for `Config::empty_result_note`
+    let mut count = 0;
+    let mut rows = rows.inspect(|row| {
+        if row.is_ok() {
+            count += 1;
+        }
+    });
     let mut prn = Printer {
         colors,
-        indent: config.indent,
-        expand_strings: config.expand_strings,
         max_width,
-        implicit_properties: config.implicit_properties,
-        max_items: config.max_items,
-        max_vector_length: config.max_vector_length,
-        trailing_comma: true,
-
-        buffer: String::with_capacity(8192),
-        stream: output,
-        delim: Delim::None,
-        flow: false,
-        committed: 0,
-        committed_indent: 0,
-        committed_column: 0,
-        column: 0,
-        cur_indent: 0,
-
-        styler: config.styler.clone(),
+        max_output_lines: config.max_output_lines,
+        trailing_comma: config.trailing_comma.unwrap_or(true),
+        ascii_only,
+        ..Printer::new(config, output)
     };
     let mut row_buf = Vec::new();
+    let mut window = None;
     let mut eos = false;
-    match format_rows_buf(&mut prn, &mut rows, &mut row_buf, &mut eos).await {
+    match format_rows_buf(&mut prn, &mut rows, &mut row_buf, &mut window, &mut eos).await {
         Ok(()) => {}
         Err(Exception::DisableFlow) => {
             if !eos {
-                format_rows(&mut prn, row_buf, &mut rows)
+                format_rows(&mut prn, row_buf, window, &mut rows)
                     .await
                     .unwrap_exc()?;
             }
@@ -461,7 +1223,13 @@ where
         Err(Exception::Error(e)) => return Err(e),
     };
     prn.end().unwrap_exc().context(PrintErr)?;
-    Ok(())
+    if let (true, Some(limit)) = (prn.truncated, prn.max_output_lines) {
+        prn.stream
+            .write(&format!("(output truncated at {limit} lines)"))
+            .context(PrintErr)?;
+    }
+    drop(rows);
+    Ok(count)
 }
 
 fn format_rows_str<I: FormatExt>(
@@ -485,30 +1253,59 @@ fn format_rows_str<I: FormatExt>(
     Ok(())
 }
 
+/// Formats `items` as an object holding them under `key`, and their
+/// number under `"count"` if `count` is set
+fn format_envelope<I: FormatExt>(
+    prn: &mut Printer<&mut String>,
+    items: &[I],
+    key: &str,
+    count: bool,
+) -> buffer::Result<Infallible> {
+    let key = serde_json::to_string(key).expect("cannot serialize string");
+    prn.json_object(|prn| {
+        prn.object_field(&key, false)?;
+        prn.array(None, |prn| {
+            for v in items {
+                v.format(prn)?;
+                prn.comma()?;
+            }
+            Ok(())
+        })?;
+        prn.comma()?;
+        if count {
+            prn.object_field("\"count\"", false)?;
+            prn.const_number(items.len())?;
+            prn.comma()?;
+        }
+        Ok(())
+    })
+}
+
+/// Line width for JSON output: compact output never wraps
+fn json_width(config: &Config) -> usize {
+    match config.json_style {
+        JsonStyle::Pretty => config.max_width.unwrap_or(80),
+        JsonStyle::Compact => usize::MAX,
+    }
+}
+
 pub fn json_to_string<I: FormatExt>(items: &[I], config: &Config) -> Result<String, Infallible> {
     let mut out = String::new();
     let mut prn = Printer {
         colors: config.colors.unwrap_or(false),
-        indent: config.indent,
-        expand_strings: config.expand_strings,
-        max_width: config.max_width.unwrap_or(80),
-        implicit_properties: config.implicit_properties,
-        max_items: config.max_items,
-        max_vector_length: config.max_vector_length,
-        trailing_comma: false,
-
-        buffer: String::with_capacity(8192),
-        stream: &mut out,
-        delim: Delim::None,
-        flow: false,
-        committed: 0,
-        committed_indent: 0,
-        committed_column: 0,
-        column: 0,
-        cur_indent: 0,
-
-        styler: config.styler.clone(),
+        max_string_length: config
+            .max_string_length
+            .filter(|_| config.truncate_json_strings),
+        max_width: json_width(config),
+        ..Printer::new(config, &mut out)
     };
+    if let Some(key) = &config.json_envelope {
+        // the outermost block re-renders itself if it doesn't fit, see
+        // `json_item_to_string`
+        format_envelope(&mut prn, items, key, config.json_envelope_count).unwrap_exc()?;
+        prn.end().unwrap_exc()?;
+        return Ok(out);
+    }
     match format_rows_str(&mut prn, items, "[", "]", false) {
         Ok(()) => {}
         Err(Exception::DisableFlow) => {
@@ -524,29 +1321,20 @@ pub fn json_item_to_string<I: FormatExt>(item: &I, config: &Config) -> Result<St
     let mut out = String::new();
     let mut prn = Printer {
         colors: config.colors.unwrap_or(false),
-        indent: config.indent,
-        expand_strings: config.expand_strings,
-        max_width: config.max_width.unwrap_or(80),
-        implicit_properties: config.implicit_properties,
-        max_items: config.max_items,
-        max_vector_length: config.max_vector_length,
-        trailing_comma: false,
-
-        buffer: String::with_capacity(8192),
-        stream: &mut out,
-        delim: Delim::None,
-        flow: false,
-        committed: 0,
-        committed_indent: 0,
-        committed_column: 0,
-        column: 0,
-        cur_indent: 0,
-
-        styler: config.styler.clone(),
+        max_string_length: config
+            .max_string_length
+            .filter(|_| config.truncate_json_strings),
+        max_width: json_width(config),
+        ..Printer::new(config, &mut out)
     };
+    // `DisableFlow` is only raised while `prn.flow` is set. Flow is switched
+    // on by the outermost block of the item (we start with `flow: false`),
+    // and that block always catches it and re-renders in multi-line mode,
+    // so it can't escape a top-level item whatever its size and
+    // `max_width` are (see the `json_item_narrow` test).
     match item.format(&mut prn) {
         Ok(()) => {}
-        Err(Exception::DisableFlow) => unreachable!(),
+        Err(Exception::DisableFlow) => unreachable!("flow is disabled at the top level"),
         Err(Exception::Error(e)) => return Err(e),
     }
     prn.end().unwrap_exc()?;
@@ -572,27 +1360,248 @@ pub fn use_utf8() -> bool {
     cfg!(windows) || *utf8_env
 }
 
-/// Does this terminal support ANSI colors?
+/// Value of the global `--color` option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Use colors when writing to a terminal that supports them
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static COLOR_CHOICE: OnceLock<ColorChoice> = OnceLock::new();
+
+/// Sets `--color` for the rest of the process. Only the first call has an
+/// effect, it should happen before anything is printed.
+pub fn set_color_choice(choice: ColorChoice) {
+    COLOR_CHOICE.set(choice).ok();
+}
+
+/// The `--color` option in effect
+pub fn color_choice() -> ColorChoice {
+    COLOR_CHOICE.get().copied().unwrap_or_default()
+}
+
+fn resolve_color(choice: ColorChoice, detect: impl FnOnce() -> bool) -> bool {
+    match choice {
+        ColorChoice::Auto => detect(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    }
+}
+
+/// Should the output be colored? This is the single source of truth for
+/// messages, native output and tables: it honors `--color` and falls back
+/// to detecting ANSI color support of the terminal.
 pub fn use_color() -> bool {
-    concolor::get(concolor::Stream::Stdout).ansi_color()
+    resolve_color(color_choice(), || {
+        concolor::get(concolor::Stream::Stdout).ansi_color()
+    })
+}
+
+/// Value of the global `--messages-format` option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum MessagesFormat {
+    /// Human-readable messages only
+    #[default]
+    Text,
+    /// Also print a JSON object per line for messages that have a
+    /// structured form, see [`success_json`]
+    Json,
+}
+
+static MESSAGES_FORMAT: OnceLock<MessagesFormat> = OnceLock::new();
+
+/// Sets `--messages-format` for the rest of the process. Only the first
+/// call has an effect.
+pub fn set_messages_format(format: MessagesFormat) {
+    MESSAGES_FORMAT.set(format).ok();
+}
+
+/// The `--messages-format` option in effect
+pub fn messages_format() -> MessagesFormat {
+    MESSAGES_FORMAT.get().copied().unwrap_or_default()
+}
+
+static NO_PAGER: AtomicBool = AtomicBool::new(false);
+
+/// Disables the pager for the rest of the process (`--no-pager`)
+pub fn disable_pager() {
+    NO_PAGER.store(true, Ordering::Relaxed);
+}
+
+/// Pager to pipe long output through, split into program and arguments.
+/// `None` means output should be written to stdout directly.
+pub fn pager_command() -> Option<Vec<String>> {
+    if NO_PAGER.load(Ordering::Relaxed) {
+        return None;
+    }
+    let env_pager = Env::pager().unwrap_or_else(|e| {
+        log::warn!("{e:#}");
+        None
+    });
+    let system_pager = Env::system_pager().unwrap_or_else(|e| {
+        log::warn!("{e:#}");
+        None
+    });
+    let pager = choose_pager(env_pager, system_pager);
+    resolve_pager(&pager, |bin| which::which(bin).is_ok())
+}
+
+/// Writes `text` to stdout, through the pager if stdout is a terminal
+/// that can't show all of it at once. Quitting the pager (or closing the
+/// pipe stdout is connected to) before everything is written is not an
+/// error.
+pub fn page(text: &str) -> io::Result<()> {
+    let pager = match terminal_size() {
+        Some((Width(w), Height(h))) if io::stdout().is_terminal() => {
+            if screen_lines(text, w.into()) < usize::from(h) {
+                None
+            } else {
+                pager_command()
+            }
+        }
+        _ => None,
+    };
+    let result = match pager {
+        Some(pager) => {
+            let mut child = Command::new(&pager[0])
+                .args(&pager[1..])
+                .stdin(Stdio::piped())
+                .spawn()?;
+            let mut stdin = child.stdin.take().expect("stdin is piped");
+            let result = stdin.write_all(text.as_bytes());
+            drop(stdin);
+            child.wait()?;
+            result
+        }
+        None => {
+            let mut out = io::stdout().lock();
+            out.write_all(text.as_bytes()).and_then(|()| out.flush())
+        }
+    };
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+/// Number of terminal lines `text` takes up, with long lines wrapped
+fn screen_lines(text: &str, width: usize) -> usize {
+    text.lines()
+        .map(|line| line.width().div_ceil(width.max(1)).max(1))
+        .sum()
+}
+
+/// `GEL_PAGER`/`EDGEDB_PAGER` takes precedence over the system `PAGER`.
+fn choose_pager(env_pager: Option<String>, system_pager: Option<String>) -> String {
+    env_pager.or(system_pager).unwrap_or_else(|| {
+        if cfg!(windows) {
+            String::from("more.com")
+        } else {
+            String::from("less -R")
+        }
+    })
+}
+
+/// Like git, an empty pager or `cat` disables paging. A pager that can't
+/// be found falls back to stdout with a warning printed once.
+fn resolve_pager(pager: &str, exists: impl Fn(&str) -> bool) -> Option<Vec<String>> {
+    static MISSING_PAGER: Once = Once::new();
+
+    let items: Vec<String> = pager.split_whitespace().map(String::from).collect();
+    let bin = items.first()?;
+    if bin == "cat" {
+        return None;
+    }
+    if !exists(bin) {
+        MISSING_PAGER.call_once(|| {
+            warn!("Pager {bin:?} not found, printing output directly");
+        });
+        return None;
+    }
+    Some(items)
 }
 
 pub fn prompt(line: impl fmt::Display) {
     println!("{}", line.to_string().emphasized().warning());
 }
 
+/// Asks to confirm a destructive operation, e.g. `target` of
+/// `"log out from profile \"default\""`, after printing `details` about
+/// what will be lost.
+///
+/// With `force` the operation proceeds without asking. In non-interactive
+/// mode it only proceeds when forced.
+pub fn confirm_overwrite(
+    target: &str,
+    details: &[String],
+    force: bool,
+    non_interactive: bool,
+) -> anyhow::Result<bool> {
+    if let Some(proceed) = overwrite_decision(force, non_interactive) {
+        return Ok(proceed);
+    }
+    for line in details {
+        msg!("{}", line.danger());
+    }
+    crate::question::Confirm::new_dangerous(format!("Still {target}?")).ask()
+}
+
+/// Result of [`confirm_overwrite`] when no question needs to be asked.
+fn overwrite_decision(force: bool, non_interactive: bool) -> Option<bool> {
+    if force {
+        Some(true)
+    } else if non_interactive {
+        Some(false)
+    } else {
+        None
+    }
+}
+
 pub fn err_marker() -> impl fmt::Display {
     concatcp!(BRANDING_CLI_CMD, " error:").danger().emphasized()
 }
 
+/// Default of `GEL_ERROR_EMPHASIS_THRESHOLD`
+const ERROR_EMPHASIS_THRESHOLD: usize = 60;
+
+static ERROR_EMPHASIS: once_cell::sync::Lazy<usize> = once_cell::sync::Lazy::new(|| {
+    Env::error_emphasis_threshold()
+        .unwrap_or_else(|e| {
+            log::warn!("GEL_ERROR_EMPHASIS_THRESHOLD: {e:#}");
+            None
+        })
+        .unwrap_or(ERROR_EMPHASIS_THRESHOLD)
+});
+
+static ENV_VECTOR_LIMIT: once_cell::sync::Lazy<Option<VectorLimit>> =
+    once_cell::sync::Lazy::new(|| {
+        Env::max_vector_length().unwrap_or_else(|e| {
+            log::warn!("GEL_MAX_VECTOR_LENGTH: {e:#}");
+            None
+        })
+    });
+
+/// Vector limit set with `GEL_MAX_VECTOR_LENGTH`, used instead of the
+/// default of [`Config::new`] and of the REPL
+pub fn env_vector_limit() -> Option<VectorLimit> {
+    *ENV_VECTOR_LIMIT
+}
+
+/// Emphasise only short lines. Long lines with bold look ugly.
+fn emphasize_error(text: &str, threshold: usize) -> bool {
+    threshold > 0 && text.len() <= threshold
+}
+
 #[doc(hidden)]
 pub fn write_error(line: impl fmt::Display) {
     let text = format!("{line:#}");
-    if text.len() > 60 {
-        msg!("{} {}", err_marker(), text);
-    } else {
-        // Emphasise only short lines. Long lines with bold look ugly.
+    if emphasize_error(&text, *ERROR_EMPHASIS) {
         msg!("{} {}", err_marker(), text.emphasized());
+    } else {
+        msg!("{} {}", err_marker(), text);
     }
 }
 
@@ -614,6 +1623,39 @@ pub fn success_msg(title: impl fmt::Display, msg: impl fmt::Display) {
     );
 }
 
+/// Structured counterpart of [`success!`] for scripts. With
+/// `--messages-format=json` prints `{"level": "success", ...}` with the
+/// fields of `value` as a single line to stderr, otherwise does nothing.
+/// The human-readable message should still be printed by the caller.
+///
+/// Fields by command:
+///
+/// * `instance create`: `command`, `instance` (the name, `org/name` for
+///   cloud instances);
+/// * `instance destroy`: `command`, `instance`;
+/// * `restore`: `command`, `databases` (a list of the restored databases
+///   or branches, a single one without `--all`).
+pub fn success_json(value: serde_json::Value) {
+    if messages_format() == MessagesFormat::Json {
+        msg!("{}", success_json_line(value));
+    }
+}
+
+fn success_json_line(value: serde_json::Value) -> String {
+    let mut line = serde_json::Map::new();
+    line.insert("level".into(), "success".into());
+    match value {
+        serde_json::Value::Object(fields) => {
+            line.extend(fields.into_iter().filter(|(key, _)| key != "level"));
+        }
+        serde_json::Value::Null => {}
+        value => {
+            line.insert("value".into(), value);
+        }
+    }
+    serde_json::Value::Object(line).to_string()
+}
+
 #[doc(hidden)]
 pub fn write_warn(line: impl fmt::Display) {
     msg!("{}", line.to_string().emphasized().warning());
@@ -0,0 +1,131 @@
+//! Writing query results as MessagePack (`--output-format=msgpack`).
+//!
+//! Every row is written as a single MessagePack value, prefixed with its
+//! length in bytes as a 4-byte big-endian integer, so that readers can split
+//! the stream without decoding it. Values are mapped similarly to JSON
+//! output:
+//!
+//! | Gel type                          | MessagePack type                 |
+//! |-----------------------------------|----------------------------------|
+//! | `bool`                            | bool                             |
+//! | `int16`, `int32`, `int64`         | int                              |
+//! | `float32`, `float64`              | float 32, float 64               |
+//! | `bytes`                           | bin                              |
+//! | `bigint`, `decimal`               | str, e.g. `"12.5"`               |
+//! | `json`                            | the decoded JSON value           |
+//! | objects, named tuples             | map of field names to values     |
+//! | sets, arrays, tuples, vectors     | array                            |
+//! | ranges                            | map, see below                   |
+//! | empty optional values             | nil                              |
+//! | everything else                   | str, as in JSON output           |
+//!
+//! `bigint` and `decimal` values are written in their decimal text
+//! representation, as MessagePack numbers can't hold them at full
+//! precision. Implicit fields of objects (`id` unless selected,
+//! `__tname__`) are skipped. A range is a map of `lower` and `upper` (nil
+//! if unbounded), `inc_lower`, `inc_upper` and `empty`.
+
+use bigdecimal::BigDecimal;
+use geozero::{ToWkt, wkb::Ewkb};
+use num_bigint::BigInt;
+use serde::ser::{Error as _, Serialize, SerializeMap, Serializer};
+
+use gel_protocol::value::Value;
+
+/// Encodes a row as a MessagePack value prefixed with its length
+pub fn encode_row(row: &Value) -> anyhow::Result<Vec<u8>> {
+    let data = rmp_serde::to_vec(&Msgpack(row))?;
+    let len = u32::try_from(data.len())
+        .map_err(|_| anyhow::anyhow!("row of {} bytes is too large", data.len()))?;
+    let mut out = Vec::with_capacity(data.len() + 4);
+    out.extend(len.to_be_bytes());
+    out.extend(data);
+    Ok(out)
+}
+
+/// Serializes a value with the mapping described in the module docs
+struct Msgpack<'a>(&'a Value);
+
+impl Serialize for Msgpack<'_> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        use Value as V;
+        match self.0 {
+            V::Nothing => ser.serialize_none(),
+            V::Bool(v) => ser.serialize_bool(*v),
+            V::Int16(v) => ser.serialize_i16(*v),
+            V::Int32(v) => ser.serialize_i32(*v),
+            V::Int64(v) => ser.serialize_i64(*v),
+            V::Float32(v) => ser.serialize_f32(*v),
+            V::Float64(v) => ser.serialize_f64(*v),
+            V::Bytes(v) => ser.serialize_bytes(v),
+            V::Str(v) => ser.serialize_str(v),
+            V::Uuid(v) => ser.collect_str(v),
+            V::BigInt(v) => ser.collect_str(&BigInt::from(v)),
+            V::Decimal(v) => ser.collect_str(&BigDecimal::from(v)),
+            V::Enum(v) => ser.serialize_str(v),
+            V::ConfigMemory(v) => ser.collect_str(v),
+            V::Datetime(v) => ser.collect_str(&format_args!("{v:?}")),
+            V::LocalDatetime(v) => ser.collect_str(&format_args!("{v:?}")),
+            V::LocalDate(v) => ser.collect_str(&format_args!("{v:?}")),
+            V::LocalTime(v) => ser.collect_str(&format_args!("{v:?}")),
+            V::Duration(v) => ser.collect_str(v),
+            V::RelativeDuration(v) => ser.collect_str(v),
+            V::DateDuration(v) => ser.collect_str(v),
+            V::Json(v) => serde_json::from_str::<serde_json::Value>(v)
+                .map_err(S::Error::custom)?
+                .serialize(ser),
+            V::Set(items) | V::Array(items) | V::Tuple(items) => {
+                ser.collect_seq(items.iter().map(Msgpack))
+            }
+            V::Vector(items) => ser.collect_seq(items),
+            V::Object { shape, fields } => ser.collect_map(
+                shape
+                    .elements
+                    .iter()
+                    .zip(fields)
+                    .filter(|(el, _)| !el.flag_implicit)
+                    .map(|(el, value)| (&el.name, value.as_ref().map(Msgpack))),
+            ),
+            V::SQLRow { shape, fields } => ser.collect_map(
+                shape
+                    .elements
+                    .iter()
+                    .zip(fields)
+                    .map(|(el, value)| (&el.name, value.as_ref().map(Msgpack))),
+            ),
+            V::NamedTuple { shape, fields } => ser.collect_map(
+                shape
+                    .elements
+                    .iter()
+                    .zip(fields)
+                    .map(|(el, value)| (&el.name, Msgpack(value))),
+            ),
+            V::SparseObject(obj) => ser.collect_map(obj.pairs().map(|(name, value)| {
+                let value = match value {
+                    Some(value) => Some(Msgpack(value)),
+                    None => None,
+                };
+                (name, value)
+            })),
+            V::Range(rng) => {
+                let mut map = ser.serialize_map(Some(5))?;
+                map.serialize_entry("lower", &rng.lower().map(|v| Msgpack(v)))?;
+                map.serialize_entry("upper", &rng.upper().map(|v| Msgpack(v)))?;
+                map.serialize_entry("inc_lower", &rng.inc_lower())?;
+                map.serialize_entry("inc_upper", &rng.inc_upper())?;
+                map.serialize_entry("empty", &rng.is_empty())?;
+                map.end()
+            }
+            V::PostGisGeometry(v) => ser.serialize_str(&ewkt(v)?),
+            V::PostGisGeography(v) => ser.serialize_str(&ewkt(v)?),
+            V::PostGisBox2d(v) => ser.serialize_str(&ewkt(v)?),
+            V::PostGisBox3d(v) => ser.serialize_str(&ewkt(v)?),
+        }
+    }
+}
+
+fn ewkt<E: serde::ser::Error>(value: impl AsRef<[u8]>) -> Result<String, E> {
+    Ewkb(value)
+        .to_ewkt(None)
+        .map_err(|e| E::custom(format!("invalid geometry: {e}")))
+}
@@ -2,6 +2,7 @@ use std::cmp::min;
 
 use bigdecimal::BigDecimal;
 use num_bigint::BigInt;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::print::buffer::Result;
 use crate::print::formatter::Formatter;
@@ -13,7 +14,31 @@ pub trait FormatExt {
     fn format<F: Formatter>(&self, prn: &mut F) -> Result<F::Error>;
 }
 
-pub fn format_string(s: &str, expanded: bool) -> String {
+/// Cuts `s` down to at most `limit` grapheme clusters. Returns the kept
+/// prefix and the number of graphemes that were dropped.
+pub fn truncate_string(s: &str, limit: usize) -> (&str, usize) {
+    let mut graphemes = s.grapheme_indices(true);
+    match graphemes.nth(limit) {
+        Some((idx, _)) => (&s[..idx], graphemes.count() + 1),
+        None => (s, 0),
+    }
+}
+
+pub fn truncation_marker(dropped: usize, ascii_only: bool) -> String {
+    let ellipsis = if ascii_only { "..." } else { "…" };
+    format!("{ellipsis}(+{dropped} chars)")
+}
+
+pub fn format_string(
+    s: &str,
+    expanded: bool,
+    max_length: Option<usize>,
+    ascii_only: bool,
+) -> String {
+    let (s, dropped) = match max_length {
+        Some(limit) => truncate_string(s, limit),
+        None => (s, 0),
+    };
     let mut buf = String::with_capacity(s.len() + 2);
     buf.push('\'');
     for c in s.chars() {
@@ -30,11 +55,14 @@ pub fn format_string(s: &str, expanded: bool) -> String {
             _ => buf.push(c),
         }
     }
+    if dropped > 0 {
+        buf.push_str(&truncation_marker(dropped, ascii_only));
+    }
     buf.push('\'');
     buf
 }
 
-fn format_bytes(bytes: &[u8]) -> String {
+pub fn format_bytes(bytes: &[u8]) -> String {
     use std::fmt::Write;
 
     let mut buf = String::with_capacity(bytes.len() + 3);
@@ -57,6 +85,54 @@ fn format_bytes(bytes: &[u8]) -> String {
     buf
 }
 
+pub fn format_bytes_base64(bytes: &[u8]) -> String {
+    use base64::Engine;
+
+    format!(
+        "b64'{}'",
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+pub fn format_bytes_hex(bytes: &[u8]) -> String {
+    format!("x'{}'", hex::encode(bytes))
+}
+
+/// Classic `hexdump -C` lines: offset, hex bytes in groups of eight and
+/// the printable ASCII characters. Fits as many groups per line as
+/// `max_width` allows (from one to four).
+pub fn hexdump_lines(bytes: &[u8], max_width: usize) -> Vec<String> {
+    use std::fmt::Write;
+
+    // a line with `8 * groups` bytes is `12 + 33 * groups` characters wide
+    let groups = (max_width.saturating_sub(12) / 33).clamp(1, 4);
+    let per_line = groups * 8;
+    let mut lines = Vec::with_capacity(bytes.len().div_ceil(per_line));
+    for (n, chunk) in bytes.chunks(per_line).enumerate() {
+        let mut line = format!("{:08x}  ", n * per_line);
+        for i in 0..per_line {
+            if i > 0 && i % 8 == 0 {
+                line.push(' ');
+            }
+            match chunk.get(i) {
+                Some(b) => write!(&mut line, "{b:02x} ").unwrap(),
+                None => line.push_str("   "),
+            }
+        }
+        line.push_str(" |");
+        for &b in chunk {
+            line.push(if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            });
+        }
+        line.push('|');
+        lines.push(line);
+    }
+    lines
+}
+
 fn format_bigint(bint: BigInt) -> String {
     let txt = bint.to_string();
     let no_zeros = txt.trim_end_matches('0');
@@ -95,8 +171,13 @@ impl FormatExt for Value {
         match self {
             V::Nothing => prn.const_uuid("Nothing"),
             V::Uuid(u) => prn.const_uuid(u),
-            V::Str(s) => prn.const_string(format_string(s, prn.expand_strings())),
-            V::Bytes(b) => prn.const_string(format_bytes(b)),
+            V::Str(s) => prn.const_string(format_string(
+                s,
+                prn.expand_strings(),
+                prn.max_string_length(),
+                prn.ascii_only(),
+            )),
+            V::Bytes(b) => prn.const_bytes(b),
             V::Int16(v) => prn.const_number(v),
             V::Int32(v) => prn.const_number(v),
             V::Int64(v) => prn.const_number(v),
@@ -113,7 +194,12 @@ impl FormatExt for Value {
             V::Duration(d) => prn.typed("duration", d.to_string()),
             V::RelativeDuration(d) => prn.typed("cal::relative_duration", d.to_string()),
             V::DateDuration(d) => prn.typed("cal::date_duration", d.to_string()),
+            V::Json(d) if prn.expand_json() => match serde_json::from_str::<serde_json::Value>(d) {
+                Ok(json) => prn.json(|prn| json.format(prn)),
+                Err(_) => prn.const_string(format!("{d:?}")),
+            },
             V::Json(d) => prn.const_string(format!("{d:?}")),
+            V::Set(items) if items.is_empty() => prn.empty_set(),
             V::Set(items) => prn.set(|prn| {
                 if let Some(limit) = prn.max_items() {
                     for item in &items[..min(limit, items.len())] {
@@ -149,7 +235,12 @@ impl FormatExt for Value {
                 prn.object(type_name, |prn| {
                     let mut n = 0;
                     for (fld, value) in shape.elements.iter().zip(fields) {
-                        if !fld.flag_implicit || prn.implicit_properties() {
+                        let shown = if fld.flag_link_property {
+                            prn.link_properties()
+                        } else {
+                            !fld.flag_implicit || prn.implicit_properties()
+                        };
+                        if shown {
                             if fld.flag_link_property {
                                 prn.object_field(&("@".to_owned() + &fld.name), true)?;
                             } else {
@@ -246,6 +337,7 @@ impl FormatExt for Value {
                     Ok(())
                 }),
                 VectorLimit::Auto => prn.auto_sized_vector(items),
+                VectorLimit::HeadTail(head, tail) => prn.head_tail_vector(items, head, tail),
             },
             V::Enum(v) => prn.const_enum(&**v),
             V::Range(rng) => {
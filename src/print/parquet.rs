@@ -0,0 +1,272 @@
+//! Writing query results to Parquet files (`--output-format=parquet`).
+//!
+//! Only flat object results are supported: every row must be an object
+//! with the same shape and every field a scalar. Links, multi properties,
+//! arrays and tuples should be projected away (or cast to `str`) in the
+//! query itself.
+//!
+//! The schema is built from the first row's shape; implicit fields (`id`
+//! unless selected, `__tname__`) are skipped. Column types are taken from
+//! the first non-empty value of each field in the first batch of rows:
+//!
+//! | Gel type                  | Arrow type                    |
+//! |---------------------------|-------------------------------|
+//! | `bool`                    | `Boolean`                     |
+//! | `int16`, `int32`, `int64` | `Int16`, `Int32`, `Int64`     |
+//! | `float32`, `float64`      | `Float32`, `Float64`          |
+//! | `bytes`                   | `Binary`                      |
+//! | `datetime`                | `Timestamp(Microsecond, UTC)` |
+//! | everything else           | `Utf8`                        |
+//!
+//! Values written as `Utf8` use their text representation, so `decimal`
+//! and `bigint` keep full precision, and `uuid`, `json`, enums, the `cal::`
+//! date and time types and durations are written as strings. Fields that
+//! are empty in the whole first batch become `Utf8` columns. All columns
+//! are nullable.
+
+use std::io::Write;
+use std::sync::Arc;
+
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::basic::Compression;
+use ::parquet::file::properties::WriterProperties;
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, Float32Array, Float64Array, Int16Array, Int32Array,
+    Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+
+use gel_protocol::value::Value;
+
+const BATCH_SIZE: usize = 8192;
+const FLAT_SHAPE_HINT: &str = "select a flat shape with scalar fields, \
+    e.g. `select User { name, email }`";
+
+struct Column {
+    index: usize,
+    name: String,
+    data_type: DataType,
+}
+
+/// Writes object rows to a Parquet file in batches of `BATCH_SIZE` rows.
+pub struct ParquetWriter<W: Write + Send> {
+    output: Option<W>,
+    writer: Option<ArrowWriter<W>>,
+    names: Option<Vec<String>>,
+    columns: Vec<Column>,
+    rows: Vec<Vec<Option<Value>>>,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    pub fn new(output: W) -> ParquetWriter<W> {
+        ParquetWriter {
+            output: Some(output),
+            writer: None,
+            names: None,
+            columns: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Value) -> anyhow::Result<()> {
+        let Value::Object { shape, fields } = row else {
+            anyhow::bail!(
+                "only object results can be written as parquet, \
+                 {FLAT_SHAPE_HINT}"
+            );
+        };
+        let names = shape.elements.iter().map(|el| el.name.as_str());
+        match &self.names {
+            Some(expected) => {
+                if !names.eq(expected.iter().map(String::as_str)) {
+                    anyhow::bail!(
+                        "rows have different shapes, which cannot be written as \
+                         parquet; {FLAT_SHAPE_HINT}"
+                    );
+                }
+            }
+            None => {
+                self.names = Some(names.map(String::from).collect());
+                self.columns = shape
+                    .elements
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, el)| !el.flag_implicit)
+                    .map(|(index, el)| Column {
+                        index,
+                        name: el.name.clone(),
+                        data_type: DataType::Utf8,
+                    })
+                    .collect();
+                if self.columns.is_empty() {
+                    anyhow::bail!("result objects have no fields to write; {FLAT_SHAPE_HINT}");
+                }
+            }
+        }
+        self.rows.push(fields);
+        if self.rows.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.writer.is_none() {
+            for column in &mut self.columns {
+                let first = self
+                    .rows
+                    .iter()
+                    .find_map(|row| non_empty(&row[column.index]));
+                if let Some(value) = first {
+                    column.data_type = data_type(&column.name, value)?;
+                }
+            }
+            let schema = Schema::new(
+                self.columns
+                    .iter()
+                    .map(|c| Field::new(&c.name, c.data_type.clone(), true))
+                    .collect::<Vec<_>>(),
+            );
+            let props = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let output = self.output.take().expect("writer is created once");
+            self.writer = Some(ArrowWriter::try_new(output, Arc::new(schema), Some(props))?);
+        }
+        let writer = self.writer.as_mut().expect("writer is created above");
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| build_array(c, &self.rows))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        writer.write(&RecordBatch::try_new(writer.schema().clone(), columns)?)?;
+        self.rows.clear();
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the file footer.
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        if self.names.is_none() {
+            anyhow::bail!("the query returned no rows, nothing to write as parquet");
+        }
+        if !self.rows.is_empty() {
+            self.flush()?;
+        }
+        let writer = self.writer.take().expect("at least one batch is written");
+        Ok(writer.into_inner()?)
+    }
+}
+
+fn non_empty(value: &Option<Value>) -> Option<&Value> {
+    match value {
+        None | Some(Value::Nothing) => None,
+        Some(Value::Set(items)) if items.is_empty() => None,
+        Some(value) => Some(value),
+    }
+}
+
+fn data_type(name: &str, value: &Value) -> anyhow::Result<DataType> {
+    use Value as V;
+    Ok(match value {
+        V::Bool(_) => DataType::Boolean,
+        V::Int16(_) => DataType::Int16,
+        V::Int32(_) => DataType::Int32,
+        V::Int64(_) => DataType::Int64,
+        V::Float32(_) => DataType::Float32,
+        V::Float64(_) => DataType::Float64,
+        V::Bytes(_) => DataType::Binary,
+        V::Datetime(_) => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        _ if text(value).is_some() => DataType::Utf8,
+        _ => anyhow::bail!(
+            "field {name:?} is not a scalar and cannot be written as parquet; \
+             {FLAT_SHAPE_HINT}"
+        ),
+    })
+}
+
+fn text(value: &Value) -> Option<String> {
+    use Value as V;
+    Some(match value {
+        V::Str(s) => s.clone(),
+        V::Uuid(u) => u.to_string(),
+        V::Decimal(v) => BigDecimal::from(v).to_string(),
+        V::BigInt(v) => BigInt::from(v).to_string(),
+        V::Json(j) => j.to_string(),
+        V::Enum(v) => v.to_string(),
+        V::ConfigMemory(m) => m.to_string(),
+        V::LocalDatetime(t) => format!("{t:?}"),
+        V::LocalDate(d) => format!("{d:?}"),
+        V::LocalTime(t) => format!("{t:?}"),
+        V::Duration(d) => d.to_string(),
+        V::RelativeDuration(d) => d.to_string(),
+        V::DateDuration(d) => d.to_string(),
+        _ => return None,
+    })
+}
+
+fn collect<A, T>(
+    column: &Column,
+    rows: &[Vec<Option<Value>>],
+    convert: impl Fn(&Value) -> Option<T>,
+) -> anyhow::Result<A>
+where
+    A: FromIterator<Option<T>>,
+{
+    rows.iter()
+        .map(|row| match non_empty(&row[column.index]) {
+            None => Ok(None),
+            Some(value) => convert(value).map(Some).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "field {:?} has values of different types, \
+                     expected {} but got {value:?}",
+                    column.name,
+                    column.data_type,
+                )
+            }),
+        })
+        .collect()
+}
+
+fn build_array(column: &Column, rows: &[Vec<Option<Value>>]) -> anyhow::Result<ArrayRef> {
+    use Value as V;
+    Ok(match &column.data_type {
+        DataType::Boolean => Arc::new(collect::<BooleanArray, _>(column, rows, |v| match v {
+            V::Bool(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Int16 => Arc::new(collect::<Int16Array, _>(column, rows, |v| match v {
+            V::Int16(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Int32 => Arc::new(collect::<Int32Array, _>(column, rows, |v| match v {
+            V::Int32(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Int64 => Arc::new(collect::<Int64Array, _>(column, rows, |v| match v {
+            V::Int64(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Float32 => Arc::new(collect::<Float32Array, _>(column, rows, |v| match v {
+            V::Float32(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Float64 => Arc::new(collect::<Float64Array, _>(column, rows, |v| match v {
+            V::Float64(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Binary => Arc::new(collect::<BinaryArray, _>(column, rows, |v| match v {
+            V::Bytes(v) => Some(v.to_vec()),
+            _ => None,
+        })?),
+        DataType::Timestamp(..) => Arc::new(
+            collect::<TimestampMicrosecondArray, _>(column, rows, |v| match v {
+                V::Datetime(v) => Some(v.to_unix_micros()),
+                _ => None,
+            })?
+            .with_timezone("UTC"),
+        ),
+        _ => Arc::new(collect::<StringArray, _>(column, rows, text)?),
+    })
+}
@@ -0,0 +1,133 @@
+//! Serializable view of [`Config`] for tools wrapping the CLI, see
+//! `cli print-config-schema`.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::print::{BytesFormat, Config, JsonStyle};
+use crate::repl::VectorLimit;
+
+/// Print settings, one field per [`Config`] option
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub struct Settings {
+    /// Colorize the output; unset means detect from the terminal
+    pub colors: Option<bool>,
+    /// Indentation of nested values, in spaces
+    pub indent: usize,
+    /// Print strings with escapes expanded
+    pub expand_strings: bool,
+    /// Truncate strings longer than this many characters
+    pub max_string_length: Option<usize>,
+    /// Also truncate strings in JSON output
+    pub truncate_json_strings: bool,
+    /// Maximum line width; unset means the terminal width
+    pub max_width: Option<usize>,
+    /// Print implicit properties (`id`, `__tname__`) of objects
+    pub implicit_properties: bool,
+    /// Print link properties of objects, as `@name`
+    pub link_properties: bool,
+    /// Print at most this many items of a set or array
+    pub max_items: Option<usize>,
+    /// Print this many first and last rows, hiding the ones in between
+    pub items_window: Option<(usize, usize)>,
+    /// Maximum number of vector elements: `unlimited`, `auto`, a number or
+    /// `HEAD..TAIL` for the first and last elements
+    #[serde(with = "vector_limit")]
+    #[schemars(with = "String")]
+    pub max_vector_length: VectorLimit,
+    /// Rendering of `bytes` values in native output
+    pub bytes_format: BytesFormatName,
+    /// Render `json` values as nested structures
+    pub expand_json: bool,
+    /// Text printed for an empty optional value
+    pub null_text: Option<String>,
+    /// Text printed for an empty set
+    pub empty_set_text: Option<String>,
+    /// Stop after this many lines of output
+    pub max_output_lines: Option<usize>,
+    /// Print a comma after the last item of multi-line collections
+    pub trailing_comma: Option<bool>,
+    /// Use only ASCII characters in tables and markers
+    pub ascii_only: Option<bool>,
+    /// Wrap JSON output in an object with the rows under this key
+    pub json_envelope: Option<String>,
+    /// Add the row count to the JSON envelope
+    pub json_envelope_count: bool,
+    /// Layout of JSON output: `pretty` or `compact`
+    pub json_style: JsonStyle,
+    /// Print each query, syntax highlighted, above its result
+    pub echo_query: bool,
+    /// Print a `(no rows)` note to stderr when a result is empty
+    pub empty_result_note: bool,
+}
+
+/// Serializable counterpart of [`BytesFormat`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum BytesFormatName {
+    Escaped,
+    Base64,
+    Hex,
+    HexDump,
+}
+
+impl From<BytesFormat> for BytesFormatName {
+    fn from(value: BytesFormat) -> BytesFormatName {
+        match value {
+            BytesFormat::Escaped => BytesFormatName::Escaped,
+            BytesFormat::Base64 => BytesFormatName::Base64,
+            BytesFormat::Hex => BytesFormatName::Hex,
+            BytesFormat::HexDump => BytesFormatName::HexDump,
+        }
+    }
+}
+
+impl From<&Config> for Settings {
+    fn from(config: &Config) -> Settings {
+        Settings {
+            colors: config.colors,
+            indent: config.indent,
+            expand_strings: config.expand_strings,
+            max_string_length: config.max_string_length,
+            truncate_json_strings: config.truncate_json_strings,
+            max_width: config.max_width,
+            implicit_properties: config.implicit_properties,
+            link_properties: config.link_properties,
+            max_items: config.max_items,
+            items_window: config.items_window,
+            max_vector_length: config.max_vector_length,
+            bytes_format: config.bytes_format.into(),
+            expand_json: config.expand_json,
+            null_text: config.null_text.clone(),
+            empty_set_text: config.empty_set_text.clone(),
+            max_output_lines: config.max_output_lines,
+            trailing_comma: config.trailing_comma,
+            ascii_only: config.ascii_only,
+            json_envelope: config.json_envelope.clone(),
+            json_envelope_count: config.json_envelope_count,
+            json_style: config.json_style,
+            echo_query: config.echo_query,
+            empty_result_note: config.empty_result_note,
+        }
+    }
+}
+
+/// JSON schema of [`Settings`]
+pub fn schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(Settings)).expect("schema is serializable")
+}
+
+mod vector_limit {
+    use serde::{Deserialize, Deserializer, Serializer, de};
+
+    use crate::repl::VectorLimit;
+
+    pub fn serialize<S: Serializer>(value: &VectorLimit, ser: S) -> Result<S::Ok, S::Error> {
+        ser.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<VectorLimit, D::Error> {
+        String::deserialize(de)?.parse().map_err(de::Error::custom)
+    }
+}
@@ -3,7 +3,11 @@ use std::io::{self, Write};
 
 use super::Stdout;
 
-pub(in crate::print) trait Output {
+/// A sink for rendered output. Implemented for `&mut String`, buffered
+/// writers and stdout; implement it to render into other destinations via [`native_to_output`].
+///
+/// [`native_to_output`]: crate::print::native_to_output
+pub trait Output {
     type Error;
     fn write(&mut self, data: &str) -> Result<(), Self::Error>;
 }
@@ -23,3 +27,10 @@ impl Output for Stdout {
         Ok(())
     }
 }
+
+impl<W: Write> Output for &mut io::BufWriter<W> {
+    type Error = io::Error;
+    fn write(&mut self, data: &str) -> Result<(), io::Error> {
+        self.write_all(data.as_bytes())
+    }
+}
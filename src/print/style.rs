@@ -27,6 +27,31 @@ pub enum Style {
     Error,
 }
 
+impl Style {
+    pub const ALL: &'static [Style] = &[
+        Style::Decorator,
+        Style::Comment,
+        Style::String,
+        Style::Number,
+        Style::Boolean,
+        Style::UUID,
+        Style::Enum,
+        Style::Cast,
+        Style::Set,
+        Style::Array,
+        Style::Tuple,
+        Style::TupleField,
+        Style::Object,
+        Style::LinkProperty,
+        Style::Pointer,
+        Style::Punctuation,
+        Style::Keyword,
+        Style::Operator,
+        Style::BackslashCommand,
+        Style::Error,
+    ];
+}
+
 #[derive(Debug, Clone)]
 pub struct Styler;
 
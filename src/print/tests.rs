@@ -10,10 +10,10 @@ use tokio_stream::Stream;
 
 use crate::print::native::FormatExt;
 use crate::print::style::Styler;
-use crate::print::{self, _native_format, Config};
+use crate::print::{self, _native_format, BytesFormat, Config, ConfigOverrides, JsonStyle};
 use crate::repl::VectorLimit;
 use gel_protocol::codec::{ObjectShape, ShapeElement};
-use gel_protocol::model::Datetime;
+use gel_protocol::model::{Datetime, Json};
 use gel_protocol::value::Value;
 
 struct UnfusedStream<'a, I>(Option<&'a [I]>);
@@ -56,6 +56,7 @@ fn test_format_cfg<I: FormatExt + Clone + Send + Sync>(
             config,
             config.max_width.unwrap_or(80),
             false,
+            config.ascii_only.unwrap_or(false),
             &mut out,
         ))
         .unwrap();
@@ -69,10 +70,26 @@ fn test_format<I: FormatExt + Clone + Send + Sync>(items: &[I]) -> Result<String
             colors: Some(false),
             indent: 2,
             expand_strings: false,
+            max_string_length: None,
+            truncate_json_strings: false,
             max_width: Some(80),
             implicit_properties: false,
+            link_properties: true,
             max_items: None,
+            items_window: None,
             max_vector_length: VectorLimit::Unlimited,
+            bytes_format: BytesFormat::Escaped,
+            expand_json: false,
+            null_text: None,
+            empty_set_text: None,
+            max_output_lines: None,
+            trailing_comma: None,
+            ascii_only: None,
+            json_envelope: None,
+            json_envelope_count: false,
+            json_style: JsonStyle::Pretty,
+            echo_query: false,
+            empty_result_note: false,
             styler: Styler::new(),
         },
     )
@@ -215,6 +232,50 @@ fn array_ellipsis() {
     );
 }
 
+#[test]
+fn items_window() {
+    let rows = (0..100).map(Value::Int64).collect::<Vec<_>>();
+    assert_eq!(
+        test_format_cfg(&rows, Config::new().items_window(Some((3, 3)))).unwrap(),
+        "{0, 1, 2, ..., 97, 98, 99}"
+    );
+    let expected = r###"{
+  0,
+  1,
+  2,
+  ... (94 hidden)
+  97,
+  98,
+  99,
+}"###;
+    // flow is disabled at the ellipsis, after the whole result is read
+    assert_eq!(
+        test_format_cfg(
+            &rows,
+            Config::new().items_window(Some((3, 3))).max_width(10)
+        )
+        .unwrap(),
+        expected
+    );
+    // flow is disabled within the first rows
+    assert_eq!(
+        test_format_cfg(&rows, Config::new().items_window(Some((3, 3))).max_width(4)).unwrap(),
+        expected
+    );
+    assert_eq!(
+        test_format_cfg(
+            &rows,
+            Config::new().items_window(Some((3, 0))).max_items(Some(1))
+        )
+        .unwrap(),
+        "{0, 1, 2, ...}"
+    );
+    assert_eq!(
+        test_format_cfg(&rows[..5], Config::new().items_window(Some((3, 3)))).unwrap(),
+        "{0, 1, 2, 3, 4}"
+    );
+}
+
 #[test]
 fn set_ellipsis() {
     assert_eq!(
@@ -290,6 +351,47 @@ fn vector() {
     );
 }
 
+#[test]
+fn vector_head_tail() {
+    use crate::repl::VectorLimit::*;
+    let long = Value::Vector((0..1536).map(|v| v as _).collect());
+    assert_eq!(
+        test_format_cfg(
+            &[long.clone()],
+            Config::new().max_vector_length(HeadTail(2, 2))
+        )
+        .unwrap(),
+        "{<ext::pgvector::vector>[0, 1, ..., 1534, 1535] (1536 dims)}",
+    );
+    assert_eq!(
+        test_format_cfg(
+            &[long.clone()],
+            Config::new().max_vector_length(HeadTail(0, 1))
+        )
+        .unwrap(),
+        "{<ext::pgvector::vector>[..., 1535] (1536 dims)}",
+    );
+    assert_eq!(
+        test_format_cfg(
+            &[long],
+            Config::new()
+                .max_width(30)
+                .max_vector_length(HeadTail(2, 2))
+        )
+        .unwrap(),
+        "{\n  <ext::pgvector::vector>[\n    0,\n    1,\n    ... (1532 hidden)\n    1534,\n    1535,\n  ] (1536 dims),\n}",
+    );
+    // short vectors are shown in full
+    assert_eq!(
+        test_format_cfg(
+            &[Value::Vector((0..4).map(|v| v as _).collect())],
+            Config::new().max_vector_length(HeadTail(2, 2))
+        )
+        .unwrap(),
+        "{<ext::pgvector::vector>[0, 1, 2, 3] (4 dims)}",
+    );
+}
+
 #[test]
 fn wrap() {
     assert_eq!(
@@ -416,6 +518,39 @@ fn link_property() {
     );
 }
 
+#[test]
+fn link_property_weight() {
+    // `select User.friends { name, @weight }`
+    let element = |name: &str, flag_link_property| ShapeElement {
+        flag_implicit: false,
+        flag_link_property,
+        flag_link: false,
+        cardinality: None,
+        name: name.into(),
+    };
+    let shape = ObjectShape::new(vec![element("name", false), element("weight", true)]);
+    let friend = |name: &str, weight| Value::Object {
+        shape: shape.clone(),
+        fields: vec![Some(Value::Str(name.into())), Some(Value::Int64(weight))],
+    };
+    let friends = [friend("Bob", 3), friend("Carol", 5)];
+    let mut config = Config::new();
+    config.max_width(60);
+    assert_eq!(
+        test_format_cfg(&friends, &config).unwrap(),
+        r###"{
+  Object {name: 'Bob', @weight: 3},
+  Object {name: 'Carol', @weight: 5},
+}"###
+    );
+
+    config.link_properties(false);
+    assert_eq!(
+        test_format_cfg(&friends, &config).unwrap(),
+        "{Object {name: 'Bob'}, Object {name: 'Carol'}}"
+    );
+}
+
 #[test]
 fn str() {
     assert_eq!(
@@ -448,6 +583,56 @@ fn str() {
     );
 }
 
+#[test]
+fn str_truncated() {
+    let cfg = Config::new().max_string_length(Some(3)).clone();
+    assert_eq!(
+        test_format_cfg(&[Value::Str("hello".into())], &cfg).unwrap(),
+        "{'hel…(+2 chars)'}"
+    );
+    assert_eq!(
+        test_format_cfg(&[Value::Str("hel".into())], &cfg).unwrap(),
+        "{'hel'}"
+    );
+    // multi-byte characters count as one
+    assert_eq!(
+        test_format_cfg(&[Value::Str("привет".into())], &cfg).unwrap(),
+        "{'при…(+3 chars)'}"
+    );
+    // never split a grapheme cluster
+    assert_eq!(
+        test_format_cfg(&[Value::Str("ab👩‍👩‍👧e\u{301}x".into())], &cfg).unwrap(),
+        "{'ab👩‍👩‍👧…(+2 chars)'}"
+    );
+    assert_eq!(
+        test_format_cfg(
+            &[Value::Str("e\u{301}e\u{301}e\u{301}e\u{301}".into())],
+            &cfg
+        )
+        .unwrap(),
+        "{'e\u{301}e\u{301}e\u{301}…(+1 chars)'}"
+    );
+}
+
+#[test]
+fn json_str_truncated() {
+    let items = [serde_json::json!("hello"), serde_json::json!("hi")];
+    assert_eq!(
+        print::json_to_string(&items, Config::new().max_string_length(Some(3))).unwrap(),
+        r#"["hello", "hi"]"#
+    );
+    assert_eq!(
+        print::json_to_string(
+            &items,
+            Config::new()
+                .max_string_length(Some(3))
+                .truncate_json_strings(true)
+        )
+        .unwrap(),
+        r#"["hel…(+2 chars)", "hi"]"#
+    );
+}
+
 #[test]
 fn bytes() {
     assert_eq!(
@@ -464,6 +649,85 @@ fn bytes() {
     );
 }
 
+#[test]
+fn bytes_formats() {
+    let bytes = [Value::Bytes(Bytes::from_static(b"hi\x00\xff"))];
+    assert_eq!(
+        test_format_cfg(&bytes, Config::new().bytes_format(BytesFormat::Base64)).unwrap(),
+        "{b64'aGkA/w=='}"
+    );
+    assert_eq!(
+        test_format_cfg(&bytes, Config::new().bytes_format(BytesFormat::Hex)).unwrap(),
+        "{x'686900ff'}"
+    );
+
+    let bytes = [Value::Bytes(Bytes::from_static(
+        b"hello\x00\x01\xff world!",
+    ))];
+    assert_eq!(
+        test_format_cfg(
+            &bytes,
+            Config::new()
+                .max_width(80)
+                .bytes_format(BytesFormat::HexDump)
+        )
+        .unwrap(),
+        r###"{
+  hexdump(
+    00000000  68 65 6c 6c 6f 00 01 ff  |hello...|
+    00000008  20 77 6f 72 6c 64 21     | world!|
+  ),
+}"###
+    );
+    assert_eq!(
+        test_format_cfg(
+            &bytes,
+            Config::new()
+                .max_width(200)
+                .bytes_format(BytesFormat::HexDump)
+        )
+        .unwrap(),
+        format!(
+            "{{\n  hexdump(\n    \
+             00000000  68 65 6c 6c 6f 00 01 ff  20 77 6f 72 6c 64 21{}|hello... world!|\n  \
+             ),\n}}",
+            " ".repeat(55)
+        )
+    );
+}
+
+#[test]
+fn json_expanded() {
+    let shape = ObjectShape::new(vec![ShapeElement {
+        flag_implicit: false,
+        flag_link_property: false,
+        flag_link: false,
+        cardinality: None,
+        name: "data".into(),
+    }]);
+    let items = [Value::Object {
+        shape,
+        fields: vec![Some(Value::Json(Json::new_unchecked(
+            r#"{"a": 1, "b": {"c": [true]}}"#.into(),
+        )))],
+    }];
+    assert_eq!(
+        test_format_cfg(&items, Config::new().max_width(80).expand_json(true)).unwrap(),
+        r###"{Object {data: <json>{"a": 1, "b": {"c": [true]}}}}"###
+    );
+    assert_eq!(
+        test_format_cfg(&items, Config::new().max_width(30).expand_json(true)).unwrap(),
+        r###"{
+  Object {
+    data: <json>{
+      "a": 1,
+      "b": {"c": [true]},
+    },
+  },
+}"###
+    );
+}
+
 #[test]
 fn all_widths() {
     let shape = ObjectShape::new(vec![ShapeElement {
@@ -497,7 +761,7 @@ fn all_widths_vec_obj() {
         cardinality: None,
         name: "field1".into(),
     }]);
-    for mvec in [Auto, Unlimited, Fixed(8), Fixed(35)] {
+    for mvec in [Auto, Unlimited, Fixed(8), Fixed(35), HeadTail(3, 3)] {
         for width in 0..100 {
             test_format_cfg(
                 &[Value::Object {
@@ -514,7 +778,7 @@ fn all_widths_vec_obj() {
 #[test]
 fn all_widths_vec() {
     use crate::repl::VectorLimit::*;
-    for mvec in [Auto, Unlimited, Fixed(8), Fixed(35)] {
+    for mvec in [Auto, Unlimited, Fixed(8), Fixed(35), HeadTail(3, 3)] {
         for width in 0..100 {
             test_format_cfg(
                 &[Value::Vector((0..200).map(|v| v as _).collect())],
@@ -725,3 +989,1077 @@ fn postgis_box_3d() {
         r###"{POLYGON((1 1 3,2 1 3,2 2 3,1 2 3,1 1 3))}"###
     );
 }
+
+#[test]
+fn auto_layout() {
+    use print::AutoLayout;
+
+    let shape = ObjectShape::new(vec![ShapeElement {
+        flag_implicit: false,
+        flag_link_property: false,
+        flag_link: false,
+        cardinality: None,
+        name: "field1".into(),
+    }]);
+    let other_shape = ObjectShape::new(vec![ShapeElement {
+        flag_implicit: false,
+        flag_link_property: false,
+        flag_link: false,
+        cardinality: None,
+        name: "field2".into(),
+    }]);
+    let flat = Value::Object {
+        shape: shape.clone(),
+        fields: vec![Some(Value::Int32(10))],
+    };
+    let nested = Value::Object {
+        shape: shape.clone(),
+        fields: vec![Some(Value::Array(vec![Value::Int32(10)]))],
+    };
+    let other = Value::Object {
+        shape: other_shape,
+        fields: vec![None],
+    };
+
+    assert_eq!(print::auto_layout(&[Value::Int32(1)]), AutoLayout::Raw);
+    assert_eq!(print::auto_layout(&[flat.clone()]), AutoLayout::Vertical);
+    assert_eq!(
+        print::auto_layout(&[flat.clone(), flat.clone()]),
+        AutoLayout::Table
+    );
+    assert_eq!(
+        print::auto_layout(&[flat.clone(), nested]),
+        AutoLayout::Native
+    );
+    assert_eq!(print::auto_layout(&[flat, other]), AutoLayout::Native);
+    assert_eq!(
+        print::auto_layout(&[Value::Int32(1), Value::Int32(2)]),
+        AutoLayout::Native
+    );
+    assert_eq!(
+        print::auto_layout(&[Value::Array(vec![Value::Int32(1)])]),
+        AutoLayout::Native
+    );
+    assert_eq!(print::auto_layout(&[]), AutoLayout::Native);
+}
+
+#[test]
+fn kv_layout() {
+    let element = |name: &str, implicit| ShapeElement {
+        flag_implicit: implicit,
+        flag_link_property: false,
+        flag_link: false,
+        cardinality: None,
+        name: name.into(),
+    };
+    let shape = ObjectShape::new(vec![
+        element("id", true),
+        element("name", false),
+        element("email_address", false),
+        element("age", false),
+    ]);
+    let user = Value::Object {
+        shape,
+        fields: vec![
+            Some(Value::Int32(1)),
+            Some(Value::Str("Alice".into())),
+            Some(Value::Str("alice@example.com".into())),
+            None,
+        ],
+    };
+    assert_eq!(
+        print::kv_to_string(&user, &Config::new(), 80, false).unwrap(),
+        "\
+name          = 'Alice'
+email_address = 'alice@example.com'
+age           = {}
+"
+    );
+    assert_eq!(
+        print::kv_to_string(&user, Config::new().implicit_properties(true), 80, false).unwrap(),
+        "\
+id            = 1
+name          = 'Alice'
+email_address = 'alice@example.com'
+age           = {}
+"
+    );
+
+    // values that don't fit are indented past the `=`
+    let tags = ["alpha", "beta", "gamma", "delta", "epsilon"];
+    let post = Value::Object {
+        shape: ObjectShape::new(vec![element("name", false), element("tags", false)]),
+        fields: vec![
+            Some(Value::Str("Alice".into())),
+            Some(Value::Array(
+                tags.iter().map(|t| Value::Str(t.to_string())).collect(),
+            )),
+        ],
+    };
+    assert_eq!(
+        print::kv_to_string(&post, &Config::new(), 30, false).unwrap(),
+        "\
+name = 'Alice'
+tags = [
+         'alpha',
+         'beta',
+         'gamma',
+         'delta',
+         'epsilon',
+       ]
+"
+    );
+    assert_eq!(
+        print::kv_to_string(&post, &Config::new(), 80, false).unwrap(),
+        "\
+name = 'Alice'
+tags = ['alpha', 'beta', 'gamma', 'delta', 'epsilon']
+"
+    );
+
+    assert_eq!(
+        print::kv_to_string(&Value::Int32(1), &Config::new(), 80, false),
+        None
+    );
+}
+
+#[test]
+fn pager_precedence() {
+    assert_eq!(
+        print::choose_pager(Some("most".into()), Some("less".into())),
+        "most"
+    );
+    assert_eq!(print::choose_pager(None, Some("less".into())), "less");
+    assert!(!print::choose_pager(None, None).is_empty());
+}
+
+#[test]
+fn pager_fallback() {
+    let exists = |bin: &str| bin == "less";
+    assert_eq!(
+        print::resolve_pager("less -R", exists),
+        Some(vec!["less".to_string(), "-R".to_string()])
+    );
+    assert_eq!(print::resolve_pager("", exists), None);
+    assert_eq!(print::resolve_pager("  ", exists), None);
+    assert_eq!(print::resolve_pager("cat", exists), None);
+    assert_eq!(print::resolve_pager("no-such-pager -x", exists), None);
+}
+
+#[test]
+fn pager_screen_lines() {
+    assert_eq!(print::screen_lines("", 80), 0);
+    assert_eq!(print::screen_lines("a\n\nb\n", 80), 3);
+    assert_eq!(print::screen_lines(&"x".repeat(81), 80), 2);
+    assert_eq!(print::screen_lines(&"x".repeat(80), 80), 1);
+}
+
+#[test]
+fn columns_narrow_terminal() {
+    use crate::print::columns::column_widths;
+
+    let headers = ["ID", "Name", "Scopes"];
+    let rows = vec![
+        vec!["1".into(), "short".into(), "a".into()],
+        vec!["2".into(), "a much longer name".into(), "b, c".into()],
+    ];
+    // everything fits
+    assert_eq!(column_widths(&headers, &rows, 80), [2, 18, 6]);
+    // the widest column gives up space first
+    assert_eq!(column_widths(&headers, &rows, 30), [2, 12, 6]);
+    // columns never get narrower than the minimum
+    assert_eq!(column_widths(&headers, &rows, 5), [2, 3, 3]);
+
+    assert_eq!(
+        print::table(&headers, &rows).render_with(30, false, false),
+        "\
+┌────┬──────────────┬────────┐
+│ ID │ Name         │ Scopes │
+├────┼──────────────┼────────┤
+│ 1  │ short        │ a      │
+│ 2  │ a much long… │ b, c   │
+└────┴──────────────┴────────┘
+"
+    );
+}
+
+#[test]
+fn color_choice() {
+    use crate::options::color_choice_from_args;
+    use crate::print::{ColorChoice, resolve_color};
+
+    for terminal in [true, false] {
+        assert_eq!(resolve_color(ColorChoice::Auto, || terminal), terminal);
+        assert!(resolve_color(ColorChoice::Always, || terminal));
+        assert!(!resolve_color(ColorChoice::Never, || terminal));
+    }
+
+    let args = |a: &[&str]| {
+        a.iter()
+            .map(Into::into)
+            .collect::<Vec<std::ffi::OsString>>()
+    };
+    assert_eq!(
+        color_choice_from_args(args(&["gel", "--color", "never", "query"])),
+        Some(ColorChoice::Never)
+    );
+    assert_eq!(
+        color_choice_from_args(args(&["gel", "instance", "list", "--color=always"])),
+        Some(ColorChoice::Always)
+    );
+    assert_eq!(color_choice_from_args(args(&["gel", "query"])), None);
+    assert_eq!(
+        color_choice_from_args(args(&["gel", "query", "--", "--color=never"])),
+        None
+    );
+}
+
+#[test]
+fn json_item_narrow() {
+    let mut item = serde_json::json!({"key": "a rather long string value", "list": [1, 2, 3]});
+    for _ in 0..20 {
+        item = serde_json::json!({"nested": item, "tail": ["x", "y"]});
+    }
+    for width in 0..10 {
+        let out = print::json_item_to_string(&item, Config::new().max_width(width)).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&out).unwrap(),
+            item
+        );
+    }
+    let item = serde_json::json!("a single scalar longer than the width");
+    assert_eq!(
+        print::json_item_to_string(&item, Config::new().max_width(1)).unwrap(),
+        "\"a single scalar longer than the width\""
+    );
+}
+
+#[test]
+fn edgeql_literals() {
+    use edgeql_parser::helpers::quote_string;
+    use print::edgeql::literal;
+
+    let lit = |v: Value| literal(&v).unwrap();
+    assert_eq!(
+        lit(Value::Str("it's \"x\"".into())),
+        quote_string("it's \"x\"")
+    );
+    assert_eq!(
+        lit(Value::Bytes(Bytes::from_static(b"a\x00\xff"))),
+        r"b'\x61\x00\xff'"
+    );
+    assert_eq!(lit(Value::Int16(-3)), "<int16>-3");
+    assert_eq!(lit(Value::Int32(7)), "<int32>7");
+    assert_eq!(lit(Value::Int64(42)), "42");
+    assert_eq!(lit(Value::Float32(1.0)), "<float32>1.0");
+    assert_eq!(lit(Value::Float64(0.5)), "<float64>0.5");
+    assert_eq!(
+        lit(Value::Float64(f64::NAN)),
+        format!("<float64>{}", quote_string("NaN"))
+    );
+    assert_eq!(lit(Value::Bool(true)), "true");
+    assert_eq!(
+        lit(Value::Decimal(
+            TryFrom::try_from(BigDecimal::from_str("1.25").unwrap()).unwrap()
+        )),
+        format!("<decimal>{}", quote_string("1.25"))
+    );
+    assert_eq!(
+        lit(Value::Datetime(Datetime::from_unix_micros(
+            1604506938347258
+        ))),
+        format!("<datetime>{}", quote_string("2020-11-04T16:22:18.347258Z"))
+    );
+    assert_eq!(
+        lit(Value::Json(Json::new_unchecked("{\"a\": 1}".into()))),
+        format!("to_json({})", quote_string("{\"a\": 1}"))
+    );
+    assert_eq!(
+        lit(Value::Array(vec![Value::Int64(1), Value::Int64(2)])),
+        "[1, 2]"
+    );
+    assert_eq!(lit(Value::Tuple(vec![Value::Bool(false)])), "(false,)");
+    assert_eq!(lit(Value::Set(vec![])), "{}");
+}
+
+#[test]
+fn edgeql_insert() {
+    let element = |name: &str, flag_implicit: bool, flag_link: bool| ShapeElement {
+        flag_implicit,
+        flag_link_property: false,
+        flag_link,
+        cardinality: None,
+        name: name.into(),
+    };
+    let target_shape = ObjectShape::new(vec![
+        element("__tname__", true, false),
+        element("id", true, false),
+    ]);
+    let shape = ObjectShape::new(vec![
+        element("__tname__", true, false),
+        element("id", true, false),
+        element("age", false, false),
+        element("nickname", false, false),
+        element("friend", false, true),
+    ]);
+    let id = "8a2c0c34-7aa0-11ee-8d8e-7f8b5ad2c7a0".parse().unwrap();
+    let row = Value::Object {
+        shape,
+        fields: vec![
+            Some(Value::Str("default::User".into())),
+            Some(Value::Uuid(id)),
+            Some(Value::Int64(30)),
+            None,
+            Some(Value::Object {
+                shape: target_shape,
+                fields: vec![
+                    Some(Value::Str("default::User".into())),
+                    Some(Value::Uuid(id)),
+                ],
+            }),
+        ],
+    };
+    assert_eq!(
+        print::edgeql::format_row(&row).unwrap(),
+        "INSERT default::User { age := 30, friend := \
+         (SELECT default::User FILTER .id = \
+         <uuid>'8a2c0c34-7aa0-11ee-8d8e-7f8b5ad2c7a0') };"
+    );
+    assert_eq!(
+        print::edgeql::format_row(&Value::Int64(1)).unwrap(),
+        "SELECT 1;"
+    );
+}
+
+#[test]
+#[cfg(feature = "parquet")]
+fn parquet_flat_objects() {
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use arrow_array::{Array, Int64Array, StringArray, TimestampMicrosecondArray};
+    use arrow_schema::{DataType, TimeUnit};
+    use print::parquet::ParquetWriter;
+
+    let element = |name: &str, flag_implicit: bool| ShapeElement {
+        flag_implicit,
+        flag_link_property: false,
+        flag_link: false,
+        cardinality: None,
+        name: name.into(),
+    };
+    let shape = ObjectShape::new(vec![
+        element("id", true),
+        element("name", false),
+        element("age", false),
+        element("created", false),
+    ]);
+    let id = "8a2c0c34-7aa0-11ee-8d8e-7f8b5ad2c7a0".parse().unwrap();
+    let row = |name: &str, age: Option<i64>| Value::Object {
+        shape: shape.clone(),
+        fields: vec![
+            Some(Value::Uuid(id)),
+            Some(Value::Str(name.into())),
+            age.map(Value::Int64),
+            Some(Value::Datetime(Datetime::from_unix_micros(
+                1604506938347258,
+            ))),
+        ],
+    };
+
+    let mut writer = ParquetWriter::new(Vec::new());
+    writer.push(row("alice", None)).unwrap();
+    writer.push(row("bob", Some(30))).unwrap();
+    let data = writer.finish().unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(data))
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    let schema = batch.schema();
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|f| (f.name().as_str(), f.data_type().clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            ("name", DataType::Utf8),
+            ("age", DataType::Int64),
+            (
+                "created",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+            ),
+        ]
+    );
+    let names = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(names.value(1), "bob");
+    let ages = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert!(ages.is_null(0));
+    assert_eq!(ages.value(1), 30);
+    let created = batch
+        .column(2)
+        .as_any()
+        .downcast_ref::<TimestampMicrosecondArray>()
+        .unwrap();
+    assert_eq!(created.value(0), 1604506938347258);
+
+    let mut writer = ParquetWriter::new(Vec::new());
+    writer.push(row("alice", None)).unwrap();
+    let other = Value::Object {
+        shape: ObjectShape::new(vec![element("title", false)]),
+        fields: vec![Some(Value::Str("x".into()))],
+    };
+    let err = writer.push(other).unwrap_err();
+    assert!(err.to_string().contains("different shapes"), "{err}");
+    assert!(
+        ParquetWriter::new(Vec::new())
+            .push(Value::Int64(1))
+            .is_err()
+    );
+    assert!(ParquetWriter::new(Vec::new()).finish().is_err());
+}
+
+#[test]
+fn null_text() {
+    use crate::outputs::tab_separated;
+
+    let shape = ObjectShape::new(vec![ShapeElement {
+        flag_implicit: false,
+        flag_link_property: false,
+        flag_link: false,
+        cardinality: None,
+        name: "field".into(),
+    }]);
+    let objects = [
+        Value::Object {
+            shape: shape.clone(),
+            fields: vec![None],
+        },
+        Value::Object {
+            shape: shape.clone(),
+            fields: vec![Some(Value::Set(vec![]))],
+        },
+    ];
+    let cell_text = |config: &Config| {
+        let mut buf = String::new();
+        let mut prn = print::cell_printer(config, &mut buf);
+        print::to_cell(&mut prn, &None).get_content()
+    };
+
+    // defaults
+    let config = Config::new();
+    assert_eq!(
+        test_format_cfg(&objects, &config).unwrap(),
+        "{Object {field: {}}, Object {field: {}}}"
+    );
+    assert_eq!(
+        print::json_item_to_string(&serde_json::Value::Null, &config).unwrap(),
+        "null"
+    );
+    assert_eq!(cell_text(&config), "");
+    assert_eq!(
+        tab_separated::format_row(&objects[0], &config, Default::default()).unwrap(),
+        ""
+    );
+
+    // overrides
+    let mut config = Config::new();
+    config
+        .null_text("∅".into())
+        .empty_set_text("<empty>".into());
+    assert_eq!(
+        test_format_cfg(&objects, &config).unwrap(),
+        "{Object {field: ∅}, Object {field: <empty>}}"
+    );
+    // JSON output stays valid JSON
+    assert_eq!(
+        print::json_item_to_string(&serde_json::Value::Null, &config).unwrap(),
+        "null"
+    );
+    assert_eq!(cell_text(&config), "∅");
+    assert_eq!(
+        tab_separated::format_row(&objects[0], &config, Default::default()).unwrap(),
+        "∅"
+    );
+}
+
+#[test]
+fn config_overrides() {
+    let mut base = Config::new();
+    base.max_items(Some(100))
+        .max_string_length(Some(20))
+        .bytes_format(BytesFormat::Hex);
+
+    let config = Config::with_overrides(&base, ConfigOverrides::default());
+    assert_eq!(config.max_items, Some(100));
+    assert_eq!(config.max_string_length, Some(20));
+    assert_eq!(config.bytes_format, BytesFormat::Hex);
+
+    let config = Config::with_overrides(
+        &base,
+        ConfigOverrides {
+            max_items: Some(None),
+            bytes_format: Some(BytesFormat::Base64),
+            null_text: Some("NULL".into()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(config.max_items, None);
+    assert_eq!(config.bytes_format, BytesFormat::Base64);
+    assert_eq!(config.null_text.as_deref(), Some("NULL"));
+    // the rest is kept from the base
+    assert_eq!(config.max_string_length, Some(20));
+    assert_eq!(config.indent, base.indent);
+    assert_eq!(config.colors, base.colors);
+    assert_eq!(config.empty_set_text, None);
+    // the base is left untouched
+    assert_eq!(base.max_items, Some(100));
+}
+
+#[test]
+fn max_output_lines() {
+    let shape = ObjectShape::new(
+        (0..50)
+            .map(|i| ShapeElement {
+                flag_implicit: false,
+                flag_link_property: false,
+                flag_link: false,
+                cardinality: None,
+                name: format!("field{i}"),
+            })
+            .collect(),
+    );
+    let huge = Value::Object {
+        shape: shape.clone(),
+        fields: (0..50).map(|i| Some(Value::Int32(i))).collect(),
+    };
+    let rows = [huge.clone(), huge];
+    assert_eq!(
+        test_format_cfg(&rows, Config::new().max_output_lines(Some(4))).unwrap(),
+        r###"{
+  Object {
+    field0: 0,
+    field1: 1,
+(output truncated at 4 lines)"###
+    );
+
+    // output that fits exactly is not marked as truncated
+    let full = test_format_cfg(&rows, &Config::new()).unwrap();
+    let lines = full.lines().count();
+    assert_eq!(
+        test_format_cfg(&rows, Config::new().max_output_lines(Some(lines))).unwrap(),
+        full
+    );
+}
+
+#[test]
+fn trailing_comma() {
+    let shape = ObjectShape::new(vec![
+        ShapeElement {
+            flag_implicit: false,
+            flag_link_property: false,
+            flag_link: false,
+            cardinality: None,
+            name: "name".into(),
+        },
+        ShapeElement {
+            flag_implicit: false,
+            flag_link_property: false,
+            flag_link: false,
+            cardinality: None,
+            name: "description".into(),
+        },
+    ]);
+    let row = |name: &str| Value::Object {
+        shape: shape.clone(),
+        fields: vec![
+            Some(Value::Str(name.into())),
+            Some(Value::Str("x".repeat(60))),
+        ],
+    };
+    let rows = [row("first"), row("second")];
+    let description = "x".repeat(60);
+    assert_eq!(
+        test_format_cfg(&rows, &Config::new()).unwrap(),
+        format!(
+            r###"{{
+  Object {{
+    name: 'first',
+    description: '{description}',
+  }},
+  Object {{
+    name: 'second',
+    description: '{description}',
+  }},
+}}"###
+        )
+    );
+    assert_eq!(
+        test_format_cfg(&rows, Config::new().trailing_comma(false)).unwrap(),
+        format!(
+            r###"{{
+  Object {{
+    name: 'first',
+    description: '{description}'
+  }},
+  Object {{
+    name: 'second',
+    description: '{description}'
+  }}
+}}"###
+        )
+    );
+}
+
+#[test]
+fn ascii_only() {
+    let headers = ["ID", "Name"];
+    let rows = vec![vec!["1".into(), "a much longer name".into()]];
+    let render = |ascii_only| print::table(&headers, &rows).render_with(20, false, ascii_only);
+    assert_eq!(
+        render(false),
+        "\
+┌────┬─────────────┐
+│ ID │ Name        │
+├────┼─────────────┤
+│ 1  │ a much lon… │
+└────┴─────────────┘
+"
+    );
+    assert_eq!(
+        render(true),
+        "\
++----+-------------+
+| ID | Name        |
++----+-------------+
+| 1  | a much l... |
++----+-------------+
+"
+    );
+
+    let shape = ObjectShape::new(vec![ShapeElement {
+        flag_implicit: false,
+        flag_link_property: false,
+        flag_link: false,
+        cardinality: None,
+        name: "name".into(),
+    }]);
+    let objects = [Value::Object {
+        shape,
+        fields: vec![Some(Value::Str("hello".into()))],
+    }];
+    let mut config = Config::new();
+    config.max_string_length(Some(3)).colors(false);
+    assert_eq!(
+        test_format_cfg(&objects, &config).unwrap(),
+        "{Object {name: 'hel…(+2 chars)'}}"
+    );
+    let table = |config: &Config| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime
+            .block_on(print::_table_format(
+                UnfusedStream::new(&objects),
+                config,
+                80,
+                false,
+            ))
+            .unwrap()
+            .to_string()
+            .replace("\r\n", "\n")
+    };
+    assert_eq!(
+        table(config.clone().ascii_only(false)),
+        "\
+┌────────────────┐
+│ name           │
+├────────────────┤
+│ hel…(+2 chars) │
+└────────────────┘
+"
+    );
+
+    config.ascii_only(true);
+    assert_eq!(
+        test_format_cfg(&objects, &config).unwrap(),
+        "{Object {name: 'hel...(+2 chars)'}}"
+    );
+    assert_eq!(
+        table(&config),
+        "\
++------------------+
+| name             |
++------------------+
+| hel...(+2 chars) |
++------------------+
+"
+    );
+}
+
+#[test]
+fn json_envelope() {
+    let items = serde_json::from_str::<serde_json::Value>(r#"[1, {"a": "b"}]"#).unwrap();
+    let items = items.as_array().unwrap();
+    let mut config = Config::new();
+    assert_eq!(
+        print::json_to_string(items, &config).unwrap(),
+        r#"[1, {"a": "b"}]"#
+    );
+
+    config.json_envelope(Some("data".into()));
+    assert_eq!(
+        print::json_to_string(items, &config).unwrap(),
+        r#"{"data": [1, {"a": "b"}]}"#
+    );
+    config.json_envelope_count(true);
+    assert_eq!(
+        print::json_to_string(items, &config).unwrap(),
+        r#"{"data": [1, {"a": "b"}], "count": 2}"#
+    );
+    assert_eq!(
+        print::json_to_string::<serde_json::Value>(&[], &config).unwrap(),
+        r#"{"data": [], "count": 0}"#
+    );
+    assert_eq!(
+        print::json_to_string(items, config.max_width(30)).unwrap(),
+        r#"{
+  "data": [1, {"a": "b"}],
+  "count": 2
+}"#
+    );
+    // the key is escaped
+    config.json_envelope(Some("my \"rows\"".into()));
+    assert_eq!(
+        print::json_to_string(items, config.max_width(80)).unwrap(),
+        r#"{"my \"rows\"": [1, {"a": "b"}], "count": 2}"#
+    );
+}
+
+#[test]
+fn output_format_for_path() {
+    use crate::repl::OutputFormat;
+    use std::path::Path;
+
+    let format = |name: &str| print::output_format_for_path(Path::new(name)).unwrap();
+    assert_eq!(format("results.json"), Some(OutputFormat::Json));
+    assert_eq!(format("out/results.JSON"), Some(OutputFormat::Json));
+    assert_eq!(format("results.ndjson"), Some(OutputFormat::JsonLines));
+    assert_eq!(format("results.jsonl"), Some(OutputFormat::JsonLines));
+    assert_eq!(format("results.tsv"), Some(OutputFormat::TabSeparated));
+    assert_eq!(format("data.edgeql"), Some(OutputFormat::Edgeql));
+    assert_eq!(format("data.parquet"), Some(OutputFormat::Parquet));
+    assert_eq!(format("rows.msgpack"), Some(OutputFormat::Msgpack));
+    assert_eq!(format("results.txt"), None);
+    assert_eq!(format("results"), None);
+    for name in ["results.csv", "results.yaml", "results.YML"] {
+        let err = print::output_format_for_path(Path::new(name)).unwrap_err();
+        assert!(err.to_string().contains("--output-format"), "{err}");
+    }
+}
+
+#[test]
+fn success_json_line() {
+    use serde_json::json;
+
+    let line = print::success_json_line(json!({
+        "command": "instance create",
+        "instance": "inst1",
+    }));
+    assert_eq!(
+        line,
+        r#"{"level":"success","command":"instance create","instance":"inst1"}"#
+    );
+    let line = print::success_json_line(json!({
+        "command": "restore",
+        "databases": ["main", "other"],
+    }));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+        json!({"level": "success", "command": "restore", "databases": ["main", "other"]}),
+    );
+    // `level` can't be overridden
+    assert_eq!(
+        print::success_json_line(json!({"level": "error"})),
+        r#"{"level":"success"}"#
+    );
+    assert_eq!(
+        print::success_json_line(json!("done")),
+        r#"{"level":"success","value":"done"}"#
+    );
+}
+
+#[test]
+fn settings_schema() {
+    use crate::print::settings::{self, Settings};
+
+    let schema = settings::schema();
+    let root: schemars::schema::RootSchema = serde_json::from_value(schema.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&root).unwrap(), schema);
+    let properties = &root.schema.object.as_ref().unwrap().properties;
+    for name in [
+        "colors",
+        "indent",
+        "max-width",
+        "max-items",
+        "max-vector-length",
+    ] {
+        assert!(properties.contains_key(name), "{name} is missing");
+    }
+
+    let mut config = Config::new();
+    config
+        .max_items(Some(10))
+        .max_vector_length(VectorLimit::Fixed(3));
+    let value = serde_json::to_value(Settings::from(&config)).unwrap();
+    assert_eq!(value["max-vector-length"], "3");
+    assert_eq!(value["bytes-format"], "escaped");
+    let parsed: Settings = serde_json::from_value(value).unwrap();
+    assert_eq!(parsed, Settings::from(&config));
+}
+
+#[test]
+fn error_emphasis_threshold() {
+    let short = "cannot connect";
+    let long = "cannot connect to the instance: connection refused, is it running?";
+    assert!(print::emphasize_error(short, 60));
+    assert!(!print::emphasize_error(long, 60));
+    assert!(print::emphasize_error(&"x".repeat(60), 60));
+    assert!(!print::emphasize_error(&"x".repeat(61), 60));
+    // custom thresholds
+    assert!(!print::emphasize_error(short, 10));
+    assert!(print::emphasize_error(long, 1000));
+    assert!(!print::emphasize_error(short, 0));
+}
+
+#[test]
+fn max_vector_length_env() {
+    use crate::cli::env::parse;
+
+    assert_eq!(
+        parse::<VectorLimit>("1536").unwrap(),
+        VectorLimit::Fixed(1536)
+    );
+    assert_eq!(parse::<VectorLimit>("0").unwrap(), VectorLimit::Fixed(0));
+    assert_eq!(
+        parse::<VectorLimit>("unlimited").unwrap(),
+        VectorLimit::Unlimited
+    );
+    assert_eq!(
+        parse::<VectorLimit>("-1").unwrap_err().to_string(),
+        "Invalid value: expected integer, `HEAD..TAIL`, `unlimited` or `auto`"
+    );
+    assert_eq!(
+        parse::<VectorLimit>("2..3").unwrap(),
+        VectorLimit::HeadTail(2, 3)
+    );
+    assert!(parse::<VectorLimit>("2..").is_err());
+    assert!(parse::<VectorLimit>("many").is_err());
+}
+
+#[test]
+fn json_style() {
+    let item = serde_json::json!({"name": "a long enough value", "tags": ["x"]});
+    let mut config = Config::new();
+    config.max_width(20);
+    assert_eq!(
+        print::json_item_to_string(&item, &config).unwrap(),
+        r#"{
+  "name": "a long enough value",
+  "tags": ["x"]
+}"#
+    );
+    config.indent(4);
+    assert_eq!(
+        print::json_item_to_string(&item, &config).unwrap(),
+        r#"{
+    "name": "a long enough value",
+    "tags": ["x"]
+}"#
+    );
+
+    config.json_style(JsonStyle::Compact);
+    let out = print::json_item_to_string(&item, &config).unwrap();
+    assert!(!out.contains('\n'), "{out}");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&out).unwrap(),
+        item
+    );
+    let out = print::json_to_string(&[item.clone(), item.clone()], &config).unwrap();
+    assert!(!out.contains('\n'), "{out}");
+}
+
+#[test]
+fn confirm_overwrite_without_asking() {
+    let details = ["still in use".to_string()];
+    assert!(print::confirm_overwrite("remove it", &details, true, false).unwrap());
+    assert!(print::confirm_overwrite("remove it", &details, true, true).unwrap());
+    assert!(!print::confirm_overwrite("remove it", &details, false, true).unwrap());
+}
+
+#[test]
+fn query_echo() {
+    let query = "select {1, 2};";
+    let mut config = Config::new();
+    config.colors(true).max_width(80);
+    assert_eq!(print::query_echo(query, &config), None);
+
+    config.echo_query(true);
+    let echo = print::query_echo(&format!("\n{query}\n"), &config).unwrap();
+    let mut highlighted = String::new();
+    crate::highlight::edgeql(&mut highlighted, query, &config.styler);
+    assert_eq!(echo, format!("{highlighted}\n"));
+
+    let transcript = echo + &test_format_cfg(&[Value::Int64(1), Value::Int64(2)], &config).unwrap();
+    assert!(transcript.starts_with(&highlighted), "{transcript}");
+    assert!(transcript.ends_with("{1, 2}"), "{transcript}");
+
+    config.colors(false);
+    assert_eq!(
+        print::query_echo(query, &config).as_deref(),
+        Some("select {1, 2};\n")
+    );
+}
+
+#[test]
+fn empty_result_note() {
+    let count = |items: &[Value], config: &Config| {
+        let mut out = String::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let rows = runtime
+            .block_on(_native_format(
+                UnfusedStream::new(items),
+                config,
+                80,
+                false,
+                false,
+                &mut out,
+            ))
+            .unwrap();
+        (out, rows)
+    };
+    let mut config = Config::new();
+    config.colors(false);
+    let empty: &[Value] = &[];
+    let rows = &[Value::Int64(1), Value::Int64(2)];
+
+    assert_eq!(count(empty, &config), ("{}".to_string(), 0));
+    assert_eq!(count(rows, &config), ("{1, 2}".to_string(), 2));
+    assert_eq!(print::empty_result_note(0, &config), None);
+    assert_eq!(print::empty_result_note(2, &config), None);
+
+    // the output is the same, only the note is added
+    config.empty_result_note(true);
+    assert_eq!(count(empty, &config), ("{}".to_string(), 0));
+    assert_eq!(count(rows, &config), ("{1, 2}".to_string(), 2));
+    assert_eq!(
+        print::empty_result_note(0, &config).as_deref(),
+        Some("(no rows)")
+    );
+    assert_eq!(print::empty_result_note(2, &config), None);
+}
+
+#[test]
+fn msgpack_round_trip() {
+    use rmpv::Value as Mp;
+
+    let element = |name: &str, flag_implicit: bool| ShapeElement {
+        flag_implicit,
+        flag_link_property: false,
+        flag_link: false,
+        cardinality: None,
+        name: name.into(),
+    };
+    let shape = ObjectShape::new(vec![
+        element("id", true),
+        element("name", false),
+        element("data", false),
+        element("score", false),
+        element("total", false),
+        element("meta", false),
+        element("tags", false),
+        element("nickname", false),
+    ]);
+    let row = Value::Object {
+        shape,
+        fields: vec![
+            Some(Value::Uuid(
+                "8a2c0c34-7aa0-11ee-8d8e-7f8b5ad2c7a0".parse().unwrap(),
+            )),
+            Some(Value::Str("alice".into())),
+            Some(Value::Bytes(Bytes::from_static(b"\x00\xff"))),
+            Some(Value::Decimal(
+                TryFrom::try_from(BigDecimal::from_str("12.5").unwrap()).unwrap(),
+            )),
+            Some(Value::BigInt(100000000000i64.into())),
+            Some(Value::Json(Json::new_unchecked(
+                r#"{"a": [1, true]}"#.into(),
+            ))),
+            Some(Value::Array(vec![
+                Value::Int32(1),
+                Value::Float64(0.5),
+                Value::Bool(false),
+            ])),
+            None,
+        ],
+    };
+
+    let mut data = Vec::new();
+    for row in [&row, &Value::Int64(7)] {
+        data.extend(print::msgpack::encode_row(row).unwrap());
+    }
+    let mut input = &data[..];
+    let mut read = || {
+        let len = u32::from_be_bytes(input[..4].try_into().unwrap()) as usize;
+        let mut item = &input[4..4 + len];
+        input = &input[4 + len..];
+        let value = rmpv::decode::read_value(&mut item).unwrap();
+        assert!(item.is_empty());
+        value
+    };
+
+    let object = read();
+    let fields = object
+        .as_map()
+        .unwrap()
+        .iter()
+        .map(|(key, value)| (key.as_str().unwrap(), value.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            ("name", Mp::from("alice")),
+            ("data", Mp::Binary(vec![0, 255])),
+            ("score", Mp::from("12.5")),
+            ("total", Mp::from("100000000000")),
+            (
+                "meta",
+                Mp::Map(vec![(
+                    Mp::from("a"),
+                    Mp::Array(vec![Mp::from(1), Mp::from(true)])
+                )])
+            ),
+            (
+                "tags",
+                Mp::Array(vec![Mp::from(1), Mp::F64(0.5), Mp::from(false)])
+            ),
+            ("nickname", Mp::Nil),
+        ]
+    );
+    assert_eq!(read(), Mp::from(7));
+    assert!(input.is_empty());
+}
@@ -16,7 +16,17 @@ use tokio::process::Command;
 
 use crate::interrupt;
 use crate::platform::tmp_file_path;
-use crate::print::Highlight;
+use crate::print::{self, Highlight};
+
+/// Target for the command lines of WSL subprocesses. They are logged at
+/// debug level: `RUST_LOG=edgedb::wsl=debug`
+const WSL_LOG_TARGET: &str = "edgedb::wsl";
+
+/// Flags whose value is replaced by `<redacted>` in logged command lines
+const SECRET_FLAGS: &[&str] = &["--password", "--secret-key", "--token"];
+
+/// `NAME=value` arguments are redacted if the name contains one of these
+const SECRET_NAMES: &[&str] = &["PASSWORD", "SECRET", "TOKEN"];
 
 #[cfg(unix)]
 static HAS_UTF8_LOCALE: Lazy<bool> = Lazy::new(|| {
@@ -157,6 +167,30 @@ impl<I: IntoArg, T: IntoIterator<Item = I>> IntoArgs for T {
     }
 }
 
+/// Command-line arguments with the values of secrets replaced, for logging
+fn redact_args(args: &[OsString]) -> Vec<Cow<'_, str>> {
+    let mut redact_next = false;
+    args.iter()
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if std::mem::take(&mut redact_next) {
+                return "<redacted>".into();
+            }
+            if SECRET_FLAGS.contains(&&*arg) {
+                redact_next = true;
+                return arg;
+            }
+            if let Some((name, _)) = arg.split_once('=') {
+                let upper = name.to_uppercase();
+                if SECRET_NAMES.iter().any(|secret| upper.contains(secret)) {
+                    return format!("{name}=<redacted>").into();
+                }
+            }
+            arg
+        })
+        .collect()
+}
+
 fn block_on<T>(f: impl Future<Output = anyhow::Result<T>>) -> anyhow::Result<T> {
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .thread_name("process-run")
@@ -266,6 +300,17 @@ impl Native {
         self
     }
 
+    fn log_wsl_command(&self) {
+        if self.program == "wsl" && log::log_enabled!(target: WSL_LOG_TARGET, log::Level::Debug) {
+            log::debug!(
+                target: WSL_LOG_TARGET,
+                "{}: argv={:?}",
+                self.description,
+                redact_args(&self.args)
+            );
+        }
+    }
+
     pub fn command_line(&self) -> impl fmt::Debug + '_ {
         &self.command
     }
@@ -364,11 +409,71 @@ impl Native {
     pub async fn run_for_status(&mut self) -> anyhow::Result<ExitStatus> {
         self._run(false, false).await.map(|x| x.status)
     }
+    /// Like [`Native::run`], for processes that may not stop on Ctrl+C,
+    /// such as commands run inside WSL. The first Ctrl+C is left to the
+    /// process, the second one kills it and fails with
+    /// [`interrupt::InterruptError`].
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn run_abortable(&mut self) -> anyhow::Result<()> {
+        let status = block_on(self._run_abortable())?;
+        if status.success() {
+            Ok(())
+        } else {
+            anyhow::bail!(
+                "{} failed: {} (command-line: {:?})",
+                self.description,
+                status,
+                self.command
+            );
+        }
+    }
+
+    async fn _run_abortable(&mut self) -> anyhow::Result<ExitStatus> {
+        let intr = interrupt::Interrupt::ctrl_c();
+        log::info!(
+            "Running {}: {:?}",
+            self.description,
+            redact_args(&self.args)
+        );
+        self.log_wsl_command();
+        let mut child = self.command.spawn().with_context(|| {
+            format!(
+                "{} failed to start (command-line: {:?})",
+                self.description, self.command
+            )
+        })?;
+        let intr = &intr;
+        let description = &self.description;
+        let result = tokio::select! {
+            res = child.wait() => Ok(res),
+            () = interrupted_twice(move || intr.wait(), description) => Err(()),
+        };
+        match result {
+            Ok(res) => res.with_context(|| {
+                format!(
+                    "failed to get status of {} (command-line: {:?})",
+                    self.description, self.command
+                )
+            }),
+            Err(()) => {
+                log::warn!("Killing {}", self.description);
+                if let Err(e) = child.kill().await {
+                    log::error!("Error stopping {}: {}", self.description, e);
+                }
+                Err(interrupt::InterruptError(interrupt::Signal::Interrupt).into())
+            }
+        }
+    }
 
     async fn _run(&mut self, capture_out: bool, capture_err: bool) -> anyhow::Result<Output> {
         let mut stdout = Vec::new();
         let mut stderr = Vec::new();
-        log::info!("Running {}: {:?}", self.description, self.command);
+        log::info!(
+            "Running {}: {:?}",
+            self.description,
+            redact_args(&self.args)
+        );
+        self.log_wsl_command();
         if capture_out || self.proxy {
             self.command.stdout(Stdio::piped());
         }
@@ -417,7 +522,12 @@ impl Native {
     async fn _daemonize(&mut self) -> anyhow::Result<Vec<u8>> {
         let term = interrupt::Interrupt::term();
         let mut stdout = Vec::new();
-        log::info!("Daemonizing {}: {:?}", self.description, self.command);
+        log::info!(
+            "Daemonizing {}: {:?}",
+            self.description,
+            redact_args(&self.args)
+        );
+        self.log_wsl_command();
         self.command.stdout(Stdio::piped());
         let mut child = self.command.spawn().with_context(|| {
             format!(
@@ -473,7 +583,12 @@ impl Native {
 
     async fn _status(&mut self) -> anyhow::Result<ExitStatus> {
         let term = interrupt::Interrupt::term();
-        log::info!("Running {}: {:?}", self.description, self.command);
+        log::info!(
+            "Running {}: {:?}",
+            self.description,
+            redact_args(&self.args)
+        );
+        self.log_wsl_command();
         self.command.stdout(Stdio::null());
         self.command.stderr(Stdio::null());
         let mut child = self.command.spawn().with_context(|| {
@@ -512,7 +627,12 @@ impl Native {
     {
         let f = f()?;
         let term = interrupt::Interrupt::term();
-        log::info!("Running {}: {:?}", self.description, self.command);
+        log::info!(
+            "Running {}: {:?}",
+            self.description,
+            redact_args(&self.args)
+        );
+        self.log_wsl_command();
         if self.proxy {
             self.command.stdout(Stdio::piped());
             self.command.stderr(Stdio::piped());
@@ -547,7 +667,12 @@ impl Native {
 
     async fn _feed(&mut self, data: &[u8]) -> anyhow::Result<()> {
         let term = interrupt::Interrupt::term();
-        log::info!("Running {}: {:?}", self.description, self.command);
+        log::info!(
+            "Running {}: {:?}",
+            self.description,
+            redact_args(&self.args)
+        );
+        self.log_wsl_command();
         self.command.stdin(Stdio::piped());
         if self.proxy {
             self.command.stdout(Stdio::piped());
@@ -797,7 +922,8 @@ impl Native {
         use nix::unistd::execve;
         use std::ffi::CString;
         use std::os::unix::ffi::OsStrExt;
-        log::debug!("Replacing CLI with {:?}", self.command);
+        log::debug!("Replacing CLI with {:?}", redact_args(&self.args));
+        self.log_wsl_command();
 
         fn env_pair(key: &OsStr, val: &OsStr) -> anyhow::Result<CString> {
             let mut cstr = Vec::with_capacity(key.len() + val.len() + 2);
@@ -909,6 +1035,14 @@ async fn feed_data<Never>(mut inp: impl io::AsyncWrite + Unpin, data: &[u8]) ->
     wait_forever().await
 }
 
+/// Resolves on the second signal `wait` returns. The first one only tells
+/// how to abort.
+async fn interrupted_twice<F: Future>(mut wait: impl FnMut() -> F, description: &str) {
+    wait().await;
+    print::warn!("Waiting for the {description} process to exit. Press Ctrl+C again to kill it.");
+    wait().await;
+}
+
 async fn wait_forever() -> ! {
     pending::<()>().await;
     unreachable!();
@@ -942,3 +1076,56 @@ fn _write_pid_file(path: &Path, pid: u32) -> anyhow::Result<()> {
     fs::rename(&tmp_path, path)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn abort_on_second_interrupt() {
+        use futures_util::poll;
+        use tokio::sync::Notify;
+
+        let notify = Notify::new();
+        let signals = &notify;
+        let mut aborted = std::pin::pin!(interrupted_twice(
+            move || signals.notified(),
+            "update certificates"
+        ));
+        assert!(poll!(&mut aborted).is_pending());
+        notify.notify_one();
+        assert!(poll!(&mut aborted).is_pending());
+        notify.notify_one();
+        assert!(poll!(&mut aborted).is_ready());
+    }
+
+    #[test]
+    fn redact_secret_args() {
+        let args = [
+            "wsl",
+            "--distribution",
+            "EdgeDB.2",
+            "_EDGEDB_FROM_WINDOWS=1",
+            "GEL_SERVER_PASSWORD=hunter2",
+            "/usr/bin/edgedb",
+            "--password",
+            "hunter2",
+            "--secret-key=nbwt1_abc",
+        ]
+        .map(OsString::from);
+        assert_eq!(
+            redact_args(&args),
+            [
+                "wsl",
+                "--distribution",
+                "EdgeDB.2",
+                "_EDGEDB_FROM_WINDOWS=1",
+                "GEL_SERVER_PASSWORD=<redacted>",
+                "/usr/bin/edgedb",
+                "--password",
+                "<redacted>",
+                "--secret-key=<redacted>",
+            ]
+        );
+    }
+}
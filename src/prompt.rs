@@ -21,9 +21,8 @@ use crate::commands::backslash;
 use crate::completion;
 use crate::highlight;
 use crate::platform::editor_path;
-use crate::platform::pager_path;
-use crate::print::Highlight;
 use crate::print::style::Styler;
+use crate::print::{self, Highlight};
 use crate::prompt::variable::{InputFlags, VariableInput};
 use crate::repl::{FAILURE_MARKER, TX_MARKER};
 use edgeql_parser::preparser::full_statement;
@@ -436,31 +435,38 @@ pub fn main(mut control: Receiver<Control>) -> Result<(), anyhow::Error> {
 }
 
 fn show_history(history: &dyn History) -> Result<(), anyhow::Error> {
-    let pager = pager_path()?;
-    let mut items = pager.split_whitespace();
-    let mut cmd = Command::new(items.next().unwrap());
+    let Some(pager) = print::pager_command() else {
+        return write_history(history, &mut std::io::stdout().lock());
+    };
+    let mut cmd = Command::new(&pager[0]);
     cmd.stdin(Stdio::piped());
-    cmd.args(items);
+    cmd.args(&pager[1..]);
     let mut child = cmd.spawn()?;
     let mut childin = child.stdin.take().expect("stdin is piped");
+    write_history(history, &mut childin)?;
+    drop(childin);
+    let res = child.wait()?;
+    if res.success() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("pager exited with: {}", res))
+    }
+}
+
+fn write_history(history: &dyn History, out: &mut dyn Write) -> Result<(), anyhow::Error> {
     for index in (0..history.len()).rev() {
         if let Ok(Some(s)) = history.get(index, rustyline::history::SearchDirection::Forward) {
             let prefix = format!("[-{}] ", history.len() - index);
             let mut lines = s.entry.lines();
             if let Some(first) = lines.next() {
-                writeln!(childin, "{prefix}{first}")?;
+                writeln!(out, "{prefix}{first}")?;
             }
             for next in lines {
-                writeln!(childin, "{:1$}{2}", "", prefix.len(), next)?;
+                writeln!(out, "{:1$}{2}", "", prefix.len(), next)?;
             }
         }
     }
-    let res = child.wait()?;
-    if res.success() {
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!("pager exited with: {}", res))
-    }
+    Ok(())
 }
 
 fn spawn_editor(data: &str) -> Result<String, anyhow::Error> {
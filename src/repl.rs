@@ -47,12 +47,31 @@ pub enum InputLanguage {
 #[value(rename_all = "kebab-case")]
 #[serde(rename_all = "kebab-case")]
 pub enum OutputFormat {
+    /// Native EdgeQL-like output, also `native`
+    #[value(alias = "native")]
     Default,
+    /// All results of a query as a single JSON array
     Json,
+    /// Every result as indented JSON
     JsonPretty,
+    /// Every result as JSON on a line of its own
     JsonLines,
+    /// Every result as a line of tab-separated values
     TabSeparated,
+    /// A table, also `table`; terminal only
+    #[value(alias = "table")]
     Tabular,
+    /// A layout picked based on the shape of the result; terminal only
+    Auto,
+    /// A `name = value` line per property of each object; terminal only
+    Kv,
+    /// Best-effort `INSERT` statements for each object
+    Edgeql,
+    /// Flat object results as a Parquet file; needs an output file
+    Parquet,
+    /// Every result as a length-prefixed MessagePack value; not to a
+    /// terminal
+    Msgpack,
 }
 
 #[derive(
@@ -81,6 +100,8 @@ pub enum VectorLimit {
     Unlimited,
     Auto,
     Fixed(usize),
+    /// First and last components, with the number of components after them
+    HeadTail(usize, usize),
 }
 
 pub struct PromptRpc {
@@ -454,24 +475,33 @@ impl From<InputLanguage> for ServerInputLanguage {
 
 impl std::str::FromStr for OutputFormat {
     type Err = anyhow::Error;
+    /// Accepts the same values, including aliases, as the command-line
+    /// options.
     fn from_str(s: &str) -> Result<OutputFormat, anyhow::Error> {
-        match s {
-            "json" => Ok(OutputFormat::Json),
-            "json-pretty" => Ok(OutputFormat::JsonPretty),
-            "json-lines" => Ok(OutputFormat::JsonLines),
-            "tab-separated" => Ok(OutputFormat::TabSeparated),
-            "default" => Ok(OutputFormat::Default),
-            _ => Err(anyhow::anyhow!("unsupported output mode {:?}", s)),
-        }
+        <OutputFormat as clap::ValueEnum>::from_str(s, false).map_err(|_| {
+            let valid = <OutputFormat as clap::ValueEnum>::value_variants()
+                .iter()
+                .map(|v| v.as_str())
+                .collect::<Vec<_>>();
+            anyhow::anyhow!(
+                "unsupported output mode {s:?}, valid values are: {}",
+                valid.join(", ")
+            )
+        })
     }
 }
 
 impl From<OutputFormat> for IoFormat {
     fn from(val: OutputFormat) -> Self {
         match val {
-            OutputFormat::Default | OutputFormat::TabSeparated | OutputFormat::Tabular => {
-                IoFormat::Binary
-            }
+            OutputFormat::Default
+            | OutputFormat::TabSeparated
+            | OutputFormat::Tabular
+            | OutputFormat::Auto
+            | OutputFormat::Kv
+            | OutputFormat::Edgeql
+            | OutputFormat::Parquet
+            | OutputFormat::Msgpack => IoFormat::Binary,
             OutputFormat::JsonLines | OutputFormat::JsonPretty => IoFormat::JsonElements,
             OutputFormat::Json => IoFormat::Json,
         }
@@ -520,6 +550,11 @@ impl OutputFormat {
             JsonLines => "json-lines",
             TabSeparated => "tab-separated",
             Tabular => "tabular",
+            Auto => "auto",
+            Kv => "kv",
+            Edgeql => "edgeql",
+            Parquet => "parquet",
+            Msgpack => "msgpack",
         }
     }
 }
@@ -541,10 +576,13 @@ impl std::str::FromStr for VectorLimit {
         match s {
             "unlimited" => Ok(VectorLimit::Unlimited),
             "auto" => Ok(VectorLimit::Auto),
-            _ => s
-                .parse()
-                .map(VectorLimit::Fixed)
-                .map_err(|_| "expected integer, `unlimited` or `auto`"),
+            _ => match s.split_once("..") {
+                Some((head, tail)) => head
+                    .parse()
+                    .and_then(|head| Ok(VectorLimit::HeadTail(head, tail.parse()?))),
+                None => s.parse().map(VectorLimit::Fixed),
+            }
+            .map_err(|_| "expected integer, `HEAD..TAIL`, `unlimited` or `auto`"),
         }
     }
 }
@@ -557,6 +595,7 @@ impl fmt::Display for VectorLimit {
             Unlimited => "unlimited".fmt(f),
             Auto => "auto".fmt(f),
             Fixed(x) => x.fmt(f),
+            HeadTail(head, tail) => write!(f, "{head}..{tail}"),
         }
     }
 }
@@ -0,0 +1,164 @@
+//! Retrying failed operations with exponential backoff.
+
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Longest delay between attempts unless changed with [`Backoff::max_delay`]
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Part of the delay randomly added to each delay, so that clients that
+/// failed at the same time don't retry at the same time too.
+const JITTER: f64 = 0.25;
+
+/// Retry schedule: `attempts` attempts in total, with the delay doubled
+/// after every failed one, starting from `base_delay`.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Backoff {
+    pub const fn new(attempts: u32, base_delay: Duration) -> Backoff {
+        Backoff {
+            attempts,
+            base_delay,
+            max_delay: DEFAULT_MAX_DELAY,
+        }
+    }
+    pub const fn max_delay(self, max_delay: Duration) -> Backoff {
+        Backoff { max_delay, ..self }
+    }
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+    /// Delay after failed attempt number `attempt` (starting at 1), without
+    /// jitter.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+    /// [`Backoff::delay`] with random jitter added, still at most
+    /// `max_delay`.
+    pub fn jittered_delay(&self, attempt: u32) -> Duration {
+        jittered(
+            self.delay(attempt),
+            rand::thread_rng().r#gen(),
+            self.max_delay,
+        )
+    }
+    /// Runs `op` until it succeeds or all attempts are used, and returns
+    /// the last error then.
+    ///
+    /// After each failed attempt but the last `next_delay` is called with
+    /// the error, the number of the attempt and the backoff delay. It
+    /// returns the delay to actually wait, or `None` to return the error
+    /// without retrying. This is where errors are logged, or a delay
+    /// requested by the server is used.
+    pub async fn retry<T, E, F, Fut>(
+        &self,
+        mut next_delay: impl FnMut(&E, u32, Duration) -> Option<Duration>,
+        mut op: F,
+    ) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let err = match op().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            if attempt >= self.attempts {
+                return Err(err);
+            }
+            let Some(delay) = next_delay(&err, attempt, self.jittered_delay(attempt)) else {
+                return Err(err);
+            };
+            log::debug!("Attempt {attempt} failed, retrying in {delay:?}");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Adds `random` (from `0.0..1.0`) times [`JITTER`] of `delay` to it.
+fn jittered(delay: Duration, random: f64, max_delay: Duration) -> Duration {
+    delay
+        .mul_f64(1.0 + JITTER * random)
+        .min(max_delay.max(delay))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use super::{Backoff, jittered};
+
+    #[test]
+    fn backoff_schedule() {
+        let backoff = Backoff::new(10, Duration::from_secs(1));
+        let delays = (1..=8)
+            .map(|a| backoff.delay(a).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(60));
+
+        let backoff = backoff.max_delay(Duration::from_secs(5));
+        let delays = (1..=4)
+            .map(|a| backoff.delay(a).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [1, 2, 4, 5]);
+
+        let max = Duration::from_secs(60);
+        let delay = Duration::from_secs(4);
+        assert_eq!(jittered(delay, 0.0, max), delay);
+        assert_eq!(jittered(delay, 0.5, max), Duration::from_millis(4500));
+        assert_eq!(jittered(max, 0.9, max), max);
+        for attempt in 1..4 {
+            let delay = backoff.jittered_delay(attempt);
+            assert!(delay >= backoff.delay(attempt));
+            assert!(delay <= backoff.delay(attempt).mul_f64(1.25));
+        }
+    }
+
+    #[tokio::test]
+    async fn retryable_errors() {
+        let calls = Cell::new(0);
+        let op = || {
+            calls.set(calls.get() + 1);
+            let call = calls.get();
+            async move { if call < 3 { Err(call) } else { Ok("done") } }
+        };
+        let backoff = Backoff::new(5, Duration::ZERO);
+        assert_eq!(
+            backoff.retry(|_, _, delay| Some(delay), op).await,
+            Ok("done")
+        );
+        assert_eq!(calls.get(), 3);
+
+        // the last error is returned once attempts are exhausted
+        calls.set(0);
+        let res = Backoff::new(2, Duration::ZERO)
+            .retry(|_, _, delay| Some(delay), op)
+            .await;
+        assert_eq!(res, Err(2));
+        assert_eq!(calls.get(), 2);
+
+        // errors that aren't retryable are returned right away
+        calls.set(0);
+        let res = backoff
+            .retry(|err, _, delay| (*err != 2).then_some(delay), op)
+            .await;
+        assert_eq!(res, Err(2));
+        assert_eq!(calls.get(), 2);
+    }
+}
@@ -3,7 +3,23 @@ use prettytable::format::{Alignment, TableFormat};
 use prettytable::format::{FormatBuilder, LinePosition, LineSeparator};
 pub use prettytable::{Attr, Cell, Row, Table};
 
-pub static FORMAT: Lazy<TableFormat> = Lazy::new(|| {
+use crate::print;
+
+/// Box-drawing format, or the ASCII one if the terminal can't show it
+pub static FORMAT: Lazy<TableFormat> = Lazy::new(|| format(!print::use_utf8()));
+
+pub fn format(ascii_only: bool) -> TableFormat {
+    if ascii_only {
+        return FormatBuilder::new()
+            .column_separator('|')
+            .borders('|')
+            .separators(
+                &[LinePosition::Top, LinePosition::Title, LinePosition::Bottom],
+                LineSeparator::new('-', '+', '+', '+'),
+            )
+            .padding(1, 1)
+            .build();
+    }
     FormatBuilder::new()
         .column_separator('│')
         .borders('│')
@@ -18,7 +34,7 @@ pub static FORMAT: Lazy<TableFormat> = Lazy::new(|| {
         )
         .padding(1, 1)
         .build()
-});
+}
 
 pub fn header_cell(title: &str) -> Cell {
     Cell::new_align(title, Alignment::LEFT).with_style(Attr::Dim)
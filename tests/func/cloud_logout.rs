@@ -0,0 +1,41 @@
+use std::fs;
+
+fn setup_profile_with_project(config: &crate::Config) -> std::path::PathBuf {
+    let dir = config.path().join("edgedb");
+    let creds = dir.join("cloud-credentials");
+    fs::create_dir_all(&creds).expect("mkdir");
+    fs::write(creds.join("test-profile.json"), r#"{"secret_key": "xxx"}"#).expect("creds");
+
+    let stash = dir.join("projects").join("project-0123456789abcdef");
+    fs::create_dir_all(&stash).expect("mkdir");
+    fs::write(stash.join("cloud-profile"), "test-profile").expect("stash");
+    fs::write(stash.join("project-path"), config.path().to_str().unwrap()).expect("stash");
+
+    creds.join("test-profile.json")
+}
+
+#[test]
+fn logout_all_profiles_with_project_refs() {
+    let config = crate::Config::new("");
+    let creds = setup_profile_with_project(&config);
+
+    crate::edgedb_cli_cmd()
+        .env("XDG_CONFIG_HOME", config.path())
+        .args(["cloud", "logout", "--all-profiles", "--non-interactive"])
+        .assert()
+        .failure();
+    assert!(creds.exists());
+
+    crate::edgedb_cli_cmd()
+        .env("XDG_CONFIG_HOME", config.path())
+        .args([
+            "cloud",
+            "logout",
+            "--all-profiles",
+            "--non-interactive",
+            "--ignore-project-refs",
+        ])
+        .assert()
+        .success();
+    assert!(!creds.exists());
+}
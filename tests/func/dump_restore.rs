@@ -125,3 +125,104 @@ fn dump_restore_all() {
         .stdout("\"world\"\n");
     new_instance.0.stop();
 }
+
+#[test]
+fn restore_all_init_dir() {
+    let tempdir = tempfile::tempdir().unwrap();
+    // retried for the same reason as in `dump_restore_all`
+    let mut retry = 0;
+    let res = loop {
+        let r = SERVER
+            .admin_cmd()
+            .arg("dump")
+            .arg("--all")
+            .arg("--format=dir")
+            .arg(tempdir.path())
+            .ok();
+        if r.is_err() && retry < 5 {
+            retry += 1;
+            continue;
+        }
+        break r;
+    };
+    res.unwrap();
+
+    // split the init script, the second file depends on the first one
+    let init = tempdir.path().join("init");
+    std::fs::create_dir(&init).unwrap();
+    std::fs::rename(
+        tempdir.path().join("init.edgeql"),
+        init.join("000-dump.edgeql"),
+    )
+    .unwrap();
+    std::fs::write(
+        init.join("001-create-role.edgeql"),
+        "CREATE SUPERUSER ROLE init_dir_role { SET password := 'first' };",
+    )
+    .unwrap();
+    std::fs::write(
+        init.join("002-alter-role.edgeql"),
+        "ALTER ROLE init_dir_role { SET password := 'second' };",
+    )
+    .unwrap();
+
+    let new_instance = ServerGuard(ServerInstance::start());
+    new_instance
+        .admin_cmd()
+        .arg("restore")
+        .arg("--all")
+        .arg(tempdir.path())
+        .assert()
+        .success();
+    new_instance
+        .admin_cmd()
+        .arg("query")
+        .arg("SELECT count((SELECT sys::Role FILTER .name = 'init_dir_role'))")
+        .assert()
+        .success()
+        .stdout("1\n");
+    new_instance.0.stop();
+}
+
+#[test]
+fn restore_into_two_branches() {
+    let tempdir = tempfile::tempdir().unwrap();
+    SERVER
+        .admin_cmd()
+        .arg("database")
+        .arg("create")
+        .arg("dump_03")
+        .assert()
+        .success();
+    SERVER
+        .database_cmd("dump_03")
+        .arg("query")
+        .arg("CREATE TYPE Hello { CREATE REQUIRED PROPERTY name -> str; }")
+        .arg("INSERT Hello { name := 'world' }")
+        .assert()
+        .success();
+    SERVER
+        .database_cmd("dump_03")
+        .arg("dump")
+        .arg(tempdir.path().join("dump_03.dump"))
+        .assert()
+        .success();
+
+    // the targets don't exist yet and are created by the restore
+    SERVER
+        .admin_cmd()
+        .arg("restore")
+        .arg("--into=into_01,into_02")
+        .arg(tempdir.path().join("dump_03.dump"))
+        .assert()
+        .success();
+    for target in ["into_01", "into_02"] {
+        SERVER
+            .database_cmd(target)
+            .arg("query")
+            .arg("SELECT Hello.name")
+            .assert()
+            .success()
+            .stdout("\"world\"\n");
+    }
+}
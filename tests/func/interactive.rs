@@ -41,6 +41,23 @@ fn test_switch_database() {
     cmd.send_line("drop branch _test_switch_asdf;").unwrap();
 }
 
+#[test]
+fn switch_output_format() {
+    let mut cmd = SERVER.admin_interactive();
+    let main = SERVER.default_branch();
+
+    cmd.exp_string(&format!("{main}>")).unwrap();
+    cmd.send_line("\\output json-lines").unwrap();
+    cmd.exp_string("Output format set to json-lines").unwrap();
+    cmd.send_line("SELECT {a := 1};\n").unwrap();
+    cmd.exp_string(r#"{"a": 1}"#).unwrap();
+    cmd.exp_string(&format!("{main}>")).unwrap();
+    cmd.send_line("\\output native").unwrap();
+    cmd.exp_string("Output format set to default").unwrap();
+    cmd.send_line("\\output csv").unwrap();
+    cmd.exp_string("possible values").unwrap();
+}
+
 #[test]
 fn create_report() {
     let mut cmd = SERVER.admin_interactive();
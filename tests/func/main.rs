@@ -35,6 +35,10 @@ mod interactive;
 
 mod help;
 
+// cloud config location is only overridable via XDG_CONFIG_HOME on linux
+#[cfg(target_os = "linux")]
+mod cloud_logout;
+
 pub const BRANDING_CLI_CMD: &str = if cfg!(feature = "gel") {
     "gel"
 } else {